@@ -0,0 +1,338 @@
+//! Aging analysis to spot recycled chips.
+//!
+//! Transistors degrade with use: a chip that has spent time in the field runs slower and needs a
+//! higher voltage than it did when it was enrolled, while a chip sitting in a warehouse barely
+//! changes. Distributors can measure a chip as they sign it, and the central server compares every
+//! measurement in the chip's record with the chip data measured at enrollment.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+
+use crate::central_record::CentralRecord;
+use crate::chip_data::ChipData;
+use crate::DatabaseModel;
+
+/// Measurement a distributor took of a chip at its hop
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HopMeasurement {
+    pub freq: f32,
+    pub voltage: f32,
+    pub temp: f32,
+}
+
+impl HopMeasurement {
+    /// Size of the measurement in a central entry's canonical encoding
+    pub const SIZE: usize = 12;
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0..4].copy_from_slice(&self.freq.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.voltage.to_be_bytes());
+        bytes[8..12].copy_from_slice(&self.temp.to_be_bytes());
+        bytes
+    }
+
+    /// Every field is finite, and the frequency and voltage are positive. Temperatures can be
+    /// negative.
+    pub fn is_valid(&self) -> bool {
+        self.freq.is_finite()
+            && self.voltage.is_finite()
+            && self.temp.is_finite()
+            && self.freq > 0.0
+            && self.voltage > 0.0
+    }
+}
+
+impl From<&ChipData> for HopMeasurement {
+    /// Enrollment baseline of a chip
+    fn from(chip_data: &ChipData) -> Self {
+        Self {
+            freq: chip_data.freq,
+            voltage: chip_data.voltage,
+            temp: chip_data.temp,
+        }
+    }
+}
+
+/// How far a chip's measurements may drift from its baseline before it is taken to be recycled.
+///
+/// Drifts are relative to the baseline, so `0.03` is 3%. Fields missing from a config keep the
+/// default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgingModel {
+    /// Largest frequency drop put down to measurement noise
+    pub max_freq_drop: f32,
+    /// Largest voltage rise put down to measurement noise
+    pub max_voltage_rise: f32,
+    /// Relative frequency change per °C, frequencies are corrected to the baseline's temperature
+    pub freq_temp_coefficient: f32,
+    /// Measurements taken further than this from the baseline's temperature aren't compared
+    pub max_temp_delta: f32,
+    /// Number of aged measurements needed to flag a chip, more tolerates a faulty reader
+    pub min_aged_measurements: usize,
+}
+
+impl Default for AgingModel {
+    fn default() -> Self {
+        Self {
+            max_freq_drop: 0.02,
+            max_voltage_rise: 0.03,
+            freq_temp_coefficient: -0.0015,
+            max_temp_delta: 15.0,
+            min_aged_measurements: 1,
+        }
+    }
+}
+
+/// A hop's measurement compared with the baseline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeasurementDrift {
+    /// Index of the hop in the central record
+    pub hop: usize,
+    pub dist_id: u32,
+    pub measurement: HopMeasurement,
+    /// Relative frequency change after temperature correction, `None` if it couldn't be compared
+    pub freq_drift: Option<f32>,
+    /// Relative voltage change, `None` if it couldn't be compared
+    pub voltage_drift: Option<f32>,
+    /// The drift is beyond what the model allows for a new chip
+    pub aged: bool,
+}
+
+/// Every measurement of a chip compared with its enrollment baseline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgingReport {
+    pub chip_id: u128,
+    pub baseline: HopMeasurement,
+    pub drifts: Vec<MeasurementDrift>,
+    /// Enough measurements show aging to suspect the chip was used before
+    pub recycled: bool,
+}
+
+/// The central server stores the reports of chips flagged as recycled when it records their hops
+impl DatabaseModel for AgingReport {
+    type ID = u128;
+
+    fn id(&self) -> Self::ID {
+        self.chip_id
+    }
+
+    fn set_id(&mut self, id: Self::ID) {
+        self.chip_id = id
+    }
+
+    fn id_type_to_bytes(id: Self::ID) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u128::<LittleEndian>(id).unwrap();
+        bytes
+    }
+
+    fn tree() -> String {
+        "recycled_chip".to_string()
+    }
+}
+
+impl AgingModel {
+    /// Compare the measurements in `record` with the chip's baseline, `None` if it has no entries
+    pub fn analyze(&self, record: &CentralRecord) -> Option<AgingReport> {
+        let baseline = HopMeasurement::from(&record.entries.first()?.rfid_data.chip_data);
+
+        Some(self.report(record, baseline, None))
+    }
+
+    /// Same as `analyze`, with `measurement` taken by `dist_id` as the record's next hop. This lets a
+    /// distributor learn whether a chip is recycled before signing it.
+    ///
+    /// `None` if the record has no entries, the baseline is only taken from the recorded enrollment
+    /// and never from the tag being checked.
+    pub fn analyze_candidate(
+        &self,
+        record: &CentralRecord,
+        dist_id: u32,
+        measurement: HopMeasurement,
+    ) -> Option<AgingReport> {
+        let baseline = HopMeasurement::from(&record.entries.first()?.rfid_data.chip_data);
+
+        Some(self.report(
+            record,
+            baseline,
+            Some((record.entries.len(), dist_id, measurement)),
+        ))
+    }
+
+    fn report(
+        &self,
+        record: &CentralRecord,
+        baseline: HopMeasurement,
+        candidate: Option<(usize, u32, HopMeasurement)>,
+    ) -> AgingReport {
+        let drifts: Vec<MeasurementDrift> = record
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(hop, entry)| Some((hop, entry.dist_id, entry.measurement?)))
+            .chain(candidate)
+            .map(|(hop, dist_id, measurement)| self.drift(hop, dist_id, &baseline, measurement))
+            .collect();
+
+        let aged = drifts.iter().filter(|drift| drift.aged).count();
+
+        AgingReport {
+            chip_id: record.chip_id,
+            baseline,
+            recycled: aged > 0 && aged >= self.min_aged_measurements,
+            drifts,
+        }
+    }
+
+    fn drift(
+        &self,
+        hop: usize,
+        dist_id: u32,
+        baseline: &HopMeasurement,
+        measurement: HopMeasurement,
+    ) -> MeasurementDrift {
+        let temp_delta = measurement.temp - baseline.temp;
+        let comparable = temp_delta.abs() <= self.max_temp_delta;
+
+        let expected_freq = baseline.freq * (1.0 + self.freq_temp_coefficient * temp_delta);
+        let freq_drift = relative(measurement.freq, expected_freq).filter(|_| comparable);
+        let voltage_drift = relative(measurement.voltage, baseline.voltage).filter(|_| comparable);
+
+        let aged = freq_drift.is_some_and(|drift| drift < -self.max_freq_drop)
+            || voltage_drift.is_some_and(|drift| drift > self.max_voltage_rise);
+
+        MeasurementDrift {
+            hop,
+            dist_id,
+            measurement,
+            freq_drift,
+            voltage_drift,
+            aged,
+        }
+    }
+}
+
+/// Change from `baseline` to `value` relative to `baseline`
+fn relative(value: f32, baseline: f32) -> Option<f32> {
+    let drift = (value - baseline) / baseline;
+    Some(drift).filter(|drift| drift.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aging::{AgingModel, HopMeasurement};
+    use crate::central_record::CentralRecord;
+    use crate::rfid::RfidBuilder;
+    use crate::signer::file_key::FileKeySigner;
+    use crate::test_util::{ed25519_keypairs, key_map};
+
+    #[test]
+    fn test_aging() {
+        let keypairs = ed25519_keypairs(2);
+        let key_map = key_map(&keypairs);
+        let central = FileKeySigner::from_pkey(keypairs[1].clone()).unwrap();
+
+        let data = RfidBuilder::default()
+            .chip_data(42, 100e6, 1.0, 25.0, 5.0)
+            .build();
+        let measure = |freq: f32, voltage: f32, temp: f32| {
+            Some(HopMeasurement {
+                freq,
+                voltage,
+                temp,
+            })
+        };
+
+        let mut record = CentralRecord::new(42);
+        for measurement in [
+            None,
+            measure(100.2e6, 1.0, 25.0),
+            // Slower only because it is warmer
+            measure(98.5e6, 1.01, 35.0),
+            // Too far from the baseline's temperature to compare
+            measure(80e6, 1.0, -20.0),
+        ] {
+            record
+                .add_measured_entry(
+                    &central,
+                    0,
+                    0,
+                    key_map[&0].key.clone(),
+                    data.clone(),
+                    measurement,
+                )
+                .unwrap();
+        }
+        assert!(record.validate_chain(&key_map, key_map[&1].clone()).is_ok());
+
+        let model = AgingModel::default();
+        let report = model.analyze(&record).unwrap();
+        assert_eq!(report.baseline.freq, 100e6);
+        assert_eq!(report.drifts.len(), 3);
+        assert_eq!(report.drifts[0].hop, 1);
+        assert!(report.drifts[1].freq_drift.unwrap().abs() < 0.001);
+        assert_eq!(report.drifts[2].freq_drift, None);
+        assert!(!report.recycled);
+
+        // Slower and needing more voltage than any new chip
+        let mut aged = record.clone();
+        for measurement in [measure(96e6, 1.0, 25.0), measure(100e6, 1.05, 25.0)] {
+            aged.add_measured_entry(
+                &central,
+                0,
+                0,
+                key_map[&0].key.clone(),
+                data.clone(),
+                measurement,
+            )
+            .unwrap();
+        }
+        let report = model.analyze(&aged).unwrap();
+        assert!(report.drifts[3].aged && report.drifts[4].aged);
+        assert!(report.recycled);
+
+        // A hop can be checked before it is recorded
+        let candidate = model
+            .analyze_candidate(&record, 0, measure(96e6, 1.0, 25.0).unwrap())
+            .unwrap();
+        assert_eq!(candidate.drifts.last().unwrap().hop, record.entries.len());
+        assert!(candidate.recycled);
+        assert!(
+            !model
+                .analyze_candidate(&record, 0, measure(100e6, 1.0, 25.0).unwrap())
+                .unwrap()
+                .recycled
+        );
+
+        // Without a recorded enrollment there is nothing to compare with
+        assert!(model
+            .analyze_candidate(
+                &CentralRecord::new(42),
+                0,
+                measure(96e6, 1.0, 25.0).unwrap()
+            )
+            .is_none());
+
+        assert!(measure(100e6, 1.0, -5.0).unwrap().is_valid());
+        assert!(!measure(f32::NAN, 1.0, 25.0).unwrap().is_valid());
+        assert!(!measure(100e6, 0.0, 25.0).unwrap().is_valid());
+        assert!(!measure(100e6, 1.0, f32::INFINITY).unwrap().is_valid());
+
+        let tolerant = AgingModel {
+            min_aged_measurements: 3,
+            ..Default::default()
+        };
+        assert!(!tolerant.analyze(&aged).unwrap().recycled);
+        assert!(model.analyze(&CentralRecord::new(42)).is_none());
+
+        // Measurements are signed with the rest of the entry
+        let mut tampered = aged;
+        tampered.entries[3].measurement = measure(100e6, 1.0, 25.0);
+        assert_eq!(
+            tampered.validate_chain(&key_map, key_map[&1].clone()),
+            Err(3)
+        );
+    }
+}
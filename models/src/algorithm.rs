@@ -0,0 +1,115 @@
+use crate::crypto;
+#[cfg(feature = "std")]
+use openssl::ecdsa::EcdsaSig;
+#[cfg(feature = "std")]
+use openssl::error::ErrorStack;
+#[cfg(feature = "std")]
+use openssl::hash::MessageDigest;
+#[cfg(feature = "std")]
+use openssl::nid::Nid;
+#[cfg(feature = "std")]
+use openssl::pkey::{HasPublic, Id, PKey, PKeyRef, Private};
+#[cfg(feature = "std")]
+use openssl::rsa::Padding;
+#[cfg(feature = "std")]
+use openssl::sign::Signer;
+use serde::{Deserialize, Serialize};
+
+pub(crate) const P256_SCALAR_SIZE: usize = 32;
+
+/// Signature scheme used by a distributor key
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SignatureAlgorithm {
+    /// RSA-2048 PKCS#1 v1.5 over SHA3-256, 256 byte signatures
+    #[default]
+    RsaSha3,
+    /// Ed25519, 64 byte signatures
+    Ed25519,
+    /// ECDSA on P-256 over SHA-256, 64 byte signatures holding `r` and `s`
+    EcdsaP256,
+}
+
+impl SignatureAlgorithm {
+    pub fn signature_size(&self) -> usize {
+        match self {
+            SignatureAlgorithm::RsaSha3 => 256,
+            SignatureAlgorithm::Ed25519 => 64,
+            SignatureAlgorithm::EcdsaP256 => 2 * P256_SCALAR_SIZE,
+        }
+    }
+
+    /// Identifier stored on the tag
+    pub fn id(&self) -> u8 {
+        match self {
+            SignatureAlgorithm::RsaSha3 => 0,
+            SignatureAlgorithm::Ed25519 => 1,
+            SignatureAlgorithm::EcdsaP256 => 2,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(SignatureAlgorithm::RsaSha3),
+            1 => Some(SignatureAlgorithm::Ed25519),
+            2 => Some(SignatureAlgorithm::EcdsaP256),
+            _ => None,
+        }
+    }
+
+    /// Algorithm of a key, `None` if the key type isn't supported
+    #[cfg(feature = "std")]
+    pub fn of_key<T: HasPublic>(key: &PKeyRef<T>) -> Option<Self> {
+        match key.id() {
+            Id::RSA if key.bits() == 2048 => Some(SignatureAlgorithm::RsaSha3),
+            Id::ED25519 => Some(SignatureAlgorithm::Ed25519),
+            Id::EC => match key.ec_key().ok()?.group().curve_name() {
+                Some(Nid::X9_62_PRIME256V1) => Some(SignatureAlgorithm::EcdsaP256),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Algorithm of a PEM public key, `None` if the key can't be parsed or isn't supported
+    pub fn of_public_key(public_key: &[u8]) -> Option<Self> {
+        crypto::algorithm_of_public_key(public_key)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn sign(&self, key: &PKey<Private>, members: &[&[u8]]) -> Result<Vec<u8>, ErrorStack> {
+        match self {
+            SignatureAlgorithm::RsaSha3 => {
+                let mut signer = Signer::new(MessageDigest::sha3_256(), key)?;
+                signer.set_rsa_padding(Padding::PKCS1)?;
+
+                for member in members {
+                    signer.update(member)?;
+                }
+
+                signer.sign_to_vec()
+            }
+            SignatureAlgorithm::Ed25519 => {
+                Signer::new_without_digest(key)?.sign_oneshot_to_vec(&members.concat())
+            }
+            SignatureAlgorithm::EcdsaP256 => {
+                let mut signer = Signer::new(MessageDigest::sha256(), key)?;
+
+                for member in members {
+                    signer.update(member)?;
+                }
+
+                // Tags store the fixed size r || s form instead of DER
+                let signature = EcdsaSig::from_der(&signer.sign_to_vec()?)?;
+                let mut bytes = signature.r().to_vec_padded(P256_SCALAR_SIZE as i32)?;
+                bytes.extend(signature.s().to_vec_padded(P256_SCALAR_SIZE as i32)?);
+
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Check a signature against a PEM public key, any malformed input fails verification
+    pub fn verify(&self, public_key: &[u8], data: &[u8], signature: &[u8]) -> bool {
+        crypto::verify(*self, public_key, data, signature)
+    }
+}
@@ -1,10 +1,31 @@
 use crate::DatabaseModel;
+use crate::aging::HopMeasurement;
+use crate::checkpoint::Checkpoint;
+use crate::crypto::SHA3_256_SIZE;
+use crate::error::{CheckpointError, SignerError};
 use crate::key::PublicKey;
-use crate::rfid::RfidData;
+use crate::rfid::{RfidBuilder, RfidData};
+use crate::signer::Signer;
+use crate::supply_chain::SupplyChainEntry;
+use crate::tag_format::TagVersion;
 use crate::{deserialize_base64, serialize_base64, BlockChainEntry};
-use byteorder::{LittleEndian, WriteBytesExt};
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+use openssl::hash::{hash, MessageDigest};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// How the contents of a central entry were encoded when it was signed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum EntryEncoding {
+    /// Entries recorded before `Canonical` existed. Their signature covered the whole record
+    /// serialized as JSON and can't be checked anymore, `validate_chain` rejects them until the
+    /// record is migrated with `CentralRecord::resign`
+    #[default]
+    Json,
+    /// The encoding produced by `CentralEntry::encode`
+    Canonical,
+}
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct CentralEntry {
@@ -16,35 +37,74 @@ pub struct CentralEntry {
         deserialize_with = "deserialize_base64"
     )]
     pub signature: Vec<u8>,
+    #[serde(default)]
+    pub encoding: EntryEncoding,
+    /// Measurement the distributor took of the chip at this hop
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub measurement: Option<HopMeasurement>,
 }
 
 impl CentralEntry {
     pub fn new(
-        private_key: Vec<u8>,
+        signer: &dyn Signer,
         dist_id: u32,
         next_dist_id: u32,
         next_dist_pk: Vec<u8>,
         rfid_data: RfidData,
-        record_data: Vec<u8>,
-    ) -> Self {
-        let data = if record_data.is_empty() {
-            vec![serde_json::to_vec(&rfid_data).unwrap(), next_dist_pk]
-        } else {
-            vec![
-                record_data,
-                serde_json::to_vec(&rfid_data).unwrap(),
-                next_dist_pk,
-            ]
-        };
-
-        let signature = Self::create_signature(private_key, data);
-
-        Self {
+        measurement: Option<HopMeasurement>,
+        previous_hash: Vec<u8>,
+    ) -> Result<Self, SignerError> {
+        let mut entry = Self {
             dist_id,
             next_dist_id,
             rfid_data,
-            signature,
+            signature: vec![],
+            encoding: EntryEncoding::Canonical,
+            measurement,
+        };
+
+        entry.signature = signer.sign(&[&entry.signed_data(&previous_hash, &next_dist_pk)])?;
+
+        Ok(entry)
+    }
+
+    /// Canonical encoding of the entry's contents, excluding its signature:
+    ///
+    /// | Field          | Encoding                                          |
+    /// |----------------|---------------------------------------------------|
+    /// | `dist_id`      | `u32`, big endian                                 |
+    /// | `next_dist_id` | `u32`, big endian                                 |
+    /// | `rfid_data`    | the tag's binary format, as written to the tag    |
+    /// | `measurement`  | `HopMeasurement::to_bytes`, only if there is one  |
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u32::<BigEndian>(self.dist_id).unwrap();
+        bytes.write_u32::<BigEndian>(self.next_dist_id).unwrap();
+        bytes.extend(Into::<Vec<u8>>::into(self.rfid_data.clone()));
+
+        if let Some(measurement) = &self.measurement {
+            bytes.extend_from_slice(&measurement.to_bytes());
         }
+
+        bytes
+    }
+
+    /// Data covered by the entry's signature: the hash of the previous entry (left out for the
+    /// first entry), the entry's contents and the PEM public key of the next distributor
+    pub fn signed_data(&self, previous_hash: &[u8], next_dist_pk: &[u8]) -> Vec<u8> {
+        let mut data = previous_hash.to_vec();
+        data.extend(self.encode());
+        data.extend_from_slice(next_dist_pk);
+        data
+    }
+
+    /// SHA3-256 hash of the canonical encoding followed by the signature, the next entry in the
+    /// record signs it to link the two
+    pub fn hash(&self) -> Vec<u8> {
+        let mut bytes = self.encode();
+        bytes.extend_from_slice(&self.signature);
+
+        hash(MessageDigest::sha3_256(), &bytes).unwrap().to_vec()
     }
 }
 
@@ -92,27 +152,137 @@ impl CentralRecord {
 
     pub fn add_entry(
         &mut self,
-        private_key: Vec<u8>,
+        signer: &dyn Signer,
         dist_id: u32,
         next_dist_id: u32,
         next_dist_pk: Vec<u8>,
         rfid_data: RfidData,
-    ) {
-        let record_data: Vec<u8> = if self.entries.is_empty() {
-            Vec::new()
-        } else {
-            serde_json::to_vec(&self).unwrap()
-        };
+    ) -> Result<(), SignerError> {
+        self.add_measured_entry(signer, dist_id, next_dist_id, next_dist_pk, rfid_data, None)
+    }
+
+    /// Same as `add_entry`, also recording the distributor's measurement of the chip
+    pub fn add_measured_entry(
+        &mut self,
+        signer: &dyn Signer,
+        dist_id: u32,
+        next_dist_id: u32,
+        next_dist_pk: Vec<u8>,
+        rfid_data: RfidData,
+        measurement: Option<HopMeasurement>,
+    ) -> Result<(), SignerError> {
+        let previous_hash = self
+            .entries
+            .last()
+            .map(|entry| entry.hash())
+            .unwrap_or_default();
 
         let entry = CentralEntry::new(
-            private_key,
+            signer,
             dist_id,
             next_dist_id,
             next_dist_pk,
             rfid_data,
-            record_data,
-        );
-        self.entries.push(entry)
+            measurement,
+            previous_hash,
+        )?;
+        self.entries.push(entry);
+
+        Ok(())
+    }
+
+    /// Compact `rfid_data` into a single checkpoint entry signed over to `holder_key`.
+    ///
+    /// The record's last entry must hold exactly the tag being compacted and name its holder as the
+    /// next distributor, so every dropped entry stays in the record. `central_key` is the public key
+    /// of `signer`, registered under `CHECKPOINT_KEY_ID`.
+    pub fn checkpoint(
+        &self,
+        signer: &dyn Signer,
+        central_key: &PublicKey,
+        rfid_data: &RfidData,
+        holder_key: &PublicKey,
+    ) -> Result<RfidData, CheckpointError> {
+        let last_entry = match self.entries.last() {
+            Some(entry)
+                if entry.next_dist_id == holder_key.id
+                    && entry.rfid_data.entries == rfid_data.entries
+                    && entry.rfid_data.checkpoint == rfid_data.checkpoint
+                    && rfid_data.hops() == self.entries.len() =>
+            {
+                entry
+            }
+            _ => return Err(CheckpointError::NotRecorded),
+        };
+
+        let hops = u16::try_from(self.entries.len())
+            .map_err(|_| CheckpointError::TooManyHops(self.entries.len()))?;
+        let mut record_hash = [0u8; SHA3_256_SIZE];
+        record_hash.copy_from_slice(&last_entry.hash());
+        let checkpoint = Checkpoint { hops, record_hash };
+
+        let chip_data: Vec<u8> = rfid_data.chip_data.clone().into();
+        let entry = SupplyChainEntry::new(
+            signer,
+            holder_key.key.clone(),
+            checkpoint.signed_data(&chip_data),
+            central_key,
+        )?;
+
+        let compacted = RfidData {
+            chip_data: rfid_data.chip_data.clone(),
+            entries: vec![entry],
+            version: TagVersion::V1,
+            checkpoint: Some(checkpoint),
+            ..Default::default()
+        };
+
+        Ok(RfidBuilder::from(compacted).build())
+    }
+
+    /// Whether any entry still uses the pre-canonical JSON encoding
+    pub fn is_legacy(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.encoding == EntryEncoding::Json)
+    }
+
+    /// Re-sign every entry over the canonical encoding, migrating a record written before it
+    /// existed. The entries are signed again as they are, so only run this on records the central
+    /// server stored itself. `keys` must hold the key of every entry's next distributor, revoked
+    /// or not.
+    pub fn resign(
+        &mut self,
+        signer: &dyn Signer,
+        keys: &HashMap<u32, PublicKey>,
+    ) -> Result<(), SignerError> {
+        let mut resigned = CentralRecord::new(self.chip_id);
+
+        for entry in self.entries.iter() {
+            let next_dist_pk = keys.get(&entry.next_dist_id).ok_or_else(|| {
+                SignerError::KeyNotFound(format!("distributor {}", entry.next_dist_id))
+            })?;
+
+            resigned.add_measured_entry(
+                signer,
+                entry.dist_id,
+                entry.next_dist_id,
+                next_dist_pk.key.clone(),
+                entry.rfid_data.clone(),
+                entry.measurement,
+            )?;
+        }
+
+        *self = resigned;
+        Ok(())
+    }
+
+    /// Whether `checkpoint` was issued from this record
+    pub fn covers(&self, checkpoint: &Checkpoint) -> bool {
+        (checkpoint.hops as usize)
+            .checked_sub(1)
+            .and_then(|ndx| self.entries.get(ndx))
+            .is_some_and(|entry| entry.hash()[..] == checkpoint.record_hash[..])
     }
 
     pub fn validate_chain(
@@ -120,31 +290,24 @@ impl CentralRecord {
         keys: &HashMap<u32, PublicKey>,
         public_key: PublicKey,
     ) -> Result<(), usize> {
-        let mut data_buff = Vec::new();
-
         for (ndx, entry) in self.entries.iter().enumerate() {
-            data_buff.clear();
+            if entry.encoding == EntryEncoding::Json {
+                return Err(ndx);
+            }
+
             let next_public_key = if let Some(pub_key) = keys.get(&entry.next_dist_id) {
                 pub_key
             } else {
                 return Err(ndx);
             };
 
-            if ndx == 0 {
-                data_buff.extend_from_slice(&serde_json::to_vec(&entry.rfid_data).unwrap());
-                data_buff.extend_from_slice(&next_public_key.key);
-            } else {
-                let last_entry = &self.entries[ndx - 1];
-                let last_pub_key = keys.get(&last_entry.next_dist_id).unwrap();
-                data_buff.extend_from_slice(&crate::utility::hash_from_signature(
-                    &last_pub_key.key,
-                    &last_entry.signature,
-                ));
-                data_buff.extend_from_slice(&serde_json::to_vec(&entry.rfid_data).unwrap());
-                data_buff.extend_from_slice(&next_public_key.key);
-            }
+            let previous_hash = match ndx {
+                0 => vec![],
+                _ => self.entries[ndx - 1].hash(),
+            };
+            let data_buff = entry.signed_data(&previous_hash, &next_public_key.key);
 
-            if !entry.verify_signature(&data_buff, &public_key.key) {
+            if !entry.verify_signature(&data_buff, &public_key) {
                 return Err(ndx);
             }
         }
@@ -155,11 +318,20 @@ impl CentralRecord {
 
 #[cfg(test)]
 mod tests {
-    use crate::models::central_record::CentralRecord;
-    use crate::models::key::PublicKey;
-    use crate::models::rfid::RfidBuilder;
+    use crate::central_record::CentralRecord;
+    use crate::checkpoint::CHECKPOINT_KEY_ID;
+    use crate::error::CheckpointError;
+    use crate::key::PublicKey;
+    use crate::rfid::{RfidBuilder, RfidData};
+    use crate::rfid_ref::RfidDataRef;
+    use crate::signer::file_key::FileKeySigner;
+    use crate::signer::Signer;
+    use crate::tag_format::TagVersion;
+    use crate::test_util::public_key;
+    use openssl::pkey::{PKey, Private};
     use openssl::rsa::Rsa;
     use std::collections::HashMap;
+    use std::convert::TryFrom;
 
     #[test]
     fn test_central_record() {
@@ -180,7 +352,8 @@ mod tests {
                 key_id1,
                 keypair1.public_key_to_pem().unwrap(),
                 "1".to_string(),
-            ),
+            )
+            .unwrap(),
         );
         key_map.insert(
             key_id2,
@@ -188,7 +361,8 @@ mod tests {
                 key_id2,
                 keypair2.public_key_to_pem().unwrap(),
                 "2".to_string(),
-            ),
+            )
+            .unwrap(),
         );
         key_map.insert(
             key_id3,
@@ -196,7 +370,8 @@ mod tests {
                 key_id3,
                 keypair3.public_key_to_pem().unwrap(),
                 "3".to_string(),
-            ),
+            )
+            .unwrap(),
         );
 
         key_map.insert(
@@ -205,36 +380,234 @@ mod tests {
                 key_id4,
                 keypair4.public_key_to_pem().unwrap(),
                 "4".to_string(),
-            ),
+            )
+            .unwrap(),
         );
 
         let data = RfidBuilder::default()
             .chip_data(42, 5.0, 5.0, 5.0, 5.0)
             .add_entry(
-                keypair1.private_key_to_pem().unwrap(),
+                &FileKeySigner::new(keypair1.clone()).unwrap(),
                 key_id1,
                 key_id2,
                 &key_map,
             )
+            .unwrap()
             .add_entry(
-                keypair2.private_key_to_pem().unwrap(),
+                &FileKeySigner::new(keypair2.clone()).unwrap(),
                 key_id2,
                 key_id3,
                 &key_map,
             )
+            .unwrap()
             .build();
 
         let mut record = CentralRecord::default();
-        record.add_entry(
-            keypair4.private_key_to_pem().unwrap().to_vec(),
-            key_id2,
-            key_id3,
-            key_map.get(&key_id3).unwrap().key.clone(),
-            data,
-        );
+        record
+            .add_entry(
+                &FileKeySigner::new(keypair4.clone()).unwrap(),
+                key_id2,
+                key_id3,
+                key_map.get(&key_id3).unwrap().key.clone(),
+                data.clone(),
+            )
+            .unwrap();
+
+        assert!(record
+            .validate_chain(&key_map, key_map.get(&key_id4).unwrap().clone())
+            .is_ok());
+
+        let data = RfidBuilder::from(data)
+            .add_entry(
+                &FileKeySigner::new(keypair3.clone()).unwrap(),
+                key_id3,
+                key_id1,
+                &key_map,
+            )
+            .unwrap()
+            .build();
+
+        record
+            .add_entry(
+                &FileKeySigner::new(keypair4.clone()).unwrap(),
+                key_id3,
+                key_id1,
+                key_map.get(&key_id1).unwrap().key.clone(),
+                data,
+            )
+            .unwrap();
 
         assert!(record
             .validate_chain(&key_map, key_map.get(&key_id4).unwrap().clone())
             .is_ok());
+
+        let json = serde_json::to_string(&record).unwrap();
+        let parsed: CentralRecord = serde_json::from_str(&json).unwrap();
+        assert!(parsed
+            .validate_chain(&key_map, key_map.get(&key_id4).unwrap().clone())
+            .is_ok());
+
+        let mut tampered = record.clone();
+        tampered.entries[0].signature[0] ^= 1;
+        assert_eq!(
+            tampered.validate_chain(&key_map, key_map.get(&key_id4).unwrap().clone()),
+            Err(0)
+        );
+
+        // Swapping in a different, validly signed first entry breaks its link to the second
+        let mut other = CentralRecord::default();
+        other
+            .add_entry(
+                &FileKeySigner::new(keypair4.clone()).unwrap(),
+                key_id2,
+                key_id3,
+                key_map.get(&key_id3).unwrap().key.clone(),
+                record.entries[1].rfid_data.clone(),
+            )
+            .unwrap();
+
+        let mut tampered = record.clone();
+        tampered.entries[0] = other.entries[0].clone();
+        assert_eq!(
+            tampered.validate_chain(&key_map, key_map.get(&key_id4).unwrap().clone()),
+            Err(1)
+        );
+
+        // Records stored before the canonical encoding have no `encoding` field, they can't be
+        // validated until they are re-signed
+        let legacy_json = serde_json::to_string(&record)
+            .unwrap()
+            .replace(r#","encoding":"Canonical""#, "");
+        let mut legacy: CentralRecord = serde_json::from_str(&legacy_json).unwrap();
+        assert!(legacy.is_legacy());
+        assert_eq!(
+            legacy.validate_chain(&key_map, key_map.get(&key_id4).unwrap().clone()),
+            Err(0)
+        );
+
+        legacy
+            .resign(&FileKeySigner::new(keypair4.clone()).unwrap(), &key_map)
+            .unwrap();
+        assert!(!legacy.is_legacy());
+        assert!(legacy
+            .validate_chain(&key_map, key_map.get(&key_id4).unwrap().clone())
+            .is_ok());
+        assert_eq!(legacy.entries.len(), record.entries.len());
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let mut ids: Vec<u32> = (0..4).collect();
+        ids.push(CHECKPOINT_KEY_ID);
+
+        let keypairs: HashMap<u32, PKey<Private>> = ids
+            .iter()
+            .map(|&id| (id, PKey::generate_ed25519().unwrap()))
+            .collect();
+
+        let signers: HashMap<u32, FileKeySigner> = keypairs
+            .iter()
+            .map(|(&id, keypair)| (id, FileKeySigner::from_pkey(keypair.clone()).unwrap()))
+            .collect();
+
+        let key_map: HashMap<u32, PublicKey> = keypairs
+            .iter()
+            .map(|(&id, keypair)| (id, public_key(id, keypair)))
+            .collect();
+        let central = &signers[&CHECKPOINT_KEY_ID];
+        let central_key = &key_map[&CHECKPOINT_KEY_ID];
+
+        // Every hop is reported to the central server as it is signed
+        let mut record = CentralRecord::new(42);
+        let hop = |data: RfidData, id: u32, record: &mut CentralRecord| {
+            let data = RfidBuilder::from(data)
+                .add_entry(&signers[&id], id, id + 1, &key_map)
+                .unwrap()
+                .build();
+            record
+                .add_entry(
+                    central,
+                    id,
+                    id + 1,
+                    key_map[&(id + 1)].key.clone(),
+                    data.clone(),
+                )
+                .unwrap();
+            data
+        };
+
+        let data = RfidBuilder::default().chip_data(42, 5.0, 5.0, 5.0, 5.0);
+        let first = hop(data.build(), 0, &mut record);
+        let data = hop(first.clone(), 1, &mut record);
+
+        assert!(matches!(
+            record.checkpoint(central, central_key, &first, &key_map[&1]),
+            Err(CheckpointError::NotRecorded)
+        ));
+        assert!(matches!(
+            record.checkpoint(central, central_key, &data, &key_map[&3]),
+            Err(CheckpointError::NotRecorded)
+        ));
+
+        let compacted = record
+            .checkpoint(central, central_key, &data, &key_map[&2])
+            .unwrap();
+        let checkpoint = compacted.checkpoint.unwrap();
+        assert_eq!(checkpoint.hops, 2);
+        assert!(record.covers(&checkpoint));
+        assert_eq!(compacted.version, TagVersion::V1);
+        assert_eq!(compacted.entries.len(), 1);
+        assert_eq!(compacted.hops(), 2);
+        assert!(compacted.inspect(&key_map, &key_map[&2]).is_ok());
+
+        // Distributors keep appending after the checkpoint
+        let data = hop(compacted, 2, &mut record);
+        assert_eq!(data.hops(), 3);
+        assert_eq!(data.distributor_entries().len(), 1);
+        assert!(data.inspect(&key_map, &key_map[&3]).is_ok());
+
+        let bytes: Vec<u8> = data.clone().into();
+        let parsed = RfidData::try_from(bytes.clone()).unwrap();
+        assert_eq!(parsed.checkpoint, Some(checkpoint));
+        assert!(parsed.validate_chain(&key_map, key_map[&3].clone()).is_ok());
+        let reencoded: Vec<u8> = parsed.into();
+        assert_eq!(reencoded, bytes);
+
+        let view = RfidDataRef::parse(&bytes).unwrap();
+        assert_eq!(view.checkpoint(), Some(checkpoint));
+        assert!(view.inspect(&key_map, &key_map[&3]).is_ok());
+
+        // The checkpoint can be compacted again
+        let compacted = record
+            .checkpoint(central, central_key, &data, &key_map[&3])
+            .unwrap();
+        assert_eq!(compacted.hops(), 3);
+        assert!(compacted
+            .validate_chain(&key_map, key_map[&3].clone())
+            .is_ok());
+
+        let mut tampered = data.clone();
+        tampered.checkpoint.as_mut().unwrap().hops = 1;
+        assert_eq!(
+            tampered.validate_chain(&key_map, key_map[&3].clone()),
+            Err(0)
+        );
+        assert!(!record.covers(&tampered.checkpoint.unwrap()));
+
+        let mut tampered = data.clone();
+        tampered.checkpoint = None;
+        assert_eq!(
+            tampered.validate_chain(&key_map, key_map[&3].clone()),
+            Err(0)
+        );
+
+        // Only the central server's key can sign a checkpoint
+        let chip_data: Vec<u8> = data.chip_data.clone().into();
+        let mut forged = data;
+        forged.entries[0].pub_key = 0;
+        forged.entries[0].signature = signers[&0]
+            .sign(&[&checkpoint.signed_data(&chip_data), &key_map[&2].key])
+            .unwrap();
+        assert_eq!(forged.validate_chain(&key_map, key_map[&3].clone()), Err(0));
     }
 }
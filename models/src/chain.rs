@@ -0,0 +1,172 @@
+//! Chain walking shared by `RfidData` and `RfidDataRef`.
+//!
+//! The first entry signs the chip data, or the checkpoint of a compacted tag, followed by the next
+//! distributor's public key. Every later entry signs the link data of the entry before it followed
+//! by the next distributor's public key.
+
+use alloc::vec::Vec;
+
+use crate::checkpoint::{Checkpoint, CHECKPOINT_KEY_ID};
+use crate::crypto;
+use crate::error::InspectionError;
+use crate::key::{KeyLookup, PublicKey};
+use crate::rfid_ref::SupplyChainEntryRef;
+
+/// Data the first entry signs, without the next distributor's public key that follows it
+pub(crate) fn first_signed_data(chip_data: &[u8], checkpoint: Option<&Checkpoint>) -> Vec<u8> {
+    match checkpoint {
+        Some(checkpoint) => checkpoint.signed_data(chip_data),
+        None => chip_data.to_vec(),
+    }
+}
+
+/// Turn the data an entry signed into the start of the data signed by the entry after it.
+///
+/// This is the SHA3-256 hash of the signed data followed by the entry's signature. For RSA entries
+/// the hash is the same one found in the signature's DigestInfo, so chains signed before the hash
+/// was computed explicitly still validate.
+fn link(data: &mut Vec<u8>, signature: &[u8]) {
+    let hash = crypto::sha3_256(data);
+    data.clear();
+    data.extend_from_slice(&hash);
+    data.extend_from_slice(signature);
+}
+
+fn verify_entry(entry: &SupplyChainEntryRef, entry_key: &PublicKey, data: &[u8]) -> bool {
+    entry.algorithm == entry_key.algorithm
+        && entry_key
+            .algorithm
+            .verify(&entry_key.key, data, entry.signature)
+}
+
+/// The last entry of a chain, which is checked against whoever holds the tag
+pub(crate) struct LastHop<'e> {
+    pub ndx: usize,
+    pub entry: SupplyChainEntryRef<'e>,
+    /// Data the entry signed, without the public key of the distributor it was signed over to
+    pub data: Vec<u8>,
+}
+
+impl<'e> LastHop<'e> {
+    /// Check that the entry was signed over to `recipient`, fails with the entry's index if its own
+    /// key is unknown
+    pub fn signed_over_to<K: KeyLookup + ?Sized>(
+        &self,
+        keys: &K,
+        recipient: &PublicKey,
+    ) -> Result<bool, usize> {
+        let entry_key = keys.get_key(self.entry.pub_key).ok_or(self.ndx)?;
+
+        let mut data = self.data.clone();
+        data.extend_from_slice(&recipient.key);
+
+        Ok(verify_entry(&self.entry, entry_key, &data))
+    }
+
+    /// The candidate the entry was signed over to, if any
+    pub fn recipient<'k, K, C>(&self, keys: &K, candidates: C) -> Option<&'k PublicKey>
+    where
+        K: KeyLookup + ?Sized,
+        C: IntoIterator<Item = &'k PublicKey>,
+    {
+        candidates
+            .into_iter()
+            .find(|candidate| self.signed_over_to(keys, candidate) == Ok(true))
+    }
+
+    /// Data the entry after this one signs, without the next distributor's public key
+    pub fn next_link_data(mut self, recipient: &PublicKey) -> Vec<u8> {
+        self.data.extend_from_slice(&recipient.key);
+        link(&mut self.data, self.entry.signature);
+        self.data
+    }
+}
+
+/// Walk the chain up to its last entry, returns `None` if the chain has no entries.
+///
+/// Every entry before the last is signed over to the distributor of the entry after it. With
+/// `verify` set those entries are checked, and the first entry of a compacted tag has to be the
+/// checkpoint entry. Returns the index of the first entry that fails, or whose next distributor's
+/// key is unknown.
+pub(crate) fn walk<'e, K, I>(
+    keys: &K,
+    first_data: Vec<u8>,
+    checkpoint: bool,
+    entries: I,
+    verify: bool,
+) -> Result<Option<LastHop<'e>>, usize>
+where
+    K: KeyLookup + ?Sized,
+    I: Iterator<Item = SupplyChainEntryRef<'e>>,
+{
+    let mut data = first_data;
+    let mut last: Option<LastHop<'e>> = None;
+
+    for (ndx, entry) in entries.enumerate() {
+        if verify && ndx == 0 && checkpoint && entry.pub_key != CHECKPOINT_KEY_ID {
+            return Err(ndx);
+        }
+
+        if let Some(previous) = last.take() {
+            let next_public_key = keys.get_key(entry.pub_key).ok_or(previous.ndx)?;
+            data = previous.data;
+            data.extend_from_slice(&next_public_key.key);
+
+            if verify {
+                let previous_key = keys.get_key(previous.entry.pub_key).ok_or(previous.ndx)?;
+                if !verify_entry(&previous.entry, previous_key, &data) {
+                    return Err(previous.ndx);
+                }
+            }
+
+            link(&mut data, previous.entry.signature);
+        }
+
+        last = Some(LastHop {
+            ndx,
+            entry,
+            data: core::mem::take(&mut data),
+        });
+    }
+
+    Ok(last)
+}
+
+/// Validate a walked chain whose last entry should be signed over to `public_key`
+pub(crate) fn validate<K: KeyLookup + ?Sized>(
+    keys: &K,
+    walked: Result<Option<LastHop>, usize>,
+    public_key: &PublicKey,
+) -> Result<(), usize> {
+    match walked? {
+        Some(last) if !last.signed_over_to(keys, public_key)? => Err(last.ndx),
+        _ => Ok(()),
+    }
+}
+
+/// Receiving inspection of a walked chain whose last entry should be signed over to `recipient`.
+///
+/// A last entry that doesn't verify is only reported as signed over to the wrong recipient if it
+/// verifies for another distributor in `keys`, otherwise its signature is invalid.
+pub(crate) fn inspect<K: KeyLookup + ?Sized>(
+    keys: &K,
+    walked: Result<Option<LastHop>, usize>,
+    recipient: &PublicKey,
+) -> Result<(), InspectionError> {
+    let last = match walked.map_err(InspectionError::InvalidSignature)? {
+        Some(last) => last,
+        None => return Ok(()),
+    };
+
+    if last
+        .signed_over_to(keys, recipient)
+        .map_err(InspectionError::InvalidSignature)?
+    {
+        return Ok(());
+    }
+
+    match last.recipient(keys, keys.public_keys()) {
+        Some(_) => Err(InspectionError::WrongRecipient(recipient.id)),
+        None => Err(InspectionError::InvalidSignature(last.ndx)),
+    }
+}
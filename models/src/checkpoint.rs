@@ -0,0 +1,62 @@
+//! Checkpoints let a tag drop entries the central server has already recorded.
+//!
+//! When a tag runs out of memory, the central server replaces every entry on it with a single
+//! checkpoint entry signed with its own key. The checkpoint commits to the hash of the central
+//! record entry holding the dropped entries, so the full chain can still be audited against the
+//! central record. Distributors then keep appending entries after the checkpoint as usual.
+
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder};
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::SHA3_256_SIZE;
+
+/// Key ID the central server signs checkpoints with, reserved for it in the key registry
+pub const CHECKPOINT_KEY_ID: u32 = 0x00ff_ffff;
+
+/// Prefix of the data a checkpoint entry signs, so it can't be mistaken for a first entry
+const CHECKPOINT_CONTEXT: &[u8] = b"RFSC checkpoint";
+
+/// Summary of the entries a tag dropped, stored on the tag after the chip data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Number of hops the dropped entries covered, including those of earlier checkpoints
+    pub hops: u16,
+    /// `CentralEntry::hash` of the central record entry of the last dropped hop
+    pub record_hash: [u8; SHA3_256_SIZE],
+}
+
+impl Checkpoint {
+    /// Size of the checkpoint on a tag
+    pub const SIZE: usize = 2 + SHA3_256_SIZE;
+
+    /// Parse a checkpoint, `None` unless `bytes` is exactly `SIZE` long
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::SIZE {
+            return None;
+        }
+
+        let mut record_hash = [0u8; SHA3_256_SIZE];
+        record_hash.copy_from_slice(&bytes[2..]);
+
+        Some(Self {
+            hops: BigEndian::read_u16(&bytes[0..2]),
+            record_hash,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+        bytes.extend_from_slice(&self.hops.to_be_bytes());
+        bytes.extend_from_slice(&self.record_hash);
+        bytes
+    }
+
+    /// Data the checkpoint entry signs, without the next distributor's public key that follows it
+    pub fn signed_data(&self, chip_data: &[u8]) -> Vec<u8> {
+        let mut data = CHECKPOINT_CONTEXT.to_vec();
+        data.extend_from_slice(chip_data);
+        data.extend_from_slice(&self.to_bytes());
+        data
+    }
+}
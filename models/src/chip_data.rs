@@ -1,11 +1,14 @@
+use alloc::vec::Vec;
+use core::convert::TryFrom;
 use serde::{Deserialize, Serialize};
-use std::convert::TryFrom;
-use std::io::Cursor;
 
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, ByteOrder};
 
 use crate::error::RfidDataParseError;
+use crate::measurement::{self, Measurement};
 
+/// Chip data as first signed, a fixed block followed by the measurement section if there are
+/// any measurements
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct ChipData {
     pub chip_id: u128,
@@ -13,39 +16,82 @@ pub struct ChipData {
     pub voltage: f32,
     pub temp: f32,
     pub time: f32,
+    /// Measurements beyond the fixed block, chips with any are always written as `V1` tags
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub measurements: Vec<Measurement>,
 }
 
 impl Into<Vec<u8>> for ChipData {
     fn into(self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+        let mut bytes = Vec::with_capacity(Self::SIZE);
 
-        bytes.write_u128::<BigEndian>(self.chip_id).unwrap();
-        bytes.write_f32::<BigEndian>(self.freq).unwrap();
-        bytes.write_f32::<BigEndian>(self.voltage).unwrap();
-        bytes.write_f32::<BigEndian>(self.temp).unwrap();
-        bytes.write_f32::<BigEndian>(self.time).unwrap();
+        bytes.extend_from_slice(&self.chip_id.to_be_bytes());
+        bytes.extend_from_slice(&self.freq.to_be_bytes());
+        bytes.extend_from_slice(&self.voltage.to_be_bytes());
+        bytes.extend_from_slice(&self.temp.to_be_bytes());
+        bytes.extend_from_slice(&self.time.to_be_bytes());
+
+        if !self.measurements.is_empty() {
+            measurement::encode(&self.measurements, &mut bytes);
+        }
         bytes
     }
 }
 
-impl TryFrom<Vec<u8>> for ChipData {
-    type Error = RfidDataParseError;
+impl ChipData {
+    /// Size of the fixed block on a tag
+    pub const SIZE: usize = 32;
 
-    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
-        let mut cursor = Cursor::new(bytes);
+    /// Parse the fixed block, followed by a measurement section if there are more than `SIZE`
+    /// bytes. Measurements that aren't finite are rejected.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RfidDataParseError> {
+        let expected = match bytes.len() {
+            len if len > Self::SIZE + 2 => {
+                Self::SIZE + 2 + BigEndian::read_u16(&bytes[Self::SIZE..]) as usize
+            }
+            len if len > Self::SIZE => Self::SIZE + 2,
+            _ => Self::SIZE,
+        };
 
-        let chip_id = cursor.read_u128::<BigEndian>()?;
-        let freq = cursor.read_f32::<BigEndian>()?;
-        let voltage = cursor.read_f32::<BigEndian>()?;
-        let temp = cursor.read_f32::<BigEndian>()?;
-        let time = cursor.read_f32::<BigEndian>()?;
+        if bytes.len() < expected {
+            return Err(RfidDataParseError::TruncatedHeader {
+                expected,
+                actual: bytes.len(),
+            });
+        } else if bytes.len() > expected {
+            return Err(RfidDataParseError::TrailingBytes(bytes.len() - expected));
+        }
+
+        let measurements = if bytes.len() > Self::SIZE {
+            measurement::decode(&bytes[Self::SIZE + 2..])?
+        } else {
+            Vec::new()
+        };
+
+        let measurement = |offset: usize, field: &'static str| {
+            let value = BigEndian::read_f32(&bytes[offset..offset + 4]);
+            if value.is_finite() {
+                Ok(value)
+            } else {
+                Err(RfidDataParseError::InvalidFloat(field))
+            }
+        };
 
         Ok(Self {
-            chip_id,
-            freq,
-            voltage,
-            temp,
-            time,
+            chip_id: BigEndian::read_u128(&bytes[0..16]),
+            freq: measurement(16, "freq")?,
+            voltage: measurement(20, "voltage")?,
+            temp: measurement(24, "temp")?,
+            time: measurement(28, "time")?,
+            measurements,
         })
     }
 }
+
+impl TryFrom<Vec<u8>> for ChipData {
+    type Error = RfidDataParseError;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        Self::from_bytes(&bytes)
+    }
+}
@@ -0,0 +1,95 @@
+//! Hashing and signature verification used to check tags.
+//!
+//! OpenSSL does the work by default. With the `rust-crypto` feature pure Rust crates are used
+//! instead, which is the only option without `std`.
+
+#[cfg(all(feature = "std", not(feature = "rust-crypto")))]
+mod openssl_backend;
+#[cfg(feature = "rust-crypto")]
+mod rust_backend;
+
+#[cfg(all(feature = "std", not(feature = "rust-crypto")))]
+use openssl_backend as backend;
+#[cfg(feature = "rust-crypto")]
+use rust_backend as backend;
+
+#[cfg(not(any(feature = "std", feature = "rust-crypto")))]
+compile_error!("models needs the std or rust-crypto feature to verify signatures");
+
+use crate::algorithm::SignatureAlgorithm;
+
+pub const SHA3_256_SIZE: usize = 32;
+
+pub fn sha3_256(data: &[u8]) -> [u8; SHA3_256_SIZE] {
+    backend::sha3_256(data)
+}
+
+/// Algorithm of a PEM public key, `None` if the key can't be parsed or isn't supported
+pub fn algorithm_of_public_key(public_key: &[u8]) -> Option<SignatureAlgorithm> {
+    backend::algorithm_of_public_key(public_key)
+}
+
+/// Check a signature against a PEM public key, any malformed input fails verification
+pub fn verify(
+    algorithm: SignatureAlgorithm,
+    public_key: &[u8],
+    data: &[u8],
+    signature: &[u8],
+) -> bool {
+    signature.len() == algorithm.signature_size()
+        && backend::verify(algorithm, public_key, data, signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::SignatureAlgorithm;
+    use crate::crypto::{algorithm_of_public_key, sha3_256, verify};
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::nid::Nid;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+
+    #[test]
+    fn test_verify() {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let keys = [
+            PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap(),
+            PKey::generate_ed25519().unwrap(),
+            PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap(),
+        ];
+        let other = PKey::generate_ed25519()
+            .unwrap()
+            .public_key_to_pem()
+            .unwrap();
+
+        for key in keys.iter() {
+            let algorithm = SignatureAlgorithm::of_key(key).unwrap();
+            let public_key = key.public_key_to_pem().unwrap();
+            assert_eq!(algorithm_of_public_key(&public_key), Some(algorithm));
+
+            let signature = algorithm.sign(key, &[b"chip", b"data"]).unwrap();
+            assert!(verify(algorithm, &public_key, b"chipdata", &signature));
+            assert!(!verify(algorithm, &public_key, b"chipdatb", &signature));
+            assert!(!verify(algorithm, &other, b"chipdata", &signature));
+            assert!(!verify(
+                algorithm,
+                &public_key,
+                b"chipdata",
+                &signature[1..]
+            ));
+        }
+
+        let small_rsa = PKey::from_rsa(Rsa::generate(1024).unwrap()).unwrap();
+        assert_eq!(
+            algorithm_of_public_key(&small_rsa.public_key_to_pem().unwrap()),
+            None
+        );
+        assert_eq!(algorithm_of_public_key(b"not a key"), None);
+
+        assert_eq!(
+            sha3_256(b"")[..4],
+            [0xa7, 0xff, 0xc6, 0xf8],
+            "SHA3-256 of the empty string"
+        );
+    }
+}
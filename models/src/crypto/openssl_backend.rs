@@ -0,0 +1,61 @@
+use crate::algorithm::{SignatureAlgorithm, P256_SCALAR_SIZE};
+use crate::crypto::SHA3_256_SIZE;
+use openssl::bn::BigNum;
+use openssl::ecdsa::EcdsaSig;
+use openssl::error::ErrorStack;
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkey::PKey;
+use openssl::sign::Verifier;
+
+pub fn sha3_256(data: &[u8]) -> [u8; SHA3_256_SIZE] {
+    let mut digest = [0u8; SHA3_256_SIZE];
+    digest.copy_from_slice(&hash(MessageDigest::sha3_256(), data).unwrap());
+    digest
+}
+
+pub fn algorithm_of_public_key(public_key: &[u8]) -> Option<SignatureAlgorithm> {
+    let key = PKey::public_key_from_pem(public_key).ok()?;
+    SignatureAlgorithm::of_key(&key)
+}
+
+pub fn verify(
+    algorithm: SignatureAlgorithm,
+    public_key: &[u8],
+    data: &[u8],
+    signature: &[u8],
+) -> bool {
+    try_verify(algorithm, public_key, data, signature).unwrap_or(false)
+}
+
+fn try_verify(
+    algorithm: SignatureAlgorithm,
+    public_key: &[u8],
+    data: &[u8],
+    signature: &[u8],
+) -> Result<bool, ErrorStack> {
+    let pkey = PKey::public_key_from_pem(public_key)?;
+
+    if SignatureAlgorithm::of_key(&pkey) != Some(algorithm) {
+        return Ok(false);
+    }
+
+    match algorithm {
+        SignatureAlgorithm::RsaSha3 => {
+            let mut verifier = Verifier::new(MessageDigest::sha3_256(), &pkey)?;
+            verifier.update(data)?;
+            verifier.verify(signature)
+        }
+        SignatureAlgorithm::Ed25519 => {
+            Verifier::new_without_digest(&pkey)?.verify_oneshot(signature, data)
+        }
+        SignatureAlgorithm::EcdsaP256 => {
+            let (r, s) = signature.split_at(P256_SCALAR_SIZE);
+            let signature =
+                EcdsaSig::from_private_components(BigNum::from_slice(r)?, BigNum::from_slice(s)?)?;
+
+            let mut verifier = Verifier::new(MessageDigest::sha256(), &pkey)?;
+            verifier.update(data)?;
+            verifier.verify(&signature.to_der()?)
+        }
+    }
+}
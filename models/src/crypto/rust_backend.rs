@@ -0,0 +1,78 @@
+use crate::algorithm::SignatureAlgorithm;
+use crate::crypto::SHA3_256_SIZE;
+use p256::ecdsa::signature::Verifier;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::traits::PublicKeyParts;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use sha3::{Digest, Sha3_256};
+
+const RSA_BITS: usize = 2048;
+
+enum VerifyingKey {
+    Rsa(RsaPublicKey),
+    Ed25519(ed25519_dalek::VerifyingKey),
+    EcdsaP256(p256::ecdsa::VerifyingKey),
+}
+
+impl VerifyingKey {
+    /// Parse a PEM encoded SubjectPublicKeyInfo, as written by OpenSSL
+    fn from_pem(public_key: &[u8]) -> Option<Self> {
+        let pem = core::str::from_utf8(public_key).ok()?.trim();
+
+        if let Ok(key) = RsaPublicKey::from_public_key_pem(pem) {
+            return (key.n().bits() == RSA_BITS).then_some(VerifyingKey::Rsa(key));
+        }
+
+        if let Ok(key) = ed25519_dalek::VerifyingKey::from_public_key_pem(pem) {
+            return Some(VerifyingKey::Ed25519(key));
+        }
+
+        p256::ecdsa::VerifyingKey::from_public_key_pem(pem)
+            .ok()
+            .map(VerifyingKey::EcdsaP256)
+    }
+
+    fn algorithm(&self) -> SignatureAlgorithm {
+        match self {
+            VerifyingKey::Rsa(_) => SignatureAlgorithm::RsaSha3,
+            VerifyingKey::Ed25519(_) => SignatureAlgorithm::Ed25519,
+            VerifyingKey::EcdsaP256(_) => SignatureAlgorithm::EcdsaP256,
+        }
+    }
+}
+
+pub fn sha3_256(data: &[u8]) -> [u8; SHA3_256_SIZE] {
+    Sha3_256::digest(data).into()
+}
+
+pub fn algorithm_of_public_key(public_key: &[u8]) -> Option<SignatureAlgorithm> {
+    VerifyingKey::from_pem(public_key).map(|key| key.algorithm())
+}
+
+pub fn verify(
+    algorithm: SignatureAlgorithm,
+    public_key: &[u8],
+    data: &[u8],
+    signature: &[u8],
+) -> bool {
+    let key = match VerifyingKey::from_pem(public_key) {
+        Some(key) if key.algorithm() == algorithm => key,
+        _ => return false,
+    };
+
+    match key {
+        VerifyingKey::Rsa(key) => key
+            .verify(
+                Pkcs1v15Sign::new::<Sha3_256>(),
+                &Sha3_256::digest(data),
+                signature,
+            )
+            .is_ok(),
+        VerifyingKey::Ed25519(key) => ed25519_dalek::Signature::from_slice(signature)
+            .map(|signature| key.verify(data, &signature).is_ok())
+            .unwrap_or(false),
+        VerifyingKey::EcdsaP256(key) => p256::ecdsa::Signature::from_slice(signature)
+            .map(|signature| key.verify(data, &signature).is_ok())
+            .unwrap_or(false),
+    }
+}
@@ -1,23 +1,297 @@
+use core::fmt::{Display, Formatter};
+#[cfg(feature = "std")]
+use openssl::error::ErrorStack;
+#[cfg(feature = "std")]
 use std::error::Error;
-use std::fmt::{Display, Formatter};
+#[cfg(feature = "std")]
+use std::path::PathBuf;
 
-#[derive(Debug)]
+/// Reason bytes read from a tag could not be parsed
+#[derive(Debug, Clone, PartialEq)]
 pub enum RfidDataParseError {
-    ByteParseError(std::io::Error),
+    TooLarge {
+        size: usize,
+        max: usize,
+    },
+    /// The tag ends before its header and chip data do
+    TruncatedHeader {
+        expected: usize,
+        actual: usize,
+    },
+    /// The tag ends after `actual` of the `expected` entries
+    EntryCountMismatch {
+        expected: u16,
+        actual: u16,
+    },
+    TrailingBytes(usize),
+    InvalidCrc {
+        expected: u16,
+        actual: u16,
+    },
+    UnsupportedVersion(u8),
+    UnsupportedFlags(u8),
+    UnknownAlgorithm(u8),
+    /// A chip data measurement is NaN or infinite
+    InvalidFloat(&'static str),
+    /// The measurement section is empty or a measurement runs past its end
+    MalformedMeasurements,
+    /// A registered measurement type doesn't hold a finite `f32`
+    InvalidMeasurement(u8),
+    /// A compacted tag without the checkpoint entry its chain starts from
+    MissingCheckpointEntry,
+}
+
+#[cfg(feature = "std")]
+impl Error for RfidDataParseError {}
+
+impl Display for RfidDataParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RfidDataParseError::TooLarge { size, max } => write!(
+                f,
+                "Tag is {} bytes, larger than the maximum of {} bytes",
+                size, max
+            ),
+            RfidDataParseError::TruncatedHeader { expected, actual } => write!(
+                f,
+                "Tag is {} bytes, too short for its {} byte header",
+                actual, expected
+            ),
+            RfidDataParseError::EntryCountMismatch { expected, actual } => write!(
+                f,
+                "Tag should have {} entries but ends after {}",
+                expected, actual
+            ),
+            RfidDataParseError::TrailingBytes(count) => {
+                write!(f, "Tag has {} bytes after its last entry", count)
+            }
+            RfidDataParseError::InvalidCrc { expected, actual } => write!(
+                f,
+                "Invalid CRC, expected {:#06x} but the tag has {:#06x}",
+                expected, actual
+            ),
+            RfidDataParseError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported tag format version {}", version)
+            }
+            RfidDataParseError::UnsupportedFlags(flags) => {
+                write!(f, "Unsupported tag format flags {:#04x}", flags)
+            }
+            RfidDataParseError::UnknownAlgorithm(id) => {
+                write!(f, "Unknown signature algorithm {}", id)
+            }
+            RfidDataParseError::InvalidFloat(field) => {
+                write!(f, "Chip data {} is not a finite number", field)
+            }
+            RfidDataParseError::MalformedMeasurements => {
+                write!(f, "Chip data measurement section is malformed")
+            }
+            RfidDataParseError::InvalidMeasurement(type_id) => {
+                write!(f, "Measurement of type {} is not a finite number", type_id)
+            }
+            RfidDataParseError::MissingCheckpointEntry => {
+                write!(f, "Compacted tag has no checkpoint entry")
+            }
+        }
+    }
+}
+
+/// Reason an incoming tag was rejected during receiving inspection
+#[derive(Debug, Clone, PartialEq)]
+pub enum InspectionError {
+    InvalidCrc { expected: u16, actual: u16 },
+    UnknownDistributor(u32),
+    InvalidSignature(usize),
+    WrongRecipient(u32),
+}
+
+#[cfg(feature = "std")]
+impl Error for InspectionError {}
+
+impl Display for InspectionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            InspectionError::InvalidCrc { expected, actual } => write!(
+                f,
+                "Invalid CRC, expected {:#06x} but the tag has {:#06x}",
+                expected, actual
+            ),
+            InspectionError::UnknownDistributor(id) => {
+                write!(f, "No valid public key for distributor {}", id)
+            }
+            InspectionError::InvalidSignature(ndx) => {
+                write!(f, "Invalid signature at position {}", ndx)
+            }
+            InspectionError::WrongRecipient(id) => write!(
+                f,
+                "Last entry does not designate distributor {} as the next distributor",
+                id
+            ),
+        }
+    }
+}
+
+/// Reason a distributor's public key can't be used
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyError {
+    /// The key with this ID isn't a PEM key of a supported `SignatureAlgorithm`
+    UnsupportedKey(u32),
+}
+
+#[cfg(feature = "std")]
+impl Error for KeyError {}
+
+impl Display for KeyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            KeyError::UnsupportedKey(id) => write!(
+                f,
+                "Key {} is not a PEM key of a supported signature algorithm",
+                id
+            ),
+        }
+    }
+}
+
+/// Reason a tag couldn't be read from or written to a tag's memory
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagMemoryError<E> {
+    /// The reader failed, or the tag left its field
+    Memory(E),
+    /// The encoded tag is larger than the tag's layout can hold
+    TooLarge { size: usize, capacity: usize },
+    /// A journaled tag can only be changed by appending entries
+    NotAppendOnly,
+    /// Memory was written to but holds no readable tag
+    Corrupt(RfidDataParseError),
+}
+
+#[cfg(feature = "std")]
+impl<E: core::fmt::Debug + Display> Error for TagMemoryError<E> {}
+
+impl<E: Display> Display for TagMemoryError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TagMemoryError::Memory(e) => write!(f, "Tag memory error: {}", e),
+            TagMemoryError::TooLarge { size, capacity } => write!(
+                f,
+                "Tag is {} bytes, larger than the {} bytes available",
+                size, capacity
+            ),
+            TagMemoryError::NotAppendOnly => {
+                write!(
+                    f,
+                    "A journaled tag can only be changed by appending entries"
+                )
+            }
+            TagMemoryError::Corrupt(e) => write!(f, "Tag memory holds no readable tag: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum SignerError {
+    OpenSslError(ErrorStack),
+    IoError(std::io::Error),
+    Pkcs11Error(String),
+    InvalidUri(String),
+    KeyNotFound(String),
+    UnknownKey(u32),
+    Unsupported(String),
+    RemoteError(String),
+    Rejected(String),
+    KeyFileError { path: PathBuf, reason: String },
+    PassphraseRequired(PathBuf),
+    BadPassphrase(PathBuf),
+}
+
+#[cfg(feature = "std")]
+impl From<ErrorStack> for SignerError {
+    fn from(e: ErrorStack) -> Self {
+        Self::OpenSslError(e)
+    }
 }
 
-impl From<std::io::Error> for RfidDataParseError {
+#[cfg(feature = "std")]
+impl From<std::io::Error> for SignerError {
     fn from(e: std::io::Error) -> Self {
-        Self::ByteParseError(e)
+        Self::IoError(e)
     }
 }
 
-impl Error for RfidDataParseError {}
+#[cfg(feature = "pkcs11")]
+impl From<cryptoki::error::Error> for SignerError {
+    fn from(e: cryptoki::error::Error) -> Self {
+        Self::Pkcs11Error(e.to_string())
+    }
+}
 
-impl Display for RfidDataParseError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+#[cfg(feature = "std")]
+impl Error for SignerError {}
+
+#[cfg(feature = "std")]
+impl Display for SignerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SignerError::OpenSslError(e) => write!(f, "OpenSSL error: {}", e),
+            SignerError::IoError(e) => write!(f, "Unable to read key: {}", e),
+            SignerError::Pkcs11Error(e) => write!(f, "PKCS#11 error: {}", e),
+            SignerError::InvalidUri(e) => write!(f, "Invalid PKCS#11 URI: {}", e),
+            SignerError::KeyNotFound(e) => write!(f, "Key not found: {}", e),
+            SignerError::UnknownKey(id) => write!(f, "No public key for distributor {}", id),
+            SignerError::Unsupported(e) => write!(f, "Unsupported signer: {}", e),
+            SignerError::RemoteError(e) => write!(f, "Signing daemon error: {}", e),
+            SignerError::Rejected(e) => write!(f, "Signing daemon refused to sign: {}", e),
+            SignerError::KeyFileError { path, reason } => {
+                write!(f, "Unable to load key {}: {}", path.display(), reason)
+            }
+            SignerError::PassphraseRequired(path) => {
+                write!(
+                    f,
+                    "Key {} is encrypted but no passphrase was given",
+                    path.display()
+                )
+            }
+            SignerError::BadPassphrase(path) => {
+                write!(f, "Wrong passphrase for key {}", path.display())
+            }
+        }
+    }
+}
+
+/// Reason the central server refused to compact a tag
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum CheckpointError {
+    /// The central record's last entry isn't the tag being compacted, its entries would be lost
+    NotRecorded,
+    /// The tag has made more hops than a checkpoint can count
+    TooManyHops(usize),
+    Signer(SignerError),
+}
+
+#[cfg(feature = "std")]
+impl From<SignerError> for CheckpointError {
+    fn from(e: SignerError) -> Self {
+        Self::Signer(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for CheckpointError {}
+
+#[cfg(feature = "std")]
+impl Display for CheckpointError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
-            RfidDataParseError::ByteParseError(e) => write!(f, "Failed to parse struct: {}", e),
+            CheckpointError::NotRecorded => write!(
+                f,
+                "The central record doesn't match the tag, report its last hop first"
+            ),
+            CheckpointError::TooManyHops(hops) => {
+                write!(f, "{} hops are more than a checkpoint can cover", hops)
+            }
+            CheckpointError::Signer(e) => write!(f, "Failed to sign checkpoint: {}", e),
         }
     }
 }
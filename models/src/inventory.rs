@@ -0,0 +1,107 @@
+use crate::DatabaseModel;
+use byteorder::{LittleEndian, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Shipment {
+    /// Identity that signed the chip out
+    #[serde(default)]
+    pub dist_id: Option<u32>,
+    pub next_dist_id: u32,
+    pub shipped_at: u64,
+    /// Reader that requested the signature
+    #[serde(default)]
+    pub reader_id: Option<u64>,
+}
+
+/// A chip coming back after it was shipped
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Receipt {
+    pub received_from: Option<u32>,
+    pub received_at: u64,
+    /// Number of shipments recorded before the chip came back
+    pub after_shipments: usize,
+}
+
+/// A chip that has passed through one of a distributor server's identities
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct InventoryItem {
+    /// Identity that holds the chip
+    pub dist_id: u32,
+    pub chip_id: u128,
+    /// Distributor that signed the chip over to us, `None` if we are the first entry in the chain
+    pub received_from: Option<u32>,
+    pub received_at: u64,
+    pub shipments: Vec<Shipment>,
+    /// Later receipts of the chip, each after it was shipped
+    #[serde(default)]
+    pub returns: Vec<Receipt>,
+}
+
+impl InventoryItem {
+    pub fn new(dist_id: u32, chip_id: u128, received_from: Option<u32>, received_at: u64) -> Self {
+        Self {
+            dist_id,
+            chip_id,
+            received_from,
+            received_at,
+            shipments: vec![],
+            returns: vec![],
+        }
+    }
+
+    /// The chip was received after it was last shipped
+    pub fn in_stock(&self) -> bool {
+        let after_shipments = self
+            .returns
+            .last()
+            .map_or(0, |receipt| receipt.after_shipments);
+
+        after_shipments == self.shipments.len()
+    }
+
+    /// Record the chip as received again if it was shipped since it was last received
+    pub fn receive(&mut self, received_from: Option<u32>, received_at: u64) {
+        if !self.in_stock() {
+            self.returns.push(Receipt {
+                received_from,
+                received_at,
+                after_shipments: self.shipments.len(),
+            });
+        }
+    }
+
+    /// Signed out more often than it was received
+    pub fn reshipped(&self) -> bool {
+        self.shipments.len() > self.returns.len() + 1
+    }
+
+    pub fn next_dist_id(&self) -> Option<u32> {
+        self.shipments.last().map(|shipment| shipment.next_dist_id)
+    }
+}
+
+impl DatabaseModel for InventoryItem {
+    /// Identity and chip, the same chip can pass through several identities of one server
+    type ID = (u32, u128);
+
+    fn id(&self) -> Self::ID {
+        (self.dist_id, self.chip_id)
+    }
+
+    fn set_id(&mut self, id: Self::ID) {
+        self.dist_id = id.0;
+        self.chip_id = id.1;
+    }
+
+    fn id_type_to_bytes(id: Self::ID) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u32::<LittleEndian>(id.0).unwrap();
+        bytes.write_u128::<LittleEndian>(id.1).unwrap();
+        bytes
+    }
+
+    fn tree() -> String {
+        "identity_inventory".to_string()
+    }
+}
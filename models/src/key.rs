@@ -1,7 +1,16 @@
+use crate::algorithm::SignatureAlgorithm;
+use crate::error::KeyError;
+#[cfg(feature = "std")]
 use crate::DatabaseModel;
 use crate::{deserialize_base64, serialize_base64};
-use byteorder::{LittleEndian, WriteBytesExt};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::hash::BuildHasher;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PublicKey {
@@ -12,18 +21,62 @@ pub struct PublicKey {
     )]
     pub key: Vec<u8>,
     pub distributor_name: String,
+    #[serde(default)]
+    pub revoked: bool,
+    #[serde(default)]
+    pub algorithm: SignatureAlgorithm,
 }
 
 impl PublicKey {
-    pub fn new(id: u32, key: Vec<u8>, distributor_name: String) -> Self {
-        Self {
+    /// Create a public key, the algorithm is taken from the PEM key
+    pub fn new(id: u32, key: Vec<u8>, distributor_name: String) -> Result<Self, KeyError> {
+        let algorithm =
+            SignatureAlgorithm::of_public_key(&key).ok_or(KeyError::UnsupportedKey(id))?;
+
+        Ok(Self {
             id,
             key,
             distributor_name,
-        }
+            revoked: false,
+            algorithm,
+        })
+    }
+}
+
+/// Distributor public keys by key ID, used to validate tags
+pub trait KeyLookup {
+    fn get_key(&self, id: u32) -> Option<&PublicKey>;
+
+    /// Every key, to find out who a tag was signed over to
+    fn public_keys(&self) -> Vec<&PublicKey>;
+
+    fn contains_key_id(&self, id: u32) -> bool {
+        self.get_key(id).is_some()
+    }
+}
+
+impl KeyLookup for BTreeMap<u32, PublicKey> {
+    fn get_key(&self, id: u32) -> Option<&PublicKey> {
+        self.get(&id)
+    }
+
+    fn public_keys(&self) -> Vec<&PublicKey> {
+        self.values().collect()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: BuildHasher> KeyLookup for HashMap<u32, PublicKey, S> {
+    fn get_key(&self, id: u32) -> Option<&PublicKey> {
+        self.get(&id)
+    }
+
+    fn public_keys(&self) -> Vec<&PublicKey> {
+        self.values().collect()
     }
 }
 
+#[cfg(feature = "std")]
 impl DatabaseModel for PublicKey {
     type ID = u32;
 
@@ -36,9 +89,7 @@ impl DatabaseModel for PublicKey {
     }
 
     fn id_type_to_bytes(id: Self::ID) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        bytes.write_u32::<LittleEndian>(id).unwrap();
-        bytes
+        id.to_le_bytes().to_vec()
     }
 
     fn tree() -> String {
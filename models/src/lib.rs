@@ -1,28 +1,55 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![allow(clippy::from_over_into)]
+
+//! Tag encoding and decoding, and signature verification, are available without `std`. Build
+//! with `default-features = false` and the `rust-crypto` feature to use them on a microcontroller.
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod aging;
+pub mod algorithm;
+mod chain;
+#[cfg(feature = "std")]
 pub mod central_record;
+pub mod checkpoint;
 pub mod chip_data;
+pub mod crypto;
+#[cfg(feature = "std")]
+pub mod inventory;
 pub mod key;
+pub mod measurement;
+#[cfg(feature = "std")]
+pub mod reader;
 pub mod rfid;
+pub mod rfid_ref;
+#[cfg(feature = "std")]
+pub mod signer;
 pub mod supply_chain;
+pub mod tag_format;
+pub mod tag_memory;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+#[cfg(feature = "std")]
 pub mod requests;
 pub mod error;
+#[cfg(feature = "std")]
 pub mod utility;
 
+use alloc::string::String;
+use alloc::vec::Vec;
 use base64::{decode, encode};
-use openssl::hash::MessageDigest;
-use openssl::pkey::PKey;
-use openssl::rsa::{Padding, Rsa};
-use openssl::sign::{Signer, Verifier};
-use serde::{Deserialize, Deserializer, Serializer, Serialize};
-use serde::de::DeserializeOwned;
-
-const SIGNATURE_SIZE: usize = 256;
+use key::PublicKey;
+use serde::{Deserialize, Deserializer, Serializer};
+#[cfg(feature = "std")]
+use serde::{de::DeserializeOwned, Serialize};
 
 pub fn serialize_base64<T, S>(buffer: &T, serializer: S) -> Result<S::Ok, S::Error>
     where
         T: AsRef<[u8]>,
         S: Serializer,
 {
-    serializer.serialize_str(&encode(&buffer))
+    serializer.serialize_str(&encode(buffer))
 }
 
 pub fn deserialize_base64<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
@@ -31,38 +58,20 @@ pub fn deserialize_base64<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
 {
     use serde::de::Error;
     String::deserialize(deserializer)
-        .and_then(|string| decode(string).map_err(|err| Error::custom(err.to_string())))
+        .and_then(|string| decode(string).map_err(Error::custom))
 }
 
 pub trait BlockChainEntry {
     fn signature(&self) -> Vec<u8>;
 
-    fn create_signature(private_key: Vec<u8>, members: Vec<Vec<u8>>) -> Vec<u8> {
-        let keypair = Rsa::private_key_from_pem(&private_key).unwrap();
-        let keypair = PKey::from_rsa(keypair).unwrap();
-        let hasher = MessageDigest::sha3_256();
-
-        let mut signer = Signer::new(hasher, &keypair).unwrap();
-        signer.set_rsa_padding(Padding::PKCS1).unwrap();
-
-        for member in members {
-            signer.update(&member).unwrap();
-        }
-
-        signer.sign_to_vec().unwrap()
-    }
-
-    fn verify_signature(&self, expected_data: &[u8], public_key: &[u8]) -> bool {
-        let pkey = PKey::public_key_from_pem(&public_key).unwrap();
-        let hasher = MessageDigest::sha3_256();
-        let mut verifier = Verifier::new(hasher, &pkey).unwrap();
-
-        verifier.update(expected_data).unwrap();
-
-        verifier.verify(&self.signature()).unwrap()
+    fn verify_signature(&self, expected_data: &[u8], public_key: &PublicKey) -> bool {
+        public_key
+            .algorithm
+            .verify(&public_key.key, expected_data, &self.signature())
     }
 }
 
+#[cfg(feature = "std")]
 pub trait DatabaseModel: Serialize + DeserializeOwned {
     type ID;
 
@@ -0,0 +1,337 @@
+//! Parametric measurements stored after the fixed chip data block.
+//!
+//! Different IC families are fingerprinted with different measurements, so they are stored as a
+//! tag-length-value section:
+//!
+//! | Field          | Size | Encoding                                                  |
+//! |----------------|------|-----------------------------------------------------------|
+//! | Section length | 2    | `u16`, big endian, the size of the measurements following |
+//! | Type           | 1    | `MeasurementType::id`                                     |
+//! | Length         | 1    | Size of the value                                         |
+//! | Value          |      | Big endian `f32` in the type's unit for registered types  |
+//!
+//! Type, length and value repeat for every measurement. Types missing from the registry are kept
+//! as raw bytes, so tags written against a newer registry still encode back to the signed bytes.
+
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder};
+use core::convert::TryFrom;
+use serde::{Deserialize, Serialize};
+
+use crate::error::RfidDataParseError;
+use crate::{deserialize_base64, serialize_base64};
+
+/// Registry of measurement types
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MeasurementType {
+    /// Frequency of one of the chip's ring oscillators
+    RingOscillator,
+    /// Supply current with the chip idle
+    LeakageCurrent,
+    /// Delay of a critical path
+    PathDelay,
+    /// Lowest supply voltage the chip still works at
+    MinimumVoltage,
+    /// Die temperature the other measurements were taken at
+    Temperature,
+}
+
+impl MeasurementType {
+    pub const ALL: [MeasurementType; 5] = [
+        MeasurementType::RingOscillator,
+        MeasurementType::LeakageCurrent,
+        MeasurementType::PathDelay,
+        MeasurementType::MinimumVoltage,
+        MeasurementType::Temperature,
+    ];
+
+    /// ID stored in the type field, never reuse the ID of a removed type
+    pub fn id(&self) -> u8 {
+        match self {
+            MeasurementType::RingOscillator => 1,
+            MeasurementType::LeakageCurrent => 2,
+            MeasurementType::PathDelay => 3,
+            MeasurementType::MinimumVoltage => 4,
+            MeasurementType::Temperature => 5,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.id() == id)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MeasurementType::RingOscillator => "ring_oscillator",
+            MeasurementType::LeakageCurrent => "leakage_current",
+            MeasurementType::PathDelay => "path_delay",
+            MeasurementType::MinimumVoltage => "minimum_voltage",
+            MeasurementType::Temperature => "temperature",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            MeasurementType::RingOscillator => "Hz",
+            MeasurementType::LeakageCurrent => "A",
+            MeasurementType::PathDelay => "s",
+            MeasurementType::MinimumVoltage => "V",
+            MeasurementType::Temperature => "°C",
+        }
+    }
+}
+
+/// A single measurement of the section, a type can be measured more than once.
+///
+/// The value is private so its size always fits the length field.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "RawMeasurement", into = "RawMeasurement")]
+pub struct Measurement {
+    /// `MeasurementType::id`
+    pub type_id: u8,
+    /// Encoded value, at most `MAX_VALUE_SIZE` bytes
+    value: Vec<u8>,
+}
+
+/// Serialized form of a `Measurement`, checked when it is converted back
+#[derive(Clone, Deserialize, Serialize)]
+struct RawMeasurement {
+    type_id: u8,
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    value: Vec<u8>,
+}
+
+impl TryFrom<RawMeasurement> for Measurement {
+    type Error = RfidDataParseError;
+
+    fn try_from(raw: RawMeasurement) -> Result<Self, Self::Error> {
+        Measurement::from_raw(raw.type_id, raw.value)
+            .ok_or(RfidDataParseError::MalformedMeasurements)
+    }
+}
+
+impl From<Measurement> for RawMeasurement {
+    fn from(measurement: Measurement) -> Self {
+        Self {
+            type_id: measurement.type_id,
+            value: measurement.value,
+        }
+    }
+}
+
+impl Measurement {
+    pub const MAX_VALUE_SIZE: usize = u8::MAX as usize;
+
+    pub fn new(kind: MeasurementType, value: f32) -> Self {
+        Self {
+            type_id: kind.id(),
+            value: value.to_be_bytes().to_vec(),
+        }
+    }
+
+    /// A measurement of any type with an already encoded value, `None` if the value is longer
+    /// than `MAX_VALUE_SIZE`
+    pub fn from_raw(type_id: u8, value: Vec<u8>) -> Option<Self> {
+        if value.len() > Self::MAX_VALUE_SIZE {
+            return None;
+        }
+
+        Some(Self { type_id, value })
+    }
+
+    /// Encoded value as stored on the tag
+    pub fn raw_value(&self) -> &[u8] {
+        &self.value
+    }
+
+    /// Registered type of the measurement, `None` for types added after this registry
+    pub fn kind(&self) -> Option<MeasurementType> {
+        MeasurementType::from_id(self.type_id)
+    }
+
+    /// Value in the unit of its type, `None` for unregistered types
+    pub fn value(&self) -> Option<f32> {
+        match (self.kind(), self.value.len()) {
+            (Some(_), 4) => Some(BigEndian::read_f32(&self.value)),
+            _ => None,
+        }
+    }
+}
+
+/// Append the section holding `measurements`, including its length.
+///
+/// Values are at most `MAX_VALUE_SIZE` bytes, and a tag's `MAX_TAG_SIZE` keeps any section that can
+/// be parsed well within the `u16` section length.
+pub(crate) fn encode(measurements: &[Measurement], bytes: &mut Vec<u8>) {
+    let len: usize = measurements.iter().map(|m| 2 + m.value.len()).sum();
+    bytes.extend_from_slice(&(len as u16).to_be_bytes());
+
+    for measurement in measurements {
+        bytes.push(measurement.type_id);
+        bytes.push(measurement.value.len() as u8);
+        bytes.extend_from_slice(&measurement.value);
+    }
+}
+
+/// Parse the measurements of a section, `bytes` holds them without the section length.
+///
+/// An empty section is rejected, it would be encoded back without the section at all.
+pub(crate) fn decode(bytes: &[u8]) -> Result<Vec<Measurement>, RfidDataParseError> {
+    if bytes.is_empty() {
+        return Err(RfidDataParseError::MalformedMeasurements);
+    }
+
+    let mut measurements = Vec::new();
+    let mut rest = bytes;
+
+    while !rest.is_empty() {
+        if rest.len() < 2 || rest.len() < 2 + rest[1] as usize {
+            return Err(RfidDataParseError::MalformedMeasurements);
+        }
+
+        let (type_id, len) = (rest[0], rest[1] as usize);
+        let measurement = Measurement {
+            type_id,
+            value: rest[2..2 + len].to_vec(),
+        };
+
+        if measurement.kind().is_some() && !measurement.value().is_some_and(f32::is_finite) {
+            return Err(RfidDataParseError::InvalidMeasurement(type_id));
+        }
+
+        measurements.push(measurement);
+        rest = &rest[2 + len..];
+    }
+
+    Ok(measurements)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chip_data::ChipData;
+    use crate::error::RfidDataParseError;
+    use crate::measurement::{Measurement, MeasurementType};
+    use crate::rfid::{RfidBuilder, RfidData};
+    use crate::rfid_ref::RfidDataRef;
+    use crate::signer::file_key::FileKeySigner;
+    use crate::tag_format::{TagVersion, FLAG_MEASUREMENTS};
+    use crate::test_util::{ed25519_keypairs, key_map};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_measurements() {
+        let keypairs = ed25519_keypairs(2);
+        let key_map = key_map(&keypairs);
+        let signer = FileKeySigner::from_pkey(keypairs[0].clone()).unwrap();
+
+        let mut data = RfidBuilder::default()
+            .chip_data(42, 5.0, 5.0, 5.0, 5.0)
+            .measurement(MeasurementType::RingOscillator, 1.2e9)
+            .measurement(MeasurementType::RingOscillator, 1.1e9)
+            .measurement(MeasurementType::LeakageCurrent, 3.5e-6)
+            .build();
+
+        // A type from a newer registry is kept as it is
+        data.chip_data
+            .measurements
+            .push(Measurement::from_raw(200, vec![1, 2, 3]).unwrap());
+        let data = RfidBuilder::from(data)
+            .add_entry(&signer, 0, 1, &key_map)
+            .unwrap()
+            .build();
+
+        let bytes: Vec<u8> = data.clone().into();
+        assert_eq!(bytes[6], FLAG_MEASUREMENTS);
+        assert_eq!(bytes.len(), 7 + 2 + 2 + 32 + 2 + 3 * 6 + 5 + 4 + 64);
+
+        let parsed = RfidData::try_from(bytes.clone()).unwrap();
+        assert_eq!(parsed.version, TagVersion::V1);
+        assert_eq!(parsed.chip_data.measurements, data.chip_data.measurements);
+        assert_eq!(
+            parsed.chip_data.measurements[2].kind(),
+            Some(MeasurementType::LeakageCurrent)
+        );
+        assert_eq!(parsed.chip_data.measurements[1].value(), Some(1.1e9));
+        assert_eq!(parsed.chip_data.measurements[3].value(), None);
+        assert!(parsed.validate_chain(&key_map, key_map[&1].clone()).is_ok());
+        let reencoded: Vec<u8> = parsed.into();
+        assert_eq!(reencoded, bytes);
+
+        let view = RfidDataRef::parse(&bytes).unwrap();
+        assert_eq!(view.chip_id(), 42);
+        assert_eq!(view.chip_data().measurements.len(), 4);
+        assert!(view.validate_chain(&key_map, &key_map[&1]).is_ok());
+
+        // The measurements are signed with the rest of the chip data
+        let mut tampered = data.clone();
+        tampered.chip_data.measurements[0] =
+            Measurement::new(MeasurementType::RingOscillator, 1.3e9);
+        assert_eq!(
+            tampered.validate_chain(&key_map, key_map[&1].clone()),
+            Err(0)
+        );
+
+        // Chips without measurements keep the legacy 32 byte block
+        let legacy = RfidBuilder::default()
+            .chip_data(42, 5.0, 5.0, 5.0, 5.0)
+            .build();
+        let legacy_bytes: Vec<u8> = legacy.chip_data.into();
+        assert_eq!(legacy_bytes.len(), ChipData::SIZE);
+
+        let chip_data: Vec<u8> = data.chip_data.clone().into();
+        let mut empty = chip_data[..ChipData::SIZE].to_vec();
+        empty.extend_from_slice(&[0, 0]);
+        assert_eq!(
+            ChipData::from_bytes(&empty).unwrap_err(),
+            RfidDataParseError::MalformedMeasurements
+        );
+
+        let mut overrun = chip_data[..ChipData::SIZE].to_vec();
+        overrun.extend_from_slice(&[0, 3, 1, 4, 0]);
+        assert_eq!(
+            ChipData::from_bytes(&overrun).unwrap_err(),
+            RfidDataParseError::MalformedMeasurements
+        );
+
+        let mut nan = chip_data[..ChipData::SIZE].to_vec();
+        nan.extend_from_slice(&[0, 6, 2, 4]);
+        nan.extend_from_slice(&f32::NAN.to_be_bytes());
+        assert_eq!(
+            ChipData::from_bytes(&nan).unwrap_err(),
+            RfidDataParseError::InvalidMeasurement(2)
+        );
+
+        let mut short = chip_data[..ChipData::SIZE].to_vec();
+        short.extend_from_slice(&[0, 4, 3, 2, 0, 0]);
+        assert_eq!(
+            ChipData::from_bytes(&short).unwrap_err(),
+            RfidDataParseError::InvalidMeasurement(3)
+        );
+
+        assert!(Measurement::from_raw(200, vec![0; Measurement::MAX_VALUE_SIZE + 1]).is_none());
+        let oversized = format!(
+            r#"{{"type_id":200,"value":"{}"}}"#,
+            base64::encode(vec![0; Measurement::MAX_VALUE_SIZE + 1])
+        );
+        assert!(serde_json::from_str::<Measurement>(&oversized).is_err());
+        let json = serde_json::to_string(&data.chip_data.measurements[3]).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Measurement>(&json).unwrap(),
+            data.chip_data.measurements[3]
+        );
+
+        let mut truncated = bytes;
+        truncated.truncate(7 + 2 + 2 + 32 + 10);
+        assert_eq!(
+            RfidData::try_from(truncated).unwrap_err(),
+            RfidDataParseError::MalformedMeasurements
+        );
+    }
+}
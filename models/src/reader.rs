@@ -0,0 +1,44 @@
+use crate::DatabaseModel;
+use crate::{deserialize_base64, serialize_base64};
+use byteorder::{LittleEndian, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+
+/// A tag reader allowed to use a distributor server
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Reader {
+    pub id: u64,
+    pub name: String,
+    /// SHA3-256 hash of the reader's API token secret
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    pub token_hash: Vec<u8>,
+    pub revoked: bool,
+    pub registered_at: u64,
+    /// Distributor identity the reader signs with, `None` for the server's default identity
+    #[serde(default)]
+    pub identity: Option<u32>,
+}
+
+impl DatabaseModel for Reader {
+    type ID = u64;
+
+    fn id(&self) -> Self::ID {
+        self.id
+    }
+
+    fn set_id(&mut self, id: Self::ID) {
+        self.id = id
+    }
+
+    fn id_type_to_bytes(id: Self::ID) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u64::<LittleEndian>(id).unwrap();
+        bytes
+    }
+
+    fn tree() -> String {
+        "readers".to_string()
+    }
+}
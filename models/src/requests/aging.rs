@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aging::{AgingReport, HopMeasurement};
+
+/// Ask the central server whether a measurement shows a chip was used before, without recording
+/// it. Distributors send this before signing a chip over.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AgingCheckRequest {
+    pub dist_id: u32,
+    pub chip_id: u128,
+    pub measurement: HopMeasurement,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AgingCheckResponse {
+    pub success: bool,
+    /// The chip's record analysed with the measurement as its next hop, `None` if the central
+    /// server has no recorded enrollment to compare it with
+    pub report: Option<AgingReport>,
+    /// Why the measurement couldn't be checked
+    #[serde(default)]
+    pub error: Option<String>,
+}
@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::SignerError;
+use crate::rfid::RfidData;
+use crate::signer::Signer;
+use crate::BlockChainEntry;
+use crate::{deserialize_base64, serialize_base64};
+use byteorder::{BigEndian, WriteBytesExt};
+
+/// Request to compact a tag that is out of memory, signed by the distributor holding it
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CheckpointRequest {
+    pub dist_id: u32,
+    pub rfid_data: RfidData,
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    pub signature: Vec<u8>,
+}
+
+impl CheckpointRequest {
+    pub fn new(
+        dist_id: u32,
+        rfid_data: RfidData,
+        signer: &dyn Signer,
+    ) -> Result<Self, SignerError> {
+        let mut req = Self {
+            dist_id,
+            rfid_data,
+            signature: vec![],
+        };
+
+        let bytes: Vec<u8> = req.clone().into();
+
+        req.signature = signer.sign(&[&bytes])?;
+
+        Ok(req)
+    }
+}
+
+impl BlockChainEntry for CheckpointRequest {
+    fn signature(&self) -> Vec<u8> {
+        self.signature.clone()
+    }
+}
+
+impl Into<Vec<u8>> for CheckpointRequest {
+    fn into(self) -> Vec<u8> {
+        let mut bytes = b"checkpoint".to_vec();
+
+        bytes.write_u32::<BigEndian>(self.dist_id).unwrap();
+        let mut rfid_bytes: Vec<u8> = self.rfid_data.into();
+        bytes.append(&mut rfid_bytes);
+
+        bytes
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CheckpointResponse {
+    pub success: bool,
+    /// The compacted tag, signed over to the distributor that asked for it
+    pub rfid_data: Option<RfidData>,
+    /// Why the tag wasn't compacted
+    #[serde(default)]
+    pub error: Option<String>,
+}
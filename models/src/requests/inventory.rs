@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InventoryReconciliation {
+    /// Chips that have been received, counting a returned chip once
+    pub received: usize,
+    /// Chips signed out and not received again since
+    pub shipped: usize,
+    pub in_stock: usize,
+    /// Chips that have been signed out more often than they were received
+    pub reshipped: Vec<u128>,
+}
+
+/// Query string of the inventory endpoints, picks the identity whose inventory is listed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InventoryQuery {
+    pub dist_id: Option<u32>,
+}
@@ -1,3 +1,12 @@
+pub mod aging;
+pub mod checkpoint;
+pub mod error_response;
+pub mod inventory;
 pub mod key_request;
+pub mod reader;
+pub mod record_queue;
+pub mod revoke_key;
+pub mod sign;
 pub mod update_blockchain;
 pub mod update_record;
+pub mod verify;
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterReaderRequest {
+    pub name: String,
+    /// Restrict the reader to a single distributor identity
+    #[serde(default)]
+    pub identity: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterReaderResponse {
+    pub reader_id: u64,
+    /// Bearer token the reader must send in its `Authorization` header
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevokeReaderResponse {
+    pub success: bool,
+}
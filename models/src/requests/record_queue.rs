@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordQueueStatus {
+    pub dist_id: u32,
+    pub depth: usize,
+    /// Updates set aside after the central server rejected them or kept failing on them
+    #[serde(default)]
+    pub rejected: usize,
+    pub failed_attempts: u64,
+    pub last_error: Option<String>,
+}
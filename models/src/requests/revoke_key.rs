@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::SignerError;
+use crate::signer::Signer;
+use crate::BlockChainEntry;
+use crate::{deserialize_base64, serialize_base64};
+use byteorder::{BigEndian, WriteBytesExt};
+
+/// Request to revoke a distributor key, signed by the key being revoked.
+///
+/// A key that is lost can't sign its own revocation, the central server's operator revokes it with
+/// `--revoke` instead.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RevokeKeyRequest {
+    pub key_id: u32,
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    pub signature: Vec<u8>,
+}
+
+impl RevokeKeyRequest {
+    pub fn new(key_id: u32, signer: &dyn Signer) -> Result<Self, SignerError> {
+        let mut req = Self {
+            key_id,
+            signature: vec![],
+        };
+
+        let bytes: Vec<u8> = req.clone().into();
+
+        req.signature = signer.sign(&[&bytes])?;
+
+        Ok(req)
+    }
+}
+
+impl BlockChainEntry for RevokeKeyRequest {
+    fn signature(&self) -> Vec<u8> {
+        self.signature.clone()
+    }
+}
+
+impl Into<Vec<u8>> for RevokeKeyRequest {
+    fn into(self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.write_u32::<BigEndian>(self.key_id).unwrap();
+
+        bytes
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RevokeKeyResponse {
+    pub success: bool,
+}
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aging::HopMeasurement;
+use crate::rfid::RfidData;
+use crate::{deserialize_base64, serialize_base64};
+
+/// Request to a signing daemon, sent as a single line of JSON over its Unix socket.
+///
+/// The daemon builds the data it signs from these fields itself, so its policy checks the chip
+/// and distributor the signature is actually for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SignRequest {
+    /// Sign a tag that was signed over to the daemon's distributor over to `next_dist_id`
+    Entry {
+        rfid_data: RfidData,
+        next_dist_id: u32,
+    },
+    /// Sign the central server update for a tag the daemon signed over to `next_dist_id`
+    UpdateRecord {
+        rfid_data: RfidData,
+        next_dist_id: u32,
+        measurement: Option<HopMeasurement>,
+    },
+    /// Sign a request to compact a tag the daemon's distributor holds
+    Checkpoint { rfid_data: RfidData },
+}
+
+impl SignRequest {
+    pub fn chip_id(&self) -> u128 {
+        match self {
+            SignRequest::Entry { rfid_data, .. }
+            | SignRequest::UpdateRecord { rfid_data, .. }
+            | SignRequest::Checkpoint { rfid_data } => rfid_data.chip_data.chip_id,
+        }
+    }
+
+    /// Distributor the chip is signed over to, `None` for checkpoints
+    pub fn next_dist_id(&self) -> Option<u32> {
+        match self {
+            SignRequest::Entry { next_dist_id, .. }
+            | SignRequest::UpdateRecord { next_dist_id, .. } => Some(*next_dist_id),
+            SignRequest::Checkpoint { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SignResponse {
+    /// Empty if the request was refused
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    pub signature: Vec<u8>,
+    pub error: Option<String>,
+}
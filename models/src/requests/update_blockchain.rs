@@ -1,11 +1,22 @@
 use serde::{Deserialize, Serialize};
 
+use crate::aging::HopMeasurement;
 use crate::rfid::RfidData;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateBlockChainRequest {
     pub rfid_data: RfidData,
     pub next_distributor: u32,
+    /// Identity to sign with when the distributor server hosts more than one
+    #[serde(default)]
+    pub dist_id: Option<u32>,
+    /// Have the central server replace the tag's entries with a checkpoint before signing, for
+    /// tags without room for another entry
+    #[serde(default)]
+    pub compact: bool,
+    /// The reader's fresh measurement of the chip, reported to the central server with the hop
+    #[serde(default)]
+    pub measurement: Option<HopMeasurement>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
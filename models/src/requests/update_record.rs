@@ -1,20 +1,22 @@
 use serde::{Deserialize, Serialize};
 
+use crate::aging::HopMeasurement;
 use crate::central_record::CentralRecord;
+use crate::error::SignerError;
 use crate::rfid::RfidData;
+use crate::signer::Signer;
 use crate::BlockChainEntry;
 use crate::{deserialize_base64, serialize_base64};
 use byteorder::{BigEndian, WriteBytesExt};
-use openssl::hash::MessageDigest;
-use openssl::pkey::{PKey, Private};
-use openssl::rsa::{Padding, Rsa};
-use openssl::sign::Signer;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct UpdateRecordRequest {
     pub dist_id: u32,
     pub next_dist_id: u32,
     pub rfid_data: RfidData,
+    /// Measurement the distributor took of the chip before signing it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub measurement: Option<HopMeasurement>,
     #[serde(
         serialize_with = "serialize_base64",
         deserialize_with = "deserialize_base64"
@@ -27,28 +29,22 @@ impl UpdateRecordRequest {
         dist_id: u32,
         next_dist_id: u32,
         rfid_data: RfidData,
-        private_key: &Rsa<Private>,
-    ) -> Self {
+        measurement: Option<HopMeasurement>,
+        signer: &dyn Signer,
+    ) -> Result<Self, SignerError> {
         let mut req = Self {
             dist_id,
             next_dist_id,
             rfid_data,
+            measurement,
             signature: vec![],
         };
 
         let bytes: Vec<u8> = req.clone().into();
 
-        let hasher = MessageDigest::sha3_256();
-        let pkey = PKey::from_rsa(private_key.clone()).unwrap();
+        req.signature = signer.sign(&[&bytes])?;
 
-        let mut signer = Signer::new(hasher, &pkey).unwrap();
-        signer.set_rsa_padding(Padding::PKCS1).unwrap();
-
-        signer.update(&bytes).unwrap();
-
-        req.signature = signer.sign_to_vec().unwrap();
-
-        req
+        Ok(req)
     }
 }
 
@@ -67,6 +63,10 @@ impl Into<Vec<u8>> for UpdateRecordRequest {
         let mut rfid_bytes: Vec<u8> = self.rfid_data.into();
         bytes.append(&mut rfid_bytes);
 
+        if let Some(measurement) = self.measurement {
+            bytes.extend_from_slice(&measurement.to_bytes());
+        }
+
         bytes
     }
 }
@@ -75,4 +75,10 @@ impl Into<Vec<u8>> for UpdateRecordRequest {
 pub struct UpdateRecordResponse {
     pub success: bool,
     pub record: Option<CentralRecord>,
+    /// The chip's measurements suggest it was used before, see `AgingModel`
+    #[serde(default)]
+    pub recycled: bool,
+    /// Why the update was rejected
+    #[serde(default)]
+    pub error: Option<String>,
 }
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+use crate::rfid::RfidData;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyTagRequest {
+    pub rfid_data: RfidData,
+    /// Identity the tag is addressed to when the distributor server hosts more than one
+    #[serde(default)]
+    pub dist_id: Option<u32>,
+}
+
+/// One hop of a chip's journey through the supply chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProvenanceHop {
+    pub dist_id: u32,
+    pub distributor_name: Option<String>,
+    /// `None` if the recipient of the last hop couldn't be determined
+    pub next_dist_id: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VerifyTagResponse {
+    pub valid: bool,
+    pub problems: Vec<String>,
+    pub provenance: Vec<ProvenanceHop>,
+    pub summary: String,
+}
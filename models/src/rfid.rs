@@ -1,128 +1,152 @@
+use alloc::vec::Vec;
+use core::convert::TryFrom;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
 use std::collections::HashMap;
-use std::convert::TryFrom;
-use std::io::{Cursor, Read};
 
-use byteorder::{BigEndian, ReadBytesExt};
-use crc::crc16;
-
-use crate::error::RfidDataParseError;
+use crate::chain::{self, LastHop};
+use crate::checkpoint::Checkpoint;
 use crate::chip_data::ChipData;
-use crate::key::PublicKey;
+#[cfg(feature = "std")]
+use crate::error::SignerError;
+use crate::error::{InspectionError, RfidDataParseError};
+use crate::key::{KeyLookup, PublicKey};
+use crate::measurement::{Measurement, MeasurementType};
+use crate::rfid_ref::SupplyChainEntryRef;
+#[cfg(feature = "std")]
+use crate::signer::Signer;
 use crate::supply_chain::SupplyChainEntry;
-use crate::BlockChainEntry;
-use crate::SIGNATURE_SIZE;
+use crate::tag_format::{self, TagVersion};
 
+/// Contents of an RFID tag.
+///
+/// The first entry signs the chip data followed by the next distributor's public key. Every later
+/// entry signs the link data of the entry before it followed by the next distributor's public key,
+/// see `chain`.
+///
+/// On a compacted tag the first entry is instead the central server's checkpoint entry, signed with
+/// `CHECKPOINT_KEY_ID` over `Checkpoint::signed_data` followed by the next distributor's public key.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RfidData {
     pub crc: u16,
     pub chip_data: ChipData,
     pub entries: Vec<SupplyChainEntry>,
+    /// Layout the tag was read in or will be written in
+    #[serde(default)]
+    pub version: TagVersion,
+    /// Entries dropped by the central server, compacted tags are always written as `V1`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<Checkpoint>,
 }
 
 impl RfidData {
     pub fn calc_crc(&self) -> u16 {
-        let bytes: Vec<u8> = self.clone().into();
-        ((bytes[0] as u16) << 8) | bytes[1] as u16
+        tag_format::crc(self)
     }
 
-    pub fn validate_chain(
+    pub fn validate_chain<K: KeyLookup + ?Sized>(
         &self,
-        keys: &HashMap<u32, PublicKey>,
+        keys: &K,
         public_key: PublicKey,
     ) -> Result<(), usize> {
-        let mut data_buff = Vec::new();
-
-        for (ndx, entry) in self.entries.iter().enumerate() {
-            data_buff.clear();
-            let next_public_key = if ndx == self.entries.len() - 1 {
-                &public_key
-            } else if let Some(pub_key) = keys.get(&self.entries[ndx + 1].pub_key) {
-                pub_key
-            } else {
-                return Err(ndx);
-            };
-
-            if ndx == 0 {
-                let chip_data: Vec<u8> = self.chip_data.clone().into();
-                data_buff.extend_from_slice(&chip_data);
-                data_buff.extend_from_slice(&next_public_key.key);
-            } else {
-                let last_entry = &self.entries[ndx - 1];
-                let last_pub_key = keys.get(&last_entry.pub_key).unwrap();
-                data_buff.extend_from_slice(&crate::utility::hash_from_signature(
-                    &last_pub_key.key,
-                    &last_entry.signature,
-                ));
-                data_buff.extend_from_slice(&last_entry.signature);
-                data_buff.extend_from_slice(&next_public_key.key);
-            }
-
-            if !entry.verify_signature(&data_buff, &keys.get(&entry.pub_key).unwrap().key) {
-                return Err(ndx);
-            }
-        }
+        chain::validate(keys, self.walk(keys, true), &public_key)
+    }
 
-        Ok(())
+    /// Walk the entries with `chain::walk`
+    fn walk<K: KeyLookup + ?Sized>(
+        &self,
+        keys: &K,
+        verify: bool,
+    ) -> Result<Option<LastHop<'_>>, usize> {
+        chain::walk(
+            keys,
+            self.first_signed_data(),
+            self.checkpoint.is_some(),
+            self.entries.iter().map(SupplyChainEntryRef::from),
+            verify,
+        )
+    }
+
+    /// Data the next entry signs, without the next distributor's public key that follows it.
+    ///
+    /// `public_key` belongs to the distributor adding the entry, which the last entry was signed over
+    /// to. Returns the index of the first entry whose next distributor's key is unknown.
+    pub fn next_link_data<K: KeyLookup + ?Sized>(
+        &self,
+        keys: &K,
+        public_key: &PublicKey,
+    ) -> Result<Vec<u8>, usize> {
+        Ok(match self.walk(keys, false)? {
+            Some(last) => last.next_link_data(public_key),
+            None => self.first_signed_data(),
+        })
+    }
+
+    /// Data the first entry signs, without the next distributor's public key that follows it
+    fn first_signed_data(&self) -> Vec<u8> {
+        let chip_data: Vec<u8> = self.chip_data.clone().into();
+        chain::first_signed_data(&chip_data, self.checkpoint.as_ref())
+    }
+
+    /// Number of hops the chip has made, including those dropped by a checkpoint
+    pub fn hops(&self) -> usize {
+        self.checkpoint
+            .map_or(0, |checkpoint| checkpoint.hops as usize)
+            + self.distributor_entries().len()
+    }
+
+    /// Entries signed by distributors, leaving out the checkpoint entry of a compacted tag
+    pub fn distributor_entries(&self) -> &[SupplyChainEntry] {
+        match self.checkpoint {
+            Some(_) if !self.entries.is_empty() => &self.entries[1..],
+            _ => &self.entries,
+        }
     }
 
     pub fn valid_crc(&self) -> bool {
         self.crc == self.calc_crc()
     }
-}
 
-impl Into<Vec<u8>> for RfidData {
-    fn into(self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        let entry_len = self.entries.len() as u16;
-
-        bytes.push((entry_len >> 8) as u8);
-        bytes.push(entry_len as u8);
-        bytes.append(&mut self.chip_data.into());
+    /// Receiving inspection, checks that the tag is intact and was signed over to `recipient`.
+    ///
+    /// This should be run before a distributor appends its own entry to the chain.
+    pub fn inspect<K: KeyLookup + ?Sized>(
+        &self,
+        keys: &K,
+        recipient: &PublicKey,
+    ) -> Result<(), InspectionError> {
+        let crc = self.calc_crc();
+        if self.crc != crc {
+            return Err(InspectionError::InvalidCrc {
+                expected: crc,
+                actual: self.crc,
+            });
+        }
 
-        let mut entry_bytes: Vec<u8> = self
+        if let Some(entry) = self
             .entries
-            .into_iter()
-            .map::<Vec<u8>, _>(|e| e.into())
-            .flatten()
-            .collect();
-
-        bytes.append(&mut entry_bytes);
-        let crc = crc16::checksum_x25(&bytes);
+            .iter()
+            .find(|entry| !keys.contains_key_id(entry.pub_key))
+        {
+            return Err(InspectionError::UnknownDistributor(entry.pub_key));
+        }
 
-        bytes.insert(0, (crc >> 8) as u8);
-        bytes.insert(0, (crc & 0xff) as u8);
+        chain::inspect(keys, self.walk(keys, true), recipient)
+    }
+}
 
-        bytes
+impl Into<Vec<u8>> for RfidData {
+    fn into(self) -> Vec<u8> {
+        tag_format::encode(&self)
     }
 }
 
 impl TryFrom<Vec<u8>> for RfidData {
     type Error = RfidDataParseError;
 
+    /// Parse a tag in either the legacy or a versioned layout
     fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
-        let mut cursor = Cursor::new(bytes);
-        let crc = cursor.read_u16::<BigEndian>()?;
-        let entry_count = cursor.read_u16::<BigEndian>()?;
-
-        let mut chip_data_bytes = vec![0u8; 32];
-        cursor.read_exact(&mut chip_data_bytes)?;
-
-        let chip_data = ChipData::try_from(chip_data_bytes)?;
-
-        let mut entries = Vec::with_capacity(entry_count as usize);
-        for _ in 0..entry_count {
-            let mut entry_bytes = vec![0u8; 4 + SIGNATURE_SIZE];
-            cursor.read_exact(&mut entry_bytes)?;
-            let entry = SupplyChainEntry::try_from(entry_bytes)?;
-            entries.push(entry)
-        }
-
-        Ok(Self {
-            crc,
-            chip_data,
-            entries,
-        })
+        tag_format::decode(&bytes)
     }
 }
 
@@ -132,31 +156,40 @@ pub struct RfidBuilder {
 }
 
 impl RfidBuilder {
+    #[cfg(feature = "std")]
     pub fn add_entry(
         mut self,
-        private_key: Vec<u8>,
+        signer: &dyn Signer,
         public_key_id: u32,
         next_public_key_id: u32,
         keys: &HashMap<u32, PublicKey>,
-    ) -> Self {
-        let data = if let Some(last_entry) = self.rfid_data.entries.last() {
-            let last_public_key = keys.get(&last_entry.pub_key).unwrap();
-            let mut buf =
-                crate::utility::hash_from_signature(&last_public_key.key, &last_entry.signature);
-            buf.extend_from_slice(&last_entry.signature);
-            buf
-        } else {
-            self.rfid_data.chip_data.clone().into()
-        };
+    ) -> Result<Self, SignerError> {
+        let public_key = keys
+            .get(&public_key_id)
+            .ok_or(SignerError::UnknownKey(public_key_id))?;
+
+        let entries = &self.rfid_data.entries;
+        let data = self
+            .rfid_data
+            .next_link_data(keys, public_key)
+            .map_err(|ndx| SignerError::UnknownKey(entries[ndx + 1].pub_key))?;
 
-        let next_public_key = keys.get(&next_public_key_id).unwrap();
+        let next_public_key = keys
+            .get(&next_public_key_id)
+            .ok_or(SignerError::UnknownKey(next_public_key_id))?;
 
         self.rfid_data.entries.push(SupplyChainEntry::new(
-            private_key,
+            signer,
             next_public_key.key.clone(),
             data,
-            public_key_id,
-        ));
+            public_key,
+        )?);
+        Ok(self)
+    }
+
+    /// Layout to write the tag in, legacy by default
+    pub fn version(mut self, version: TagVersion) -> Self {
+        self.rfid_data.version = version;
         self
     }
 
@@ -177,6 +210,15 @@ impl RfidBuilder {
         self
     }
 
+    /// Append a measurement to the chip data, the tag is then written as `V1`
+    pub fn measurement(mut self, kind: MeasurementType, value: f32) -> Self {
+        self.rfid_data
+            .chip_data
+            .measurements
+            .push(Measurement::new(kind, value));
+        self
+    }
+
     pub fn build(mut self) -> RfidData {
         let crc = self.rfid_data.calc_crc();
         self.rfid_data.crc = crc;
@@ -190,3 +232,166 @@ impl From<RfidData> for RfidBuilder {
         Self { rfid_data }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::SignatureAlgorithm;
+    use crate::error::{InspectionError, KeyError, SignerError};
+    use crate::key::PublicKey;
+    use crate::rfid::{RfidBuilder, RfidData};
+    use crate::signer::file_key::FileKeySigner;
+    use crate::tag_format::TagVersion;
+    use crate::test_util::signed_tag;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::nid::Nid;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use std::collections::HashMap;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_inspect() {
+        let keypair1 = Rsa::generate(2048).unwrap();
+        let keypair2 = Rsa::generate(2048).unwrap();
+        let keypair3 = Rsa::generate(2048).unwrap();
+
+        let key_id1: u32 = 0;
+        let key_id2: u32 = 1;
+        let key_id3: u32 = 2;
+
+        let mut key_map: HashMap<u32, PublicKey> = HashMap::new();
+        key_map.insert(
+            key_id1,
+            PublicKey::new(
+                key_id1,
+                keypair1.public_key_to_pem().unwrap(),
+                "1".to_string(),
+            )
+            .unwrap(),
+        );
+        key_map.insert(
+            key_id2,
+            PublicKey::new(
+                key_id2,
+                keypair2.public_key_to_pem().unwrap(),
+                "2".to_string(),
+            )
+            .unwrap(),
+        );
+        key_map.insert(
+            key_id3,
+            PublicKey::new(
+                key_id3,
+                keypair3.public_key_to_pem().unwrap(),
+                "3".to_string(),
+            )
+            .unwrap(),
+        );
+
+        let data = RfidBuilder::default()
+            .chip_data(42, 5.0, 5.0, 5.0, 5.0)
+            .add_entry(
+                &FileKeySigner::new(keypair1.clone()).unwrap(),
+                key_id1,
+                key_id2,
+                &key_map,
+            )
+            .unwrap()
+            .build();
+
+        assert!(data.inspect(&key_map, &key_map[&key_id2]).is_ok());
+        assert_eq!(
+            data.inspect(&key_map, &key_map[&key_id3]),
+            Err(InspectionError::WrongRecipient(key_id3))
+        );
+
+        // A broken last signature isn't mistaken for a tag sent to someone else
+        let mut tampered = data.clone();
+        tampered.entries[0].signature[0] ^= 1;
+        let tampered = RfidBuilder::from(tampered).build();
+        assert_eq!(
+            tampered.inspect(&key_map, &key_map[&key_id2]),
+            Err(InspectionError::InvalidSignature(0))
+        );
+
+        let mut bad_crc = data.clone();
+        bad_crc.crc = !bad_crc.crc;
+        assert!(matches!(
+            bad_crc.inspect(&key_map, &key_map[&key_id2]),
+            Err(InspectionError::InvalidCrc { .. })
+        ));
+
+        let mut missing_keys = key_map.clone();
+        missing_keys.remove(&key_id1);
+        assert_eq!(
+            data.inspect(&missing_keys, &key_map[&key_id2]),
+            Err(InspectionError::UnknownDistributor(key_id1))
+        );
+
+        // Signing reports a missing key instead of panicking
+        let signer2 = FileKeySigner::new(keypair2.clone()).unwrap();
+        let forwarded = RfidBuilder::from(data.clone())
+            .add_entry(&signer2, key_id2, key_id3, &key_map)
+            .unwrap()
+            .build();
+        let without = |key_id: u32| {
+            let mut keys = key_map.clone();
+            keys.remove(&key_id);
+            keys
+        };
+        let signer3 = FileKeySigner::new(keypair3.clone()).unwrap();
+        for (tag, signer, dist_id, next_dist_id, missing) in [
+            (&data, &signer2, key_id2, key_id3, key_id2),
+            (&forwarded, &signer3, key_id3, key_id1, key_id2),
+            (&data, &signer2, key_id2, key_id3, key_id3),
+        ] {
+            assert!(matches!(
+                RfidBuilder::from(tag.clone()).add_entry(
+                    signer,
+                    dist_id,
+                    next_dist_id,
+                    &without(missing)
+                ),
+                Err(SignerError::UnknownKey(id)) if id == missing
+            ));
+        }
+    }
+
+    #[test]
+    fn test_mixed_algorithms() {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let keypairs = [
+            PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap(),
+            PKey::generate_ed25519().unwrap(),
+            PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap(),
+            PKey::generate_ed25519().unwrap(),
+        ];
+
+        let (data, key_map) = signed_tag(TagVersion::Legacy, &keypairs);
+
+        assert_eq!(key_map[&1].algorithm, SignatureAlgorithm::Ed25519);
+        assert_eq!(key_map[&2].algorithm, SignatureAlgorithm::EcdsaP256);
+        assert_eq!(
+            PublicKey::new(4, b"nope".to_vec(), "4".to_string()).unwrap_err(),
+            KeyError::UnsupportedKey(4)
+        );
+
+        assert!(data.validate_chain(&key_map, key_map[&3].clone()).is_ok());
+
+        let bytes: Vec<u8> = data.clone().into();
+        // Mixed algorithms are written as version 1, each entry prefixed with its algorithm
+        assert_eq!(bytes.len(), 7 + 2 + 2 + 32 + (5 + 256) + 2 * (5 + 64));
+
+        let parsed = RfidData::try_from(bytes).unwrap();
+        assert!(parsed.valid_crc());
+        assert_eq!(parsed.entries[2].algorithm, SignatureAlgorithm::EcdsaP256);
+        assert!(parsed.validate_chain(&key_map, key_map[&3].clone()).is_ok());
+
+        let mut tampered = parsed;
+        tampered.entries[1].signature[0] ^= 1;
+        assert_eq!(
+            tampered.validate_chain(&key_map, key_map[&3].clone()),
+            Err(1)
+        );
+    }
+}
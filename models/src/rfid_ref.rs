@@ -0,0 +1,277 @@
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::algorithm::SignatureAlgorithm;
+use crate::chain::{self, LastHop};
+use crate::checkpoint::Checkpoint;
+use crate::chip_data::ChipData;
+use crate::error::{InspectionError, RfidDataParseError};
+use crate::key::{KeyLookup, PublicKey};
+use crate::rfid::RfidData;
+use crate::supply_chain::SupplyChainEntry;
+use crate::tag_format::{self, TagHeader, TagReader, TagVersion};
+
+/// Borrowed view of a tag's bytes.
+///
+/// Fields are read straight from the tag when they are needed, so a batch of tags can be checked
+/// without copying their entries. Views are only created by parsing, so the tag is always well
+/// formed and its CRC is valid.
+#[derive(Debug, Clone, Copy)]
+pub struct RfidDataRef<'a> {
+    pub(crate) header: Option<TagHeader>,
+    pub(crate) crc: u16,
+    pub(crate) chip_data: &'a [u8],
+    pub(crate) checkpoint: Option<&'a [u8]>,
+    pub(crate) entry_count: u16,
+    pub(crate) entries: &'a [u8],
+}
+
+impl<'a> RfidDataRef<'a> {
+    /// Parse a tag in any supported layout, with the same checks as `RfidData::try_from`
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, RfidDataParseError> {
+        tag_format::decode_ref(bytes)
+    }
+
+    pub fn version(&self) -> TagVersion {
+        self.header.map(|header| header.version).unwrap_or_default()
+    }
+
+    pub fn crc(&self) -> u16 {
+        self.crc
+    }
+
+    pub fn chip_id(&self) -> u128 {
+        BigEndian::read_u128(&self.chip_data[0..16])
+    }
+
+    /// Chip data as stored on the tag, this is what the first entry signed
+    pub fn chip_data_bytes(&self) -> &'a [u8] {
+        self.chip_data
+    }
+
+    pub fn chip_data(&self) -> ChipData {
+        // Checked when the tag was parsed
+        ChipData::from_bytes(self.chip_data).unwrap()
+    }
+
+    /// Entries the central server dropped from a compacted tag
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        // Its size was checked when the tag was parsed
+        self.checkpoint.and_then(Checkpoint::from_bytes)
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.entry_count as usize
+    }
+
+    pub fn entries(&self) -> Entries<'a> {
+        Entries {
+            reader: TagReader::new(self.entries),
+            header: self.header,
+            remaining: self.entry_count,
+        }
+    }
+
+    /// Same as `RfidData::validate_chain`, reading the entries straight from the tag
+    pub fn validate_chain<K: KeyLookup + ?Sized>(
+        &self,
+        keys: &K,
+        public_key: &PublicKey,
+    ) -> Result<(), usize> {
+        chain::validate(keys, self.walk(keys), public_key)
+    }
+
+    /// Walk and verify the entries with `chain::walk`
+    fn walk<K: KeyLookup + ?Sized>(&self, keys: &K) -> Result<Option<LastHop<'a>>, usize> {
+        let checkpoint = self.checkpoint();
+
+        chain::walk(
+            keys,
+            chain::first_signed_data(self.chip_data, checkpoint.as_ref()),
+            checkpoint.is_some(),
+            self.entries(),
+            true,
+        )
+    }
+
+    /// The candidate the last entry was signed over to, walking and verifying the chain once.
+    ///
+    /// Returns `None` if the tag has no entries or the last entry isn't signed over to any of
+    /// `candidates`, and the index of the first entry that fails or whose key is unknown.
+    pub fn recipient<'k, K, C>(
+        &self,
+        keys: &K,
+        candidates: C,
+    ) -> Result<Option<&'k PublicKey>, usize>
+    where
+        K: KeyLookup + ?Sized,
+        C: IntoIterator<Item = &'k PublicKey>,
+    {
+        let last = match self.walk(keys)? {
+            Some(last) => last,
+            None => return Ok(None),
+        };
+
+        keys.get_key(last.entry.pub_key).ok_or(last.ndx)?;
+        Ok(last.recipient(keys, candidates))
+    }
+
+    /// Same as `RfidData::inspect`, the CRC was already checked when the tag was parsed
+    pub fn inspect<K: KeyLookup + ?Sized>(
+        &self,
+        keys: &K,
+        recipient: &PublicKey,
+    ) -> Result<(), InspectionError> {
+        if let Some(entry) = self
+            .entries()
+            .find(|entry| !keys.contains_key_id(entry.pub_key))
+        {
+            return Err(InspectionError::UnknownDistributor(entry.pub_key));
+        }
+
+        chain::inspect(keys, self.walk(keys), recipient)
+    }
+
+    /// Copy the tag into an owned `RfidData`
+    pub fn to_rfid_data(&self) -> RfidData {
+        RfidData {
+            crc: self.crc,
+            chip_data: self.chip_data(),
+            entries: self.entries().map(|entry| entry.to_entry()).collect(),
+            version: self.version(),
+            checkpoint: self.checkpoint(),
+        }
+    }
+}
+
+/// Borrowed view of a single entry on a tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SupplyChainEntryRef<'a> {
+    pub pub_key: u32,
+    pub signature: &'a [u8],
+    pub algorithm: SignatureAlgorithm,
+}
+
+impl<'a> From<&'a SupplyChainEntry> for SupplyChainEntryRef<'a> {
+    fn from(entry: &'a SupplyChainEntry) -> Self {
+        Self {
+            pub_key: entry.pub_key,
+            signature: &entry.signature,
+            algorithm: entry.algorithm,
+        }
+    }
+}
+
+impl<'a> SupplyChainEntryRef<'a> {
+    pub fn to_entry(&self) -> SupplyChainEntry {
+        SupplyChainEntry {
+            pub_key: self.pub_key,
+            signature: self.signature.to_vec(),
+            algorithm: self.algorithm,
+        }
+    }
+}
+
+/// Iterator over the entries of a tag, in the order they were signed
+pub struct Entries<'a> {
+    reader: TagReader<'a>,
+    header: Option<TagHeader>,
+    remaining: u16,
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = SupplyChainEntryRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        // Every entry was read once already when the tag was parsed
+        tag_format::read_entry(&mut self.reader, self.header.as_ref())
+            .ok()
+            .flatten()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+impl<'a> ExactSizeIterator for Entries<'a> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::InspectionError;
+    use crate::key::PublicKey;
+    use crate::rfid::{RfidBuilder, RfidData};
+    use crate::rfid_ref::RfidDataRef;
+    use crate::tag_format::TagVersion;
+    use crate::test_util::signed_tag;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+
+    #[test]
+    fn test_view() {
+        let rsa = || PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let ed25519 = || PKey::generate_ed25519().unwrap();
+
+        // The legacy layout only holds RSA entries
+        for (version, keypairs) in [
+            (TagVersion::Legacy, [rsa(), rsa(), rsa(), rsa()]),
+            (TagVersion::V1, [rsa(), ed25519(), ed25519(), ed25519()]),
+        ] {
+            let (data, key_map) = signed_tag(version, &keypairs);
+            let bytes: Vec<u8> = data.clone().into();
+
+            let view = RfidDataRef::parse(&bytes).unwrap();
+            assert_eq!(view.version(), version);
+            assert_eq!(view.chip_id(), 42);
+            assert_eq!(view.entries().len(), 3);
+            assert_eq!(
+                view.entries().nth(1).unwrap().signature,
+                &data.entries[1].signature[..]
+            );
+            assert!(view.validate_chain(&key_map, &key_map[&3]).is_ok());
+            assert!(view.inspect(&key_map, &key_map[&3]).is_ok());
+            assert_eq!(
+                view.inspect(&key_map, &key_map[&2]),
+                Err(InspectionError::WrongRecipient(2))
+            );
+            let recipient_id = |candidates: Vec<&PublicKey>| {
+                view.recipient(&key_map, candidates)
+                    .map(|key| key.map(|key| key.id))
+            };
+            assert_eq!(recipient_id(key_map.values().collect()), Ok(Some(3)));
+            assert_eq!(recipient_id(vec![&key_map[&2]]), Ok(None));
+
+            let mut tampered = view.to_rfid_data();
+            tampered.entries[2].signature[0] ^= 1;
+            let tampered: Vec<u8> = RfidBuilder::from(tampered).build().into();
+            assert_eq!(
+                RfidDataRef::parse(&tampered)
+                    .unwrap()
+                    .inspect(&key_map, &key_map[&3]),
+                Err(InspectionError::InvalidSignature(2))
+            );
+            assert_eq!(
+                RfidDataRef::parse(&tampered)
+                    .unwrap()
+                    .recipient(&key_map, key_map.values())
+                    .map(|key| key.is_none()),
+                Ok(true)
+            );
+
+            let owned: RfidData = view.to_rfid_data();
+            let reencoded: Vec<u8> = owned.into();
+            assert_eq!(reencoded, bytes);
+
+            let mut unknown = key_map.clone();
+            unknown.remove(&1);
+            assert_eq!(
+                view.inspect(&unknown, &key_map[&3]),
+                Err(InspectionError::UnknownDistributor(1))
+            );
+        }
+    }
+}
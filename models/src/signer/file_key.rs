@@ -0,0 +1,96 @@
+use crate::algorithm::SignatureAlgorithm;
+use crate::error::SignerError;
+use crate::signer::{PassphraseSource, Signer};
+use crate::utility::open_private_key;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use std::path::Path;
+
+/// Signer backed by a private key held in memory.
+///
+/// The key lives in OpenSSL's memory, which clears the private components when the key is freed.
+pub struct FileKeySigner {
+    key: PKey<Private>,
+    algorithm: SignatureAlgorithm,
+}
+
+impl FileKeySigner {
+    pub fn new(private_key: Rsa<Private>) -> Result<Self, SignerError> {
+        Self::from_pkey(PKey::from_rsa(private_key)?)
+    }
+
+    /// Use an RSA-2048, Ed25519 or P-256 key
+    pub fn from_pkey(key: PKey<Private>) -> Result<Self, SignerError> {
+        let algorithm = SignatureAlgorithm::of_key(&key).ok_or_else(|| {
+            SignerError::Unsupported(format!("{:?} keys can't be used to sign", key.id()))
+        })?;
+
+        Ok(Self { key, algorithm })
+    }
+
+    pub fn from_pem(private_key: &[u8]) -> Result<Self, SignerError> {
+        Self::from_pkey(PKey::private_key_from_pem(private_key)?)
+    }
+
+    /// Open a PEM key file, either a PKCS#1 key or a PKCS#8 key that may be encrypted
+    pub fn open(path: &Path, passphrase: &dyn PassphraseSource) -> Result<Self, SignerError> {
+        Self::from_pkey(open_private_key(path, passphrase)?)
+    }
+
+    pub fn algorithm(&self) -> SignatureAlgorithm {
+        self.algorithm
+    }
+}
+
+impl Signer for FileKeySigner {
+    fn sign(&self, members: &[&[u8]]) -> Result<Vec<u8>, SignerError> {
+        Ok(self.algorithm.sign(&self.key, members)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::SignerError;
+    use crate::signer::file_key::FileKeySigner;
+    use crate::signer::{NoPassphrase, PassphraseSource};
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::symm::Cipher;
+    use std::path::Path;
+    use zeroize::Zeroizing;
+
+    struct FixedPassphrase(&'static str);
+
+    impl PassphraseSource for FixedPassphrase {
+        fn passphrase(&self, _key_path: &Path) -> Result<Zeroizing<String>, SignerError> {
+            Ok(Zeroizing::new(self.0.to_string()))
+        }
+    }
+
+    #[test]
+    fn test_encrypted_key() {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let pem = key
+            .private_key_to_pem_pkcs8_passphrase(Cipher::aes_256_cbc(), b"hunter2")
+            .unwrap();
+
+        let path = std::env::temp_dir().join("test_encrypted_key.pem");
+        std::fs::write(&path, pem).unwrap();
+
+        assert!(FileKeySigner::open(&path, &FixedPassphrase("hunter2")).is_ok());
+        assert!(matches!(
+            FileKeySigner::open(&path, &FixedPassphrase("wrong")),
+            Err(SignerError::BadPassphrase(_))
+        ));
+        assert!(matches!(
+            FileKeySigner::open(&path, &NoPassphrase),
+            Err(SignerError::PassphraseRequired(_))
+        ));
+        assert!(matches!(
+            FileKeySigner::open(Path::new("missing.pem"), &NoPassphrase),
+            Err(SignerError::KeyFileError { .. })
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,49 @@
+pub mod file_key;
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
+
+use crate::error::SignerError;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Signs blockchain entries and requests on behalf of a distributor or the central server.
+///
+/// The signature covers the concatenated `members`, using the `SignatureAlgorithm` of the key.
+pub trait Signer: Send + Sync {
+    fn sign(&self, members: &[&[u8]]) -> Result<Vec<u8>, SignerError>;
+}
+
+/// Supplies the passphrase of an encrypted key file, only asked when the key is encrypted
+pub trait PassphraseSource {
+    fn passphrase(&self, key_path: &Path) -> Result<Zeroizing<String>, SignerError>;
+}
+
+/// Passphrase source for keys that are never encrypted
+pub struct NoPassphrase;
+
+impl PassphraseSource for NoPassphrase {
+    fn passphrase(&self, key_path: &Path) -> Result<Zeroizing<String>, SignerError> {
+        Err(SignerError::PassphraseRequired(key_path.to_path_buf()))
+    }
+}
+
+/// Open a signer from a private key path or a `pkcs11:` URI
+pub fn open_signer(
+    spec: &str,
+    passphrase: &dyn PassphraseSource,
+) -> Result<Box<dyn Signer>, SignerError> {
+    if spec.starts_with("pkcs11:") {
+        #[cfg(feature = "pkcs11")]
+        return Ok(Box::new(pkcs11::Pkcs11Signer::from_uri(spec)?));
+
+        #[cfg(not(feature = "pkcs11"))]
+        return Err(SignerError::Unsupported(
+            "PKCS#11 support is not enabled".to_string(),
+        ));
+    }
+
+    Ok(Box::new(file_key::FileKeySigner::open(
+        Path::new(spec),
+        passphrase,
+    )?))
+}
@@ -0,0 +1,568 @@
+//! Signer for keys held on a PKCS#11 token such as an HSM or SoftHSM.
+//!
+//! The algorithm follows the key found on the token:
+//!
+//! * RSA-2048 keys sign with `CKM_RSA_PKCS`. The SHA3-256 hash is computed locally and wrapped in a
+//!   DigestInfo, which gives the same signature as signing with the key directly without relying
+//!   on the token supporting SHA3 mechanisms.
+//! * P-256 keys sign a locally computed SHA-256 hash with `CKM_ECDSA`.
+//! * Ed25519 keys sign the data itself with `CKM_EDDSA`.
+
+use crate::algorithm::{SignatureAlgorithm, P256_SCALAR_SIZE};
+use crate::error::SignerError;
+use crate::signer::Signer;
+use cryptoki::context::{CInitializeArgs, CInitializeFlags, Pkcs11};
+use cryptoki::error::{Error, RvError};
+use cryptoki::mechanism::eddsa::{EddsaParams, EddsaSignatureScheme};
+use cryptoki::mechanism::Mechanism;
+use cryptoki::object::{Attribute, AttributeType, KeyType, ObjectClass, ObjectHandle};
+use cryptoki::session::{Session, UserType};
+use cryptoki::slot::Slot;
+use cryptoki::types::AuthPin;
+use openssl::bn::BigNum;
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::{hash, Hasher, MessageDigest};
+use percent_encoding::percent_decode_str;
+use std::convert::TryFrom;
+use std::sync::Mutex;
+use zeroize::Zeroizing;
+
+/// DER prefix of a PKCS#1 DigestInfo for SHA3-256
+const SHA3_256_DIGEST_INFO: [u8; 19] = [
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x08, 0x05,
+    0x00, 0x04, 0x20,
+];
+
+/// DER encoded OID of the P-256 curve, as found in `CKA_EC_PARAMS`
+const P256_EC_PARAMS: [u8; 10] = [0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+
+/// `CKA_EC_PARAMS` of an Ed25519 key, either the curve's OID or its name as a PrintableString
+const ED25519_EC_PARAMS: [&[u8]; 2] = [&[0x06, 0x03, 0x2b, 0x65, 0x70], b"\x13\x0cedwards25519"];
+
+/// Bytes of an RSA-2048 modulus
+const RSA_2048_MODULUS_SIZE: usize = 256;
+
+/// Parameters parsed from an RFC 7512 PKCS#11 URI
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pkcs11Uri {
+    pub module_path: String,
+    pub token: Option<String>,
+    pub slot_id: Option<u64>,
+    pub object: Option<String>,
+    pub id: Option<Vec<u8>>,
+    pub pin: Option<Zeroizing<String>>,
+}
+
+impl Pkcs11Uri {
+    /// Parse a URI such as
+    /// `pkcs11:token=dist;object=signing-key?module-path=/usr/lib/softhsm/libsofthsm2.so&pin-value=1234`
+    pub fn parse(uri: &str) -> Result<Self, SignerError> {
+        let uri = uri
+            .strip_prefix("pkcs11:")
+            .ok_or_else(|| SignerError::InvalidUri("missing pkcs11: scheme".to_string()))?;
+
+        let mut parts = uri.splitn(2, '?');
+        let path = parts.next().unwrap_or("");
+        let query = parts.next().unwrap_or("");
+
+        let mut parsed = Self::default();
+        let mut pin_source = None;
+
+        let attributes = path
+            .split(';')
+            .map(|attr| (attr, false))
+            .chain(query.split('&').map(|attr| (attr, true)))
+            .filter(|(attr, _)| !attr.is_empty());
+
+        for (attr, is_query) in attributes {
+            let mut attr = attr.splitn(2, '=');
+            let name = attr.next().unwrap();
+            let value = attr
+                .next()
+                .ok_or_else(|| SignerError::InvalidUri(format!("{} has no value", name)))?;
+            let bytes: Vec<u8> = percent_decode_str(value).collect();
+            let string = String::from_utf8_lossy(&bytes).to_string();
+
+            match (name, is_query) {
+                ("token", false) => parsed.token = Some(string),
+                ("object", false) => parsed.object = Some(string),
+                ("id", false) => parsed.id = Some(bytes),
+                ("slot-id", false) => {
+                    parsed.slot_id = Some(string.parse().map_err(|_| {
+                        SignerError::InvalidUri(format!("invalid slot-id {}", string))
+                    })?)
+                }
+                ("module-path", true) => parsed.module_path = string,
+                ("pin-value", true) => parsed.pin = Some(Zeroizing::new(string)),
+                ("pin-source", true) => pin_source = Some(string),
+                _ => {}
+            }
+        }
+
+        if let Some(pin_source) = pin_source {
+            let path = pin_source.strip_prefix("file:").unwrap_or(&pin_source);
+            let pin = Zeroizing::new(std::fs::read_to_string(path)?);
+            parsed.pin = Some(Zeroizing::new(pin.trim_end().to_string()));
+        }
+
+        if parsed.module_path.is_empty() {
+            return Err(SignerError::InvalidUri("missing module-path".to_string()));
+        }
+
+        if parsed.slot_id.is_some() && parsed.token.is_some() {
+            return Err(SignerError::InvalidUri(
+                "give either a slot-id or a token label, not both".to_string(),
+            ));
+        }
+
+        if parsed.object.is_none() && parsed.id.is_none() {
+            return Err(SignerError::InvalidUri(
+                "an object or id is required to find the key".to_string(),
+            ));
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Signer backed by a private key that never leaves a PKCS#11 token
+pub struct Pkcs11Signer {
+    session: Mutex<Session>,
+    key: ObjectHandle,
+    algorithm: SignatureAlgorithm,
+}
+
+impl Pkcs11Signer {
+    pub fn from_uri(uri: &str) -> Result<Self, SignerError> {
+        Self::open(&Pkcs11Uri::parse(uri)?)
+    }
+
+    pub fn open(uri: &Pkcs11Uri) -> Result<Self, SignerError> {
+        let pkcs11 = Pkcs11::new(&uri.module_path).map_err(|e| {
+            SignerError::Pkcs11Error(format!("Unable to load PKCS#11 module: {}", e))
+        })?;
+
+        // The module is left initialized, other signers in the process may share it
+        match pkcs11.initialize(CInitializeArgs::new(CInitializeFlags::OS_LOCKING_OK)) {
+            Ok(()) | Err(Error::Pkcs11(RvError::CryptokiAlreadyInitialized, _)) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let slot = Self::find_slot(&pkcs11, uri)?;
+        let session = pkcs11.open_ro_session(slot)?;
+
+        if let Some(pin) = &uri.pin {
+            match session.login(UserType::User, Some(&AuthPin::from(pin.as_str()))) {
+                Ok(()) | Err(Error::Pkcs11(RvError::UserAlreadyLoggedIn, _)) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let key = Self::find_key(&session, uri)?;
+        let algorithm = Self::key_algorithm(&session, key)?;
+
+        Ok(Self {
+            session: Mutex::new(session),
+            key,
+            algorithm,
+        })
+    }
+
+    pub fn algorithm(&self) -> SignatureAlgorithm {
+        self.algorithm
+    }
+
+    fn find_slot(pkcs11: &Pkcs11, uri: &Pkcs11Uri) -> Result<Slot, SignerError> {
+        if let Some(slot_id) = uri.slot_id {
+            return Ok(Slot::try_from(slot_id)?);
+        }
+
+        for slot in pkcs11.get_slots_with_token()? {
+            let token = match &uri.token {
+                Some(token) => token,
+                None => return Ok(slot),
+            };
+
+            // Token labels are padded with spaces
+            if pkcs11.get_token_info(slot)?.label().trim_end() == token {
+                return Ok(slot);
+            }
+        }
+
+        Err(SignerError::KeyNotFound(format!(
+            "no token matching {:?}",
+            uri.token
+        )))
+    }
+
+    fn find_key(session: &Session, uri: &Pkcs11Uri) -> Result<ObjectHandle, SignerError> {
+        let mut template = vec![Attribute::Class(ObjectClass::PRIVATE_KEY)];
+        if let Some(object) = &uri.object {
+            template.push(Attribute::Label(object.clone().into_bytes()));
+        }
+        if let Some(id) = &uri.id {
+            template.push(Attribute::Id(id.clone()));
+        }
+
+        session
+            .find_objects(&template)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                SignerError::KeyNotFound(format!(
+                    "no private key matching object {:?} id {:?}",
+                    uri.object, uri.id
+                ))
+            })
+    }
+
+    /// Signature algorithm of the key's type and size or curve
+    fn key_algorithm(
+        session: &Session,
+        key: ObjectHandle,
+    ) -> Result<SignatureAlgorithm, SignerError> {
+        let attributes = session.get_attributes(
+            key,
+            &[
+                AttributeType::KeyType,
+                AttributeType::Modulus,
+                AttributeType::EcParams,
+            ],
+        )?;
+
+        let mut key_type = None;
+        let mut modulus_size = 0;
+        let mut ec_params = Vec::new();
+        for attribute in attributes {
+            match attribute {
+                Attribute::KeyType(value) => key_type = Some(value),
+                Attribute::Modulus(modulus) => modulus_size = modulus.len(),
+                Attribute::EcParams(params) => ec_params = params,
+                _ => {}
+            }
+        }
+
+        match key_type {
+            Some(KeyType::RSA) if modulus_size == RSA_2048_MODULUS_SIZE => {
+                Ok(SignatureAlgorithm::RsaSha3)
+            }
+            Some(KeyType::EC) if ec_params == P256_EC_PARAMS => Ok(SignatureAlgorithm::EcdsaP256),
+            Some(KeyType::EC_EDWARDS) if ED25519_EC_PARAMS.contains(&ec_params.as_slice()) => {
+                Ok(SignatureAlgorithm::Ed25519)
+            }
+            key_type => Err(SignerError::Unsupported(format!(
+                "{:?} keys on a PKCS#11 token can't be used to sign",
+                key_type
+            ))),
+        }
+    }
+}
+
+/// Convert an ECDSA signature from the token to r and s, each padded to the size of a P-256
+/// scalar. `CKM_ECDSA` already returns r and s, but some tokens trim leading zeros or return DER.
+fn ecdsa_raw(signature: &[u8]) -> Result<Vec<u8>, SignerError> {
+    let (r, s) = match EcdsaSig::from_der(signature) {
+        Ok(sig) if signature.len() != 2 * P256_SCALAR_SIZE => {
+            (sig.r().to_owned()?, sig.s().to_owned()?)
+        }
+        _ if signature.len().is_multiple_of(2) => {
+            let (r, s) = signature.split_at(signature.len() / 2);
+            (BigNum::from_slice(r)?, BigNum::from_slice(s)?)
+        }
+        _ => {
+            return Err(SignerError::Pkcs11Error(format!(
+                "malformed ECDSA signature of {} bytes",
+                signature.len()
+            )))
+        }
+    };
+
+    let mut raw = r.to_vec_padded(P256_SCALAR_SIZE as i32)?;
+    raw.extend_from_slice(&s.to_vec_padded(P256_SCALAR_SIZE as i32)?);
+
+    Ok(raw)
+}
+
+impl Signer for Pkcs11Signer {
+    fn sign(&self, members: &[&[u8]]) -> Result<Vec<u8>, SignerError> {
+        let session = self.session.lock().unwrap();
+
+        match self.algorithm {
+            SignatureAlgorithm::RsaSha3 => {
+                let mut hasher = Hasher::new(MessageDigest::sha3_256())?;
+                for member in members {
+                    hasher.update(member)?;
+                }
+
+                let mut digest_info = SHA3_256_DIGEST_INFO.to_vec();
+                digest_info.extend_from_slice(&hasher.finish()?);
+
+                Ok(session.sign(&Mechanism::RsaPkcs, self.key, &digest_info)?)
+            }
+            SignatureAlgorithm::EcdsaP256 => {
+                let digest = hash(MessageDigest::sha256(), &members.concat())?;
+                let signature = session.sign(&Mechanism::Ecdsa, self.key, &digest)?;
+
+                ecdsa_raw(&signature)
+            }
+            SignatureAlgorithm::Ed25519 => {
+                let mechanism = Mechanism::Eddsa(EddsaParams::new(EddsaSignatureScheme::Ed25519));
+
+                Ok(session.sign(&mechanism, self.key, &members.concat())?)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::SignatureAlgorithm;
+    use crate::crypto::verify;
+    use crate::signer::pkcs11::{ecdsa_raw, Pkcs11Signer, Pkcs11Uri};
+    use crate::signer::Signer;
+    use cryptoki::context::{CInitializeArgs, CInitializeFlags, Pkcs11};
+    use cryptoki::mechanism::Mechanism;
+    use cryptoki::object::{Attribute, AttributeType, ObjectHandle};
+    use cryptoki::session::{Session, UserType};
+    use cryptoki::types::AuthPin;
+    use openssl::bn::{BigNum, BigNumContext};
+    use openssl::ec::{EcGroup, EcKey, EcPoint};
+    use openssl::ecdsa::EcdsaSig;
+    use openssl::nid::Nid;
+    use openssl::pkey::{Id, PKey};
+    use openssl::rsa::Rsa;
+
+    #[test]
+    fn test_parse_uri() {
+        let uri = Pkcs11Uri::parse(
+            "pkcs11:token=My%20Token;object=dist-key;id=%01%02?module-path=/usr/lib/softhsm/libsofthsm2.so&pin-value=1234",
+        )
+        .unwrap();
+
+        assert_eq!(uri.module_path, "/usr/lib/softhsm/libsofthsm2.so");
+        assert_eq!(uri.token, Some("My Token".to_string()));
+        assert_eq!(uri.object, Some("dist-key".to_string()));
+        assert_eq!(uri.id, Some(vec![1, 2]));
+        assert_eq!(uri.pin.as_ref().map(|pin| pin.as_str()), Some("1234"));
+
+        assert!(Pkcs11Uri::parse("pkcs11:object=dist-key").is_err());
+        assert!(Pkcs11Uri::parse("pkcs11:?module-path=/lib.so").is_err());
+        assert!(Pkcs11Uri::parse(
+            "pkcs11:token=dist;slot-id=1;object=dist-key?module-path=/lib.so"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_ecdsa_raw() {
+        let r = [0x01; 32];
+        let s = [0x02; 32];
+        let raw = [r, s].concat();
+        assert_eq!(ecdsa_raw(&raw).unwrap(), raw);
+
+        let der = EcdsaSig::from_private_components(
+            BigNum::from_slice(&r).unwrap(),
+            BigNum::from_slice(&s).unwrap(),
+        )
+        .unwrap()
+        .to_der()
+        .unwrap();
+        assert_eq!(ecdsa_raw(&der).unwrap(), raw);
+
+        // Halves with their leading zeros trimmed are padded again
+        let mut padded = raw.clone();
+        padded[0] = 0;
+        padded[32] = 0;
+        assert_eq!(
+            ecdsa_raw(&[&r[1..], &s[1..]].concat()).unwrap(),
+            [&[0], &r[1..], &[0], &s[1..]].concat()
+        );
+        assert_eq!(ecdsa_raw(&padded).unwrap(), padded);
+
+        assert!(ecdsa_raw(&raw[..63]).is_err());
+        assert!(ecdsa_raw(&[0x01; 66]).is_err());
+    }
+
+    /// Key pair generated on the token, returning the PEM of its public key
+    fn generate_key(
+        session: &Session,
+        label: &str,
+        mechanism: Mechanism,
+        public_template: &[Attribute],
+    ) -> Vec<u8> {
+        let mut public_template = public_template.to_vec();
+        public_template.extend_from_slice(&[Attribute::Token(true), Attribute::Verify(true)]);
+        let private_template = [
+            Attribute::Token(true),
+            Attribute::Private(true),
+            Attribute::Sign(true),
+            Attribute::Label(label.as_bytes().to_vec()),
+        ];
+
+        let (public, _) = session
+            .generate_key_pair(&mechanism, &public_template, &private_template)
+            .unwrap();
+
+        public_key_pem(session, public, &mechanism)
+    }
+
+    fn public_key_pem(session: &Session, public: ObjectHandle, mechanism: &Mechanism) -> Vec<u8> {
+        let attributes = session
+            .get_attributes(
+                public,
+                &[
+                    AttributeType::Modulus,
+                    AttributeType::PublicExponent,
+                    AttributeType::EcPoint,
+                ],
+            )
+            .unwrap();
+        let attribute = |wanted: AttributeType| {
+            attributes
+                .iter()
+                .find(|attribute| attribute.attribute_type() == wanted)
+                .map(|attribute| match attribute {
+                    Attribute::Modulus(value)
+                    | Attribute::PublicExponent(value)
+                    | Attribute::EcPoint(value) => value.clone(),
+                    _ => unreachable!(),
+                })
+                .unwrap()
+        };
+        // The point is wrapped in a DER OCTET STRING
+        let point = || attribute(AttributeType::EcPoint)[2..].to_vec();
+
+        let key = match mechanism {
+            Mechanism::RsaPkcsKeyPairGen => PKey::from_rsa(
+                Rsa::from_public_components(
+                    BigNum::from_slice(&attribute(AttributeType::Modulus)).unwrap(),
+                    BigNum::from_slice(&attribute(AttributeType::PublicExponent)).unwrap(),
+                )
+                .unwrap(),
+            )
+            .unwrap(),
+            Mechanism::EccKeyPairGen => {
+                let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+                let mut ctx = BigNumContext::new().unwrap();
+                let point = EcPoint::from_bytes(&group, &point(), &mut ctx).unwrap();
+                PKey::from_ec_key(EcKey::from_public_key(&group, &point).unwrap()).unwrap()
+            }
+            _ => PKey::public_key_from_raw_bytes(&point(), Id::ED25519).unwrap(),
+        };
+
+        key.public_key_to_pem().unwrap()
+    }
+
+    /// Signs with RSA, P-256 and Ed25519 keys generated on a fresh SoftHSM token, and verifies the
+    /// signatures the way tag entries are verified.
+    ///
+    /// Only runs with `SOFTHSM2_MODULE` set to the path of `libsofthsm2.so`. The token is created
+    /// under the temporary directory.
+    #[test]
+    fn test_softhsm_signer() {
+        let module = match std::env::var("SOFTHSM2_MODULE") {
+            Ok(module) => module,
+            Err(_) => {
+                println!("SOFTHSM2_MODULE is not set, skipping the SoftHSM test");
+                return;
+            }
+        };
+
+        let dir = std::env::temp_dir().join("rfsc_test_softhsm");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("tokens")).unwrap();
+        let conf = dir.join("softhsm2.conf");
+        std::fs::write(
+            &conf,
+            format!("directories.tokendir = {}\n", dir.join("tokens").display()),
+        )
+        .unwrap();
+        std::env::set_var("SOFTHSM2_CONF", &conf);
+
+        let pkcs11 = Pkcs11::new(&module).unwrap();
+        pkcs11
+            .initialize(CInitializeArgs::new(CInitializeFlags::OS_LOCKING_OK))
+            .unwrap();
+        let pin = AuthPin::from("1234");
+        let free_slot = pkcs11.get_slots_with_token().unwrap()[0];
+        pkcs11.init_token(free_slot, &pin, "test").unwrap();
+
+        // SoftHSM moves an initialized token to a new slot
+        let slot = pkcs11
+            .get_slots_with_token()
+            .unwrap()
+            .into_iter()
+            .find(|&slot| pkcs11.get_token_info(slot).unwrap().label() == "test")
+            .unwrap();
+        let session = pkcs11.open_rw_session(slot).unwrap();
+        session.login(UserType::So, Some(&pin)).unwrap();
+        session.init_pin(&pin).unwrap();
+        session.logout().unwrap();
+        session.login(UserType::User, Some(&pin)).unwrap();
+
+        let p256 = vec![0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+        let ed25519 = vec![0x06, 0x03, 0x2b, 0x65, 0x70];
+        let keys = [
+            (
+                "rsa-key",
+                SignatureAlgorithm::RsaSha3,
+                generate_key(
+                    &session,
+                    "rsa-key",
+                    Mechanism::RsaPkcsKeyPairGen,
+                    &[
+                        Attribute::ModulusBits(2048.into()),
+                        Attribute::PublicExponent(vec![0x01, 0x00, 0x01]),
+                    ],
+                ),
+            ),
+            (
+                "p256-key",
+                SignatureAlgorithm::EcdsaP256,
+                generate_key(
+                    &session,
+                    "p256-key",
+                    Mechanism::EccKeyPairGen,
+                    &[Attribute::EcParams(p256)],
+                ),
+            ),
+            (
+                "ed25519-key",
+                SignatureAlgorithm::Ed25519,
+                generate_key(
+                    &session,
+                    "ed25519-key",
+                    Mechanism::EccEdwardsKeyPairGen,
+                    &[Attribute::EcParams(ed25519)],
+                ),
+            ),
+        ];
+
+        for (label, algorithm, public_key) in keys.iter() {
+            let signer = Pkcs11Signer::from_uri(&format!(
+                "pkcs11:token=test;object={}?module-path={}&pin-value=1234",
+                label, module
+            ))
+            .unwrap();
+            assert_eq!(signer.algorithm(), *algorithm);
+
+            let signature = signer.sign(&[b"\xDE\xAD", b"\xBE\xEF"]).unwrap();
+            assert!(verify(
+                *algorithm,
+                public_key,
+                b"\xDE\xAD\xBE\xEF",
+                &signature
+            ));
+        }
+
+        assert!(matches!(
+            Pkcs11Signer::from_uri(&format!(
+                "pkcs11:token=test;object=missing?module-path={}&pin-value=1234",
+                module
+            )),
+            Err(crate::error::SignerError::KeyNotFound(_))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
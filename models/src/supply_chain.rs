@@ -1,13 +1,19 @@
 use serde::{Deserialize, Serialize};
-use std::convert::TryFrom;
-use std::io::{Cursor, Read};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
 
+use crate::algorithm::SignatureAlgorithm;
 use crate::error::RfidDataParseError;
+#[cfg(feature = "std")]
+use crate::error::SignerError;
+#[cfg(feature = "std")]
+use crate::key::PublicKey;
+#[cfg(feature = "std")]
+use crate::signer::Signer;
+use crate::tag_format;
 use crate::{deserialize_base64, serialize_base64, BlockChainEntry};
-use crate::SIGNATURE_SIZE;
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct SupplyChainEntry {
     pub pub_key: u32,
     #[serde(
@@ -15,15 +21,16 @@ pub struct SupplyChainEntry {
         deserialize_with = "deserialize_base64"
     )]
     pub signature: Vec<u8>,
+    #[serde(default)]
+    pub algorithm: SignatureAlgorithm,
 }
 
+/// Entry in the legacy layout, which only holds RSA entries
 impl Into<Vec<u8>> for SupplyChainEntry {
     fn into(self) -> Vec<u8> {
-        let mut buffer = Vec::new();
-        let pub_key = self.pub_key;
         let mut signature = self.signature;
 
-        buffer.write_u32::<LittleEndian>(pub_key).unwrap();
+        let mut buffer = self.pub_key.to_le_bytes().to_vec();
         buffer.append(&mut signature);
 
         buffer
@@ -34,30 +41,25 @@ impl TryFrom<Vec<u8>> for SupplyChainEntry {
     type Error = RfidDataParseError;
 
     fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
-        let mut cursor = Cursor::new(bytes);
-
-        let pub_key = cursor.read_u32::<LittleEndian>()?;
-
-        let mut signature = vec![0u8; SIGNATURE_SIZE];
-        cursor.read_exact(&mut signature)?;
-
-        Ok(Self { pub_key, signature })
+        tag_format::decode_entry(&bytes)
     }
 }
 
 impl SupplyChainEntry {
+    #[cfg(feature = "std")]
     pub fn new(
-        private_key: Vec<u8>,
+        signer: &dyn Signer,
         next_public_key: Vec<u8>,
         rfid_data: Vec<u8>,
-        public_key_id: u32,
-    ) -> SupplyChainEntry {
-        let signature = Self::create_signature(private_key, vec![rfid_data, next_public_key]);
+        public_key: &PublicKey,
+    ) -> Result<SupplyChainEntry, SignerError> {
+        let signature = signer.sign(&[&rfid_data, &next_public_key])?;
 
-        Self {
-            pub_key: public_key_id,
+        Ok(Self {
+            pub_key: public_key.id,
             signature,
-        }
+            algorithm: public_key.algorithm,
+        })
     }
 }
 
@@ -0,0 +1,672 @@
+//! Binary layouts of an RFID tag.
+//!
+//! Legacy tags have no header:
+//!
+//! | Field       | Size | Encoding                                                      |
+//! |-------------|------|---------------------------------------------------------------|
+//! | CRC         | 2    | CRC-16/X-25 of the rest of the tag, low byte first            |
+//! | Entry count | 2    | `u16`, big endian                                             |
+//! | Chip data   | 32   | `ChipData`                                                    |
+//! | Entries     |      | key ID as a little endian `u32`, then an RSA signature        |
+//!
+//! Version 1 tags start with a header:
+//!
+//! | Field       | Size | Encoding                                                      |
+//! |-------------|------|---------------------------------------------------------------|
+//! | Magic       | 4    | `RFSC`                                                        |
+//! | Version     | 1    | `1`                                                           |
+//! | Algorithm   | 1    | `SignatureAlgorithm::id` shared by every entry                |
+//! | Flags       | 1    | `FLAG_PER_ENTRY_ALGORITHM`, `FLAG_CHECKPOINT`, `FLAG_MEASUREMENTS` |
+//! | CRC         | 2    | CRC-16/X-25 of every other byte of the tag, big endian        |
+//! | Entry count | 2    | `u16`, big endian                                             |
+//! | Chip data   | 32   | `ChipData`                                                    |
+//! | Measurements |     | `measurement` section, only if `FLAG_MEASUREMENTS` is set     |
+//! | Checkpoint  | 34   | `Checkpoint`, only if `FLAG_CHECKPOINT` is set                |
+//! | Entries     |      | algorithm ID if `FLAG_PER_ENTRY_ALGORITHM` is set, key ID as a big endian `u32`, then the signature |
+//!
+//! A legacy tag can't start with the magic, it would need more entries than fit in `MAX_TAG_SIZE`.
+//! It can't hold a checkpoint, measurements or entries of other algorithms than RSA either, so those
+//! tags are always written as version 1.
+
+use crate::algorithm::SignatureAlgorithm;
+use crate::checkpoint::Checkpoint;
+use crate::chip_data::ChipData;
+use crate::error::RfidDataParseError;
+use crate::rfid::RfidData;
+use crate::rfid_ref::{RfidDataRef, SupplyChainEntryRef};
+use crate::supply_chain::SupplyChainEntry;
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use crc::crc16;
+use serde::{Deserialize, Serialize};
+
+pub const TAG_MAGIC: [u8; 4] = *b"RFSC";
+
+/// Entries are prefixed with their own algorithm ID instead of sharing the header's
+pub const FLAG_PER_ENTRY_ALGORITHM: u8 = 0x01;
+
+/// A checkpoint follows the chip data and the first entry is the central server's checkpoint entry
+pub const FLAG_CHECKPOINT: u8 = 0x02;
+
+/// The chip data is followed by a measurement section, the first entry signs both
+pub const FLAG_MEASUREMENTS: u8 = 0x04;
+
+/// Largest tag the parser accepts, bigger than the user memory of any tag we write
+pub const MAX_TAG_SIZE: usize = 8192;
+
+const HEADER_SIZE: usize = 7;
+const LEGACY_PREFIX_SIZE: usize = 2 + 2 + ChipData::SIZE;
+const V1_PREFIX_SIZE: usize = HEADER_SIZE + 2 + 2 + ChipData::SIZE;
+
+/// Binary layout a tag is written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TagVersion {
+    /// Tags written before the header existed
+    #[default]
+    Legacy,
+    V1,
+}
+
+impl TagVersion {
+    pub fn id(&self) -> u8 {
+        match self {
+            TagVersion::Legacy => 0,
+            TagVersion::V1 => 1,
+        }
+    }
+}
+
+/// Header of a versioned tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TagHeader {
+    pub version: TagVersion,
+    pub algorithm: SignatureAlgorithm,
+    pub flags: u8,
+}
+
+impl TagHeader {
+    fn for_tag(rfid_data: &RfidData) -> Self {
+        let entries = &rfid_data.entries;
+        let algorithm = entries
+            .first()
+            .map(|entry| entry.algorithm)
+            .unwrap_or_default();
+
+        let mut flags = if entries.iter().all(|entry| entry.algorithm == algorithm) {
+            0
+        } else {
+            FLAG_PER_ENTRY_ALGORITHM
+        };
+        if rfid_data.checkpoint.is_some() {
+            flags |= FLAG_CHECKPOINT;
+        }
+        if !rfid_data.chip_data.measurements.is_empty() {
+            flags |= FLAG_MEASUREMENTS;
+        }
+
+        Self {
+            version: TagVersion::V1,
+            algorithm,
+            flags,
+        }
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&TAG_MAGIC);
+        bytes.push(self.version.id());
+        bytes.push(self.algorithm.id());
+        bytes.push(self.flags);
+    }
+
+    /// Parse the header following the magic
+    fn read(reader: &mut TagReader) -> Result<Self, RfidDataParseError> {
+        let version = match reader.u8() {
+            Some(1) => TagVersion::V1,
+            Some(version) => return Err(RfidDataParseError::UnsupportedVersion(version)),
+            None => return Err(reader.truncated_header(V1_PREFIX_SIZE)),
+        };
+
+        let (algorithm, flags) = match (reader.u8(), reader.u8()) {
+            (Some(algorithm), Some(flags)) => (algorithm_from_id(algorithm)?, flags),
+            _ => return Err(reader.truncated_header(V1_PREFIX_SIZE)),
+        };
+
+        if flags & !(FLAG_PER_ENTRY_ALGORITHM | FLAG_CHECKPOINT | FLAG_MEASUREMENTS) != 0 {
+            return Err(RfidDataParseError::UnsupportedFlags(flags));
+        }
+
+        Ok(Self {
+            version,
+            algorithm,
+            flags,
+        })
+    }
+
+    fn per_entry_algorithm(&self) -> bool {
+        self.flags & FLAG_PER_ENTRY_ALGORITHM != 0
+    }
+
+    fn has_checkpoint(&self) -> bool {
+        self.flags & FLAG_CHECKPOINT != 0
+    }
+
+    fn has_measurements(&self) -> bool {
+        self.flags & FLAG_MEASUREMENTS != 0
+    }
+}
+
+/// Reads fields off the front of a tag without ever reading past its end
+pub(crate) struct TagReader<'a> {
+    bytes: &'a [u8],
+    len: usize,
+}
+
+impl<'a> TagReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            len: bytes.len(),
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < len {
+            return None;
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(BigEndian::read_u16)
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn truncated_header(&self, expected: usize) -> RfidDataParseError {
+        RfidDataParseError::TruncatedHeader {
+            expected,
+            actual: self.len,
+        }
+    }
+}
+
+fn algorithm_from_id(id: u8) -> Result<SignatureAlgorithm, RfidDataParseError> {
+    SignatureAlgorithm::from_id(id).ok_or(RfidDataParseError::UnknownAlgorithm(id))
+}
+
+/// CRC of the tag as stored in its CRC field
+pub fn crc(rfid_data: &RfidData) -> u16 {
+    crc_of(layout_version(rfid_data), &encode(rfid_data))
+}
+
+/// Layout the tag is encoded in, its own version unless that can't hold its checkpoint,
+/// measurements or entries
+fn layout_version(rfid_data: &RfidData) -> TagVersion {
+    let legacy_entries = rfid_data
+        .entries
+        .iter()
+        .all(|entry| entry.algorithm == SignatureAlgorithm::RsaSha3);
+
+    if rfid_data.checkpoint.is_some()
+        || !rfid_data.chip_data.measurements.is_empty()
+        || !legacy_entries
+    {
+        TagVersion::V1
+    } else {
+        rfid_data.version
+    }
+}
+
+/// CRC of encoded tag bytes, `bytes` must hold at least the tag's header
+fn crc_of(version: TagVersion, bytes: &[u8]) -> u16 {
+    match version {
+        // Legacy tags are read back with the CRC's bytes swapped, keep comparing it the same way
+        TagVersion::Legacy => crc16::checksum_x25(&bytes[2..]).swap_bytes(),
+        TagVersion::V1 => {
+            let header_crc = crc16::checksum_x25(&bytes[..HEADER_SIZE]);
+            crc16::update(header_crc, &crc16::X25_TABLE, &bytes[HEADER_SIZE + 2..])
+        }
+    }
+}
+
+/// Encode the tag in its version's layout, the CRC is always recomputed
+pub fn encode(rfid_data: &RfidData) -> Vec<u8> {
+    let version = layout_version(rfid_data);
+    let mut bytes = match version {
+        TagVersion::Legacy => encode_legacy(rfid_data),
+        TagVersion::V1 => encode_v1(rfid_data),
+    };
+
+    let crc = crc_of(version, &bytes);
+    let offset = match version {
+        TagVersion::Legacy => 0,
+        TagVersion::V1 => HEADER_SIZE,
+    };
+    BigEndian::write_u16(&mut bytes[offset..offset + 2], crc);
+
+    bytes
+}
+
+/// Legacy layout with a zeroed CRC
+fn encode_legacy(rfid_data: &RfidData) -> Vec<u8> {
+    let mut bytes = vec![0u8; 2];
+
+    bytes.extend_from_slice(&(rfid_data.entries.len() as u16).to_be_bytes());
+    bytes.append(&mut rfid_data.chip_data.clone().into());
+
+    for entry in &rfid_data.entries {
+        bytes.append(&mut entry.clone().into());
+    }
+
+    bytes
+}
+
+/// Version 1 layout with a zeroed CRC
+fn encode_v1(rfid_data: &RfidData) -> Vec<u8> {
+    let header = TagHeader::for_tag(rfid_data);
+
+    let mut bytes = Vec::new();
+    header.write(&mut bytes);
+    bytes.extend_from_slice(&[0, 0]);
+
+    bytes.extend_from_slice(&(rfid_data.entries.len() as u16).to_be_bytes());
+    bytes.append(&mut rfid_data.chip_data.clone().into());
+
+    if let Some(checkpoint) = &rfid_data.checkpoint {
+        bytes.extend_from_slice(&checkpoint.to_bytes());
+    }
+
+    for entry in &rfid_data.entries {
+        if header.per_entry_algorithm() {
+            bytes.push(entry.algorithm.id());
+        }
+        bytes.extend_from_slice(&entry.pub_key.to_be_bytes());
+        bytes.extend_from_slice(&entry.signature);
+    }
+
+    bytes
+}
+
+/// Parse a tag in any supported layout into an owned `RfidData`
+pub fn decode(bytes: &[u8]) -> Result<RfidData, RfidDataParseError> {
+    decode_ref(bytes).map(|rfid_data| rfid_data.to_rfid_data())
+}
+
+/// Parse a tag in any supported layout into a view over `bytes`.
+///
+/// The whole tag is checked: its size, header, entry count, CRC and that nothing follows the last
+/// entry. Nothing is allocated, so hostile bytes can't make the parser allocate.
+pub fn decode_ref(bytes: &[u8]) -> Result<RfidDataRef<'_>, RfidDataParseError> {
+    if bytes.len() > MAX_TAG_SIZE {
+        return Err(RfidDataParseError::TooLarge {
+            size: bytes.len(),
+            max: MAX_TAG_SIZE,
+        });
+    }
+
+    let (rfid_data, len) = decode_prefix(bytes)?;
+    if len != bytes.len() {
+        return Err(RfidDataParseError::TrailingBytes(bytes.len() - len));
+    }
+
+    Ok(rfid_data)
+}
+
+/// Parse a tag from the start of `bytes`, returning it with its length.
+///
+/// Whatever follows the last entry is ignored, which is how a tag is read out of memory larger
+/// than it. Otherwise the checks are the same as `decode_ref`.
+pub fn decode_prefix(bytes: &[u8]) -> Result<(RfidDataRef<'_>, usize), RfidDataParseError> {
+    let bytes = &bytes[..bytes.len().min(MAX_TAG_SIZE)];
+    let mut reader = TagReader::new(bytes);
+
+    let (header, prefix_size) = if bytes.starts_with(&TAG_MAGIC) {
+        reader.take(TAG_MAGIC.len());
+        let header = TagHeader::read(&mut reader)?;
+        let prefix_size = if header.has_checkpoint() {
+            V1_PREFIX_SIZE + Checkpoint::SIZE
+        } else {
+            V1_PREFIX_SIZE
+        };
+        (Some(header), prefix_size)
+    } else {
+        (None, LEGACY_PREFIX_SIZE)
+    };
+
+    if bytes.len() < prefix_size {
+        return Err(reader.truncated_header(prefix_size));
+    }
+
+    let crc = reader.u16().unwrap();
+    let entry_count = reader.u16().unwrap();
+
+    let chip_data_start = bytes.len() - reader.remaining();
+    reader.take(ChipData::SIZE).unwrap();
+
+    if header.is_some_and(|header| header.has_measurements()) {
+        let section = match reader.u16() {
+            Some(len) => reader.take(len as usize),
+            None => None,
+        };
+        if section.is_none() {
+            return Err(RfidDataParseError::MalformedMeasurements);
+        }
+    }
+
+    let chip_data = &bytes[chip_data_start..bytes.len() - reader.remaining()];
+    ChipData::from_bytes(chip_data)?;
+
+    let checkpoint = match header {
+        Some(header) if header.has_checkpoint() => {
+            if entry_count == 0 {
+                return Err(RfidDataParseError::MissingCheckpointEntry);
+            }
+
+            // The measurement section comes first, so the checkpoint can still be cut off
+            let size = bytes.len() - reader.remaining() + Checkpoint::SIZE;
+            Some(
+                reader
+                    .take(Checkpoint::SIZE)
+                    .ok_or_else(|| reader.truncated_header(size))?,
+            )
+        }
+        _ => None,
+    };
+
+    let entries_start = bytes.len() - reader.remaining();
+    for found in 0..entry_count {
+        if read_entry(&mut reader, header.as_ref())?.is_none() {
+            return Err(RfidDataParseError::EntryCountMismatch {
+                expected: entry_count,
+                actual: found,
+            });
+        }
+    }
+
+    let len = bytes.len() - reader.remaining();
+    let version = header.map(|header| header.version).unwrap_or_default();
+
+    let expected = crc_of(version, &bytes[..len]);
+    if crc != expected {
+        return Err(RfidDataParseError::InvalidCrc {
+            expected,
+            actual: crc,
+        });
+    }
+
+    let rfid_data = RfidDataRef {
+        header,
+        crc,
+        chip_data,
+        checkpoint,
+        entry_count,
+        entries: &bytes[entries_start..len],
+    };
+
+    Ok((rfid_data, len))
+}
+
+/// Parse a single entry in the legacy layout, `bytes` must hold exactly one entry
+pub fn decode_entry(bytes: &[u8]) -> Result<SupplyChainEntry, RfidDataParseError> {
+    let mut reader = TagReader::new(bytes);
+
+    let entry = read_entry(&mut reader, None)?.ok_or(RfidDataParseError::EntryCountMismatch {
+        expected: 1,
+        actual: 0,
+    })?;
+
+    match reader.remaining() {
+        0 => Ok(entry.to_entry()),
+        trailing => Err(RfidDataParseError::TrailingBytes(trailing)),
+    }
+}
+
+/// Read the next entry of a tag with `header`, or of a legacy tag if there is no header.
+///
+/// Returns `None` if the tag ends before the entry does.
+pub(crate) fn read_entry<'a>(
+    reader: &mut TagReader<'a>,
+    header: Option<&TagHeader>,
+) -> Result<Option<SupplyChainEntryRef<'a>>, RfidDataParseError> {
+    match header {
+        Some(header) => read_v1_entry(reader, header),
+        None => read_legacy_entry(reader),
+    }
+}
+
+/// Legacy entry, always signed with RSA
+fn read_legacy_entry<'a>(
+    reader: &mut TagReader<'a>,
+) -> Result<Option<SupplyChainEntryRef<'a>>, RfidDataParseError> {
+    let pub_key = match reader.take(4) {
+        Some(bytes) => LittleEndian::read_u32(bytes),
+        None => return Ok(None),
+    };
+
+    let algorithm = SignatureAlgorithm::RsaSha3;
+
+    Ok(reader
+        .take(algorithm.signature_size())
+        .map(|signature| SupplyChainEntryRef {
+            pub_key,
+            signature,
+            algorithm,
+        }))
+}
+
+fn read_v1_entry<'a>(
+    reader: &mut TagReader<'a>,
+    header: &TagHeader,
+) -> Result<Option<SupplyChainEntryRef<'a>>, RfidDataParseError> {
+    let algorithm = if header.per_entry_algorithm() {
+        match reader.u8() {
+            Some(id) => algorithm_from_id(id)?,
+            None => return Ok(None),
+        }
+    } else {
+        header.algorithm
+    };
+
+    let pub_key = match reader.take(4) {
+        Some(bytes) => BigEndian::read_u32(bytes),
+        None => return Ok(None),
+    };
+
+    Ok(reader
+        .take(algorithm.signature_size())
+        .map(|signature| SupplyChainEntryRef {
+            pub_key,
+            signature,
+            algorithm,
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::SignatureAlgorithm;
+    use crate::checkpoint::Checkpoint;
+    use crate::error::RfidDataParseError;
+    use crate::key::PublicKey;
+    use crate::rfid::{RfidBuilder, RfidData};
+    use crate::supply_chain::SupplyChainEntry;
+    use crate::tag_format::{TagVersion, FLAG_PER_ENTRY_ALGORITHM, MAX_TAG_SIZE, TAG_MAGIC};
+    use crate::test_util::{ed25519_keypairs, signed_tag};
+    use openssl::pkey::{PKey, Private};
+    use openssl::rsa::Rsa;
+    use std::collections::HashMap;
+    use std::convert::TryFrom;
+
+    fn round_trip(data: &RfidData, key_map: &HashMap<u32, PublicKey>) -> Vec<u8> {
+        let last_key = key_map[&(key_map.len() as u32 - 1)].clone();
+        let bytes: Vec<u8> = data.clone().into();
+
+        let parsed = RfidData::try_from(bytes.clone()).unwrap();
+        assert_eq!(parsed.version, data.version);
+        assert!(parsed.valid_crc());
+        assert!(parsed.validate_chain(key_map, last_key).is_ok());
+
+        let reencoded: Vec<u8> = parsed.into();
+        assert_eq!(reencoded, bytes);
+
+        bytes
+    }
+
+    fn rsa() -> PKey<Private> {
+        PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_versions() {
+        let (legacy, key_map) = signed_tag(TagVersion::Legacy, &[rsa(), rsa(), rsa()]);
+        let bytes = round_trip(&legacy, &key_map);
+        assert_eq!(bytes.len(), 2 + 2 + 32 + 2 * (4 + 256));
+
+        // Legacy entries are RSA only and keep the whole key ID
+        let entry = SupplyChainEntry {
+            pub_key: 0x0100_0002,
+            signature: vec![7; 256],
+            algorithm: SignatureAlgorithm::RsaSha3,
+        };
+        let entry_bytes: Vec<u8> = entry.clone().into();
+        assert_eq!(entry_bytes[..4], [2, 0, 0, 1]);
+        assert_eq!(SupplyChainEntry::try_from(entry_bytes).unwrap(), entry);
+
+        let uniform = ed25519_keypairs(3);
+
+        // Other algorithms don't fit the legacy layout, so the tag is written as version 1
+        let (legacy, _) = signed_tag(TagVersion::Legacy, &uniform);
+        let bytes: Vec<u8> = legacy.into();
+        assert_eq!(bytes.len(), 7 + 2 + 2 + 32 + 2 * (4 + 64));
+        assert_eq!(RfidData::try_from(bytes).unwrap().version, TagVersion::V1);
+
+        let (v1, key_map) = signed_tag(TagVersion::V1, &uniform);
+        let bytes = round_trip(&v1, &key_map);
+        assert_eq!(bytes.len(), 7 + 2 + 2 + 32 + 2 * (4 + 64));
+        assert_eq!(bytes[0..4], TAG_MAGIC);
+        assert_eq!(bytes[4], 1);
+        assert_eq!(bytes[5], SignatureAlgorithm::Ed25519.id());
+        assert_eq!(bytes[6], 0);
+
+        let mixed = [
+            rsa(),
+            PKey::generate_ed25519().unwrap(),
+            PKey::generate_ed25519().unwrap(),
+        ];
+
+        let (v1, key_map) = signed_tag(TagVersion::V1, &mixed);
+        let bytes = round_trip(&v1, &key_map);
+        assert_eq!(bytes.len(), 7 + 2 + 2 + 32 + (5 + 256) + (5 + 64));
+        assert_eq!(bytes[6], FLAG_PER_ENTRY_ALGORITHM);
+
+        let mut corrupted = bytes.clone();
+        corrupted[20] ^= 1;
+        assert!(matches!(
+            RfidData::try_from(corrupted),
+            Err(RfidDataParseError::InvalidCrc { .. })
+        ));
+
+        let mut future = bytes;
+        future[4] = 2;
+        assert!(matches!(
+            RfidData::try_from(future),
+            Err(RfidDataParseError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn test_malformed_tags() {
+        let keypairs = [rsa(), rsa(), rsa()];
+        let (data, _) = signed_tag(TagVersion::Legacy, &keypairs);
+        let bytes: Vec<u8> = data.into();
+
+        assert_eq!(
+            RfidData::try_from(bytes[..20].to_vec()).unwrap_err(),
+            RfidDataParseError::TruncatedHeader {
+                expected: 36,
+                actual: 20
+            }
+        );
+        assert_eq!(
+            RfidData::try_from(bytes[..bytes.len() - 1].to_vec()).unwrap_err(),
+            RfidDataParseError::EntryCountMismatch {
+                expected: 2,
+                actual: 1
+            }
+        );
+
+        let mut trailing = bytes.clone();
+        trailing.extend_from_slice(&[0, 0, 0]);
+        assert_eq!(
+            RfidData::try_from(trailing).unwrap_err(),
+            RfidDataParseError::TrailingBytes(3)
+        );
+
+        // An entry count far beyond what the bytes hold is reported, not allocated
+        let mut hostile = bytes[..36].to_vec();
+        hostile[2..4].copy_from_slice(&[0xff, 0xff]);
+        assert_eq!(
+            RfidData::try_from(hostile).unwrap_err(),
+            RfidDataParseError::EntryCountMismatch {
+                expected: 0xffff,
+                actual: 0
+            }
+        );
+
+        assert_eq!(
+            RfidData::try_from(vec![0u8; MAX_TAG_SIZE + 1]).unwrap_err(),
+            RfidDataParseError::TooLarge {
+                size: MAX_TAG_SIZE + 1,
+                max: MAX_TAG_SIZE
+            }
+        );
+
+        let mut nan = bytes.clone();
+        nan[4 + 20..4 + 24].copy_from_slice(&f32::NAN.to_be_bytes());
+        assert_eq!(
+            RfidData::try_from(nan).unwrap_err(),
+            RfidDataParseError::InvalidFloat("voltage")
+        );
+
+        let (data, _) = signed_tag(TagVersion::V1, &keypairs);
+        let mut flags: Vec<u8> = data.into();
+        flags[6] = 0x80;
+        assert_eq!(
+            RfidData::try_from(flags).unwrap_err(),
+            RfidDataParseError::UnsupportedFlags(0x80)
+        );
+
+        assert_eq!(
+            RfidData::try_from(TAG_MAGIC.to_vec()).unwrap_err(),
+            RfidDataParseError::TruncatedHeader {
+                expected: 43,
+                actual: 4
+            }
+        );
+
+        // A compacted tag always starts with the checkpoint entry
+        let compacted = RfidData {
+            checkpoint: Some(Checkpoint {
+                hops: 2,
+                record_hash: [0; 32],
+            }),
+            ..RfidBuilder::default()
+                .chip_data(42, 5.0, 5.0, 5.0, 5.0)
+                .build()
+        };
+        let compacted: Vec<u8> = compacted.into();
+        assert_eq!(
+            RfidData::try_from(compacted).unwrap_err(),
+            RfidDataParseError::MissingCheckpointEntry
+        );
+    }
+}
@@ -0,0 +1,745 @@
+//! Tag memory profiles, and write layouts that survive losing power mid-write.
+//!
+//! Tags are written one block at a time. A block write either completes or leaves the block as it
+//! was, but power can be lost between any two of them, for example when the tag is pulled out of
+//! the reader's field. Both layouts commit a write with a single block, so a torn write leaves the
+//! tag holding either the old chain or the new one:
+//!
+//! * `WriteLayout::TwoSlot` splits user memory into two slots, each a generation block followed by
+//!   a copy of the tag. The new tag is written into the slot holding the older copy, then that
+//!   slot's generation is bumped. Any change can be written, but only half of user memory holds
+//!   the tag.
+//! * `WriteLayout::Journaled` keeps a single copy of the tag after a small journal. The blocks after
+//!   the end of the current tag are written first. Then the head of the tag, which holds its entry
+//!   count and CRC, is staged in the journal, committed and copied into place. Only appending
+//!   entries is supported, but almost all of user memory holds the tag.
+//!
+//! Compacting a tag rewrites its entries, which the journal can't stage, so tags that may be
+//! compacted by the central server have to use `WriteLayout::TwoSlot`.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use crc::crc16;
+
+use crate::algorithm::SignatureAlgorithm;
+use crate::error::{RfidDataParseError, TagMemoryError};
+use crate::rfid::RfidData;
+use crate::supply_chain::SupplyChainEntry;
+use crate::tag_format;
+
+/// Bytes at the start of a tag that change when an entry is appended: the header, CRC and entry
+/// count of every tag version
+const JOURNAL_HEAD_SIZE: usize = 16;
+
+/// First byte of a committed journal
+const JOURNAL_COMMITTED: u8 = b'J';
+
+/// How a tag type's user memory is addressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryMap {
+    /// `count` consecutive blocks starting at block `first`
+    Contiguous { first: u16, count: u16 },
+    /// MIFARE Classic with `sectors` sectors, 4 blocks each for the first 32 and 16 blocks each
+    /// after that. The manufacturer block and each sector's trailer, which holds its keys, are
+    /// skipped.
+    MifareClassic { sectors: u16 },
+}
+
+/// User memory of a type of tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TagProfile {
+    pub name: &'static str,
+    /// Bytes written by one write command, a page on NTAG and a block on ICODE and MIFARE
+    pub block_size: usize,
+    pub memory: MemoryMap,
+}
+
+impl TagProfile {
+    pub const NTAG213: Self = Self::contiguous("NTAG213", 4, 4, 36);
+    pub const NTAG215: Self = Self::contiguous("NTAG215", 4, 4, 126);
+    pub const NTAG216: Self = Self::contiguous("NTAG216", 4, 4, 222);
+    pub const ICODE_SLIX: Self = Self::contiguous("ICODE SLIX", 4, 0, 28);
+    pub const ICODE_SLIX2: Self = Self::contiguous("ICODE SLIX2", 4, 0, 80);
+    pub const MIFARE_CLASSIC_1K: Self = Self::mifare_classic("MIFARE Classic 1K", 16);
+    pub const MIFARE_CLASSIC_4K: Self = Self::mifare_classic("MIFARE Classic 4K", 40);
+
+    pub const ALL: [Self; 7] = [
+        Self::NTAG213,
+        Self::NTAG215,
+        Self::NTAG216,
+        Self::ICODE_SLIX,
+        Self::ICODE_SLIX2,
+        Self::MIFARE_CLASSIC_1K,
+        Self::MIFARE_CLASSIC_4K,
+    ];
+
+    const fn contiguous(name: &'static str, block_size: usize, first: u16, count: u16) -> Self {
+        Self {
+            name,
+            block_size,
+            memory: MemoryMap::Contiguous { first, count },
+        }
+    }
+
+    const fn mifare_classic(name: &'static str, sectors: u16) -> Self {
+        Self {
+            name,
+            block_size: 16,
+            memory: MemoryMap::MifareClassic { sectors },
+        }
+    }
+
+    /// Look up a profile by name, ignoring case
+    pub fn by_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|profile| profile.name.eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    /// Addresses of the user memory blocks, in the order the tag is written to them
+    pub fn blocks(&self) -> Vec<u16> {
+        match self.memory {
+            MemoryMap::Contiguous { first, count } => (first..first + count).collect(),
+            MemoryMap::MifareClassic { sectors } => (0..sectors)
+                .flat_map(|sector| {
+                    let (first, count) = if sector < 32 {
+                        (sector * 4, 4)
+                    } else {
+                        (128 + (sector - 32) * 16, 16)
+                    };
+                    first..first + count - 1
+                })
+                .filter(|&block| block != 0)
+                .collect(),
+        }
+    }
+
+    /// Bytes of user memory
+    pub fn user_memory(&self) -> usize {
+        self.blocks().len() * self.block_size
+    }
+
+    /// Largest encoded tag that can be written with `layout`
+    pub fn capacity(&self, layout: WriteLayout) -> usize {
+        let blocks = self.blocks().len();
+        let data_blocks = match layout {
+            WriteLayout::TwoSlot => (blocks / 2).saturating_sub(1),
+            WriteLayout::Journaled => blocks.saturating_sub(1 + self.head_blocks()),
+        };
+
+        data_blocks * self.block_size
+    }
+
+    /// Number of entries signed with `algorithm` that can still be added to `rfid_data`
+    pub fn hops_remaining(
+        &self,
+        layout: WriteLayout,
+        rfid_data: &RfidData,
+        algorithm: SignatureAlgorithm,
+    ) -> usize {
+        let capacity = self.capacity(layout);
+        let mut next = rfid_data.clone();
+
+        let mut hops = 0;
+        loop {
+            next.entries.push(SupplyChainEntry {
+                pub_key: 0,
+                signature: vec![0; algorithm.signature_size()],
+                algorithm,
+            });
+
+            if tag_format::encode(&next).len() > capacity {
+                return hops;
+            }
+            hops += 1;
+        }
+    }
+
+    /// Blocks holding the part of a tag that is staged in the journal
+    fn head_blocks(&self) -> usize {
+        JOURNAL_HEAD_SIZE.div_ceil(self.block_size)
+    }
+}
+
+/// How a tag is laid out in user memory so a torn write never loses it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteLayout {
+    TwoSlot,
+    /// Append only, writing a compacted tag over the tag it was compacted from fails with
+    /// `TagMemoryError::NotAppendOnly`
+    Journaled,
+}
+
+/// Block access to a tag in a reader's field, implemented by reader drivers.
+///
+/// Drivers for MIFARE Classic authenticate each sector before accessing its blocks.
+pub trait TagMemory {
+    type Error;
+
+    /// Read the block at `address` into `buffer`, which is one block long
+    fn read_block(&mut self, address: u16, buffer: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Write one block, `data` is one block long
+    fn write_block(&mut self, address: u16, data: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Reads and writes tags in a tag's user memory
+pub struct TagStore<M> {
+    profile: TagProfile,
+    layout: WriteLayout,
+    memory: M,
+    blocks: Vec<u16>,
+}
+
+/// A decoded copy of the tag found in user memory
+struct StoredTag {
+    rfid_data: RfidData,
+    len: usize,
+}
+
+impl<M: TagMemory> TagStore<M> {
+    pub fn new(profile: TagProfile, layout: WriteLayout, memory: M) -> Self {
+        Self {
+            profile,
+            layout,
+            memory,
+            blocks: profile.blocks(),
+        }
+    }
+
+    pub fn into_inner(self) -> M {
+        self.memory
+    }
+
+    pub fn profile(&self) -> &TagProfile {
+        &self.profile
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.profile.capacity(self.layout)
+    }
+
+    /// Same as `TagProfile::hops_remaining` with this store's layout
+    pub fn hops_remaining(&self, rfid_data: &RfidData, algorithm: SignatureAlgorithm) -> usize {
+        self.profile
+            .hops_remaining(self.layout, rfid_data, algorithm)
+    }
+
+    /// Read the last tag written in full, `None` if nothing has ever been written
+    pub fn read(&mut self) -> Result<Option<RfidData>, TagMemoryError<M::Error>> {
+        match self.layout {
+            WriteLayout::TwoSlot => {
+                let slots = self.read_slots()?;
+                if let Some((_, tag)) = newest(&slots) {
+                    return Ok(Some(tag.rfid_data.clone()));
+                }
+
+                // Only report a slot that was committed as corrupt, never written slots are blank
+                match slots.into_iter().find_map(|slot| slot.tag.err()) {
+                    Some(e) => Err(TagMemoryError::Corrupt(e)),
+                    None => Ok(None),
+                }
+            }
+            WriteLayout::Journaled => Ok(self.read_journaled()?.1.map(|tag| tag.rfid_data)),
+        }
+    }
+
+    /// Write `rfid_data`, replacing the tag in user memory.
+    ///
+    /// If the write is interrupted, `read` returns either the previous tag or this one. With
+    /// `WriteLayout::Journaled` the new tag has to extend the previous one, so compacted tags can
+    /// only be written with `WriteLayout::TwoSlot`.
+    pub fn write(&mut self, rfid_data: &RfidData) -> Result<(), TagMemoryError<M::Error>> {
+        let bytes = tag_format::encode(rfid_data);
+        if bytes.len() > self.capacity() {
+            return Err(TagMemoryError::TooLarge {
+                size: bytes.len(),
+                capacity: self.capacity(),
+            });
+        }
+
+        match self.layout {
+            WriteLayout::TwoSlot => self.write_two_slot(&bytes),
+            WriteLayout::Journaled => self.write_journaled(&bytes),
+        }
+    }
+
+    fn read_blocks(
+        &mut self,
+        first: usize,
+        count: usize,
+    ) -> Result<Vec<u8>, TagMemoryError<M::Error>> {
+        let block_size = self.profile.block_size;
+        let mut bytes = vec![0u8; count * block_size];
+
+        for (ndx, buffer) in bytes.chunks_mut(block_size).enumerate() {
+            self.memory
+                .read_block(self.blocks[first + ndx], buffer)
+                .map_err(TagMemoryError::Memory)?;
+        }
+
+        Ok(bytes)
+    }
+
+    /// Write the blocks of `new` that differ from `old`, which hold the blocks starting at `first`
+    fn write_changed(
+        &mut self,
+        first: usize,
+        old: &[u8],
+        new: &[u8],
+    ) -> Result<(), TagMemoryError<M::Error>> {
+        let block_size = self.profile.block_size;
+
+        for (ndx, (old, new)) in old
+            .chunks(block_size)
+            .zip(new.chunks(block_size))
+            .enumerate()
+        {
+            if old != new {
+                self.memory
+                    .write_block(self.blocks[first + ndx], new)
+                    .map_err(TagMemoryError::Memory)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Blocks in each slot, including its generation block
+    fn slot_blocks(&self) -> usize {
+        self.blocks.len() / 2
+    }
+
+    /// Generation and raw bytes of both slots, with the tag each holds if it is readable
+    fn read_slots(&mut self) -> Result<Vec<Slot>, TagMemoryError<M::Error>> {
+        let slot_blocks = self.slot_blocks();
+        let mut slots = Vec::with_capacity(2);
+
+        for first in [0, slot_blocks] {
+            let bytes = self.read_blocks(first, slot_blocks)?;
+            let (generation_block, data) = bytes.split_at(self.profile.block_size);
+            let generation = u32::from_be_bytes([
+                generation_block[0],
+                generation_block[1],
+                generation_block[2],
+                generation_block[3],
+            ]);
+
+            let tag = match generation {
+                0 => Ok(None),
+                _ => tag_format::decode_prefix(data).map(|(tag, len)| {
+                    Some(StoredTag {
+                        rfid_data: tag.to_rfid_data(),
+                        len,
+                    })
+                }),
+            };
+
+            slots.push(Slot {
+                generation,
+                bytes,
+                tag,
+            });
+        }
+
+        Ok(slots)
+    }
+
+    fn write_two_slot(&mut self, bytes: &[u8]) -> Result<(), TagMemoryError<M::Error>> {
+        let block_size = self.profile.block_size;
+        let slots = self.read_slots()?;
+
+        let (target, generation) = match newest(&slots) {
+            Some((ndx, _)) => (1 - ndx, next_generation(slots[ndx].generation)),
+            None => (0, 1),
+        };
+        let first = target * self.slot_blocks();
+        let old = &slots[target].bytes;
+
+        let mut new = old.clone();
+        new[..block_size].fill(0);
+        new[..4].copy_from_slice(&generation.to_be_bytes());
+        new[block_size..block_size + bytes.len()].copy_from_slice(bytes);
+
+        // Fill the slot, then commit it by bumping its generation
+        self.write_changed(first + 1, &old[block_size..], &new[block_size..])?;
+        self.write_changed(first, &old[..block_size], &new[..block_size])
+    }
+
+    /// Raw journal and main area, with the journal applied if it was committed, and the tag the main
+    /// area holds
+    fn read_journaled(
+        &mut self,
+    ) -> Result<(JournaledImage, Option<StoredTag>), TagMemoryError<M::Error>> {
+        let block_size = self.profile.block_size;
+        let head_size = self.profile.head_blocks() * block_size;
+
+        let journal = self.read_blocks(0, 1 + self.profile.head_blocks())?;
+        let main_blocks = self.blocks.len() - journal.len() / block_size;
+        let stored = self.read_blocks(journal.len() / block_size, main_blocks)?;
+
+        let (commit, shadow) = journal.split_at(block_size);
+        let committed = commit[0] == JOURNAL_COMMITTED
+            && commit[1] as usize == self.profile.head_blocks()
+            && commit[2..4] == crc16::checksum_x25(shadow).to_be_bytes();
+
+        let mut main = stored.clone();
+        if committed {
+            main[..head_size].copy_from_slice(shadow);
+        }
+
+        let tag = match tag_format::decode_prefix(&main) {
+            Ok((tag, len)) => Some(StoredTag {
+                rfid_data: tag.to_rfid_data(),
+                len,
+            }),
+            Err(_) if main[..head_size].iter().all(|&byte| byte == 0) => None,
+            Err(e) => return Err(TagMemoryError::Corrupt(e)),
+        };
+
+        let image = JournaledImage {
+            journal,
+            committed,
+            stored,
+            main,
+        };
+
+        Ok((image, tag))
+    }
+
+    fn write_journaled(&mut self, bytes: &[u8]) -> Result<(), TagMemoryError<M::Error>> {
+        let block_size = self.profile.block_size;
+        let head_blocks = self.profile.head_blocks();
+        let head_size = head_blocks * block_size;
+        let journal_blocks = 1 + head_blocks;
+
+        let (mut image, old_len) = match self.read_journaled() {
+            Ok((image, tag)) => (image, tag.map(|tag| tag.len).unwrap_or_default()),
+            // An unreadable tag is overwritten as if it were blank
+            Err(TagMemoryError::Corrupt(_)) => {
+                let journal = self.read_blocks(0, journal_blocks)?;
+                let stored =
+                    self.read_blocks(journal_blocks, self.blocks.len() - journal_blocks)?;
+                let image = JournaledImage {
+                    journal,
+                    committed: false,
+                    main: stored.clone(),
+                    stored,
+                };
+                (image, 0)
+            }
+            Err(e) => return Err(e),
+        };
+
+        let mut new = image.main.clone();
+        new[..bytes.len()].copy_from_slice(bytes);
+
+        // Only the head may change within the current tag, everything else is past its end
+        if new[head_size.min(old_len)..old_len] != image.main[head_size.min(old_len)..old_len] {
+            return Err(TagMemoryError::NotAppendOnly);
+        }
+
+        // Finish a write that was committed but not copied into place before starting this one
+        if image.committed {
+            self.write_changed(
+                journal_blocks,
+                &image.stored[..head_size],
+                &image.main[..head_size],
+            )?;
+            self.clear_journal(&mut image)?;
+            image.stored[..head_size].copy_from_slice(&image.main[..head_size]);
+        }
+
+        self.write_changed(
+            journal_blocks + head_blocks,
+            &image.stored[head_size..],
+            &new[head_size..],
+        )?;
+
+        if new[..head_size] == image.stored[..head_size] {
+            return Ok(());
+        }
+
+        let mut journal = vec![0u8; block_size];
+        journal[0] = JOURNAL_COMMITTED;
+        journal[1] = head_blocks as u8;
+        journal[2..4].copy_from_slice(&crc16::checksum_x25(&new[..head_size]).to_be_bytes());
+        journal.extend_from_slice(&new[..head_size]);
+
+        // Stage the head, commit it, then copy it into place
+        self.write_changed(1, &image.journal[block_size..], &journal[block_size..])?;
+        self.write_changed(0, &image.journal[..block_size], &journal[..block_size])?;
+        image.journal = journal;
+
+        self.write_changed(
+            journal_blocks,
+            &image.stored[..head_size],
+            &new[..head_size],
+        )?;
+        self.clear_journal(&mut image)
+    }
+
+    fn clear_journal(
+        &mut self,
+        image: &mut JournaledImage,
+    ) -> Result<(), TagMemoryError<M::Error>> {
+        let block_size = self.profile.block_size;
+        let cleared = vec![0u8; block_size];
+
+        self.write_changed(0, &image.journal[..block_size], &cleared)?;
+        image.journal[..block_size].copy_from_slice(&cleared);
+        image.committed = false;
+
+        Ok(())
+    }
+}
+
+/// One of the two slots of `WriteLayout::TwoSlot`
+struct Slot {
+    /// Zero if the slot was never committed
+    generation: u32,
+    bytes: Vec<u8>,
+    /// `None` if the slot was never committed
+    tag: Result<Option<StoredTag>, RfidDataParseError>,
+}
+
+/// User memory of `WriteLayout::Journaled`
+struct JournaledImage {
+    /// The commit block followed by the staged head
+    journal: Vec<u8>,
+    committed: bool,
+    /// The main area as read from the tag
+    stored: Vec<u8>,
+    /// The main area with a committed journal applied
+    main: Vec<u8>,
+}
+
+/// Index and tag of the most recently committed readable slot
+fn newest(slots: &[Slot]) -> Option<(usize, &StoredTag)> {
+    slots
+        .iter()
+        .enumerate()
+        .filter_map(|(ndx, slot)| match &slot.tag {
+            Ok(Some(tag)) => Some((ndx, slot.generation, tag)),
+            _ => None,
+        })
+        .max_by(|(_, a, _), (_, b, _)| {
+            // Generations wrap, the newer one is less than half the range ahead
+            (a.wrapping_sub(*b) as i32).cmp(&0)
+        })
+        .map(|(ndx, _, tag)| (ndx, tag))
+}
+
+fn next_generation(generation: u32) -> u32 {
+    match generation.wrapping_add(1) {
+        0 => 1,
+        next => next,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::SignatureAlgorithm;
+    use crate::error::TagMemoryError;
+    use crate::rfid::{RfidBuilder, RfidData};
+    use crate::signer::file_key::FileKeySigner;
+    use crate::supply_chain::SupplyChainEntry;
+    use crate::tag_format::TagVersion;
+    use crate::tag_memory::{TagMemory, TagProfile, TagStore, WriteLayout};
+    use crate::test_util::{ed25519_keypairs, key_map};
+
+    /// Tag memory that loses power after a number of block writes
+    struct SimulatedTag {
+        block_size: usize,
+        memory: Vec<u8>,
+        writes_left: Option<usize>,
+    }
+
+    impl SimulatedTag {
+        fn new(profile: &TagProfile) -> Self {
+            let last = *profile.blocks().last().unwrap() as usize;
+            Self {
+                block_size: profile.block_size,
+                memory: vec![0; (last + 1) * profile.block_size],
+                writes_left: None,
+            }
+        }
+    }
+
+    impl TagMemory for SimulatedTag {
+        type Error = &'static str;
+
+        fn read_block(&mut self, address: u16, buffer: &mut [u8]) -> Result<(), Self::Error> {
+            let start = address as usize * self.block_size;
+            buffer.copy_from_slice(&self.memory[start..start + self.block_size]);
+            Ok(())
+        }
+
+        fn write_block(&mut self, address: u16, data: &[u8]) -> Result<(), Self::Error> {
+            match &mut self.writes_left {
+                Some(0) => return Err("Power lost"),
+                Some(writes_left) => *writes_left -= 1,
+                None => {}
+            }
+
+            let start = address as usize * self.block_size;
+            self.memory[start..start + self.block_size].copy_from_slice(data);
+            Ok(())
+        }
+    }
+
+    fn bytes(rfid_data: &RfidData) -> Vec<u8> {
+        rfid_data.clone().into()
+    }
+
+    #[test]
+    fn test_profiles() {
+        assert_eq!(TagProfile::NTAG213.user_memory(), 144);
+        assert_eq!(TagProfile::NTAG216.user_memory(), 888);
+        assert_eq!(TagProfile::ICODE_SLIX2.user_memory(), 320);
+        assert_eq!(TagProfile::MIFARE_CLASSIC_1K.user_memory(), 752);
+        assert_eq!(TagProfile::MIFARE_CLASSIC_4K.user_memory(), 3440);
+        assert_eq!(TagProfile::MIFARE_CLASSIC_1K.blocks()[..4], [1, 2, 4, 5]);
+        assert_eq!(
+            TagProfile::by_name("mifare classic 4k"),
+            Some(TagProfile::MIFARE_CLASSIC_4K)
+        );
+
+        let data = RfidBuilder::default()
+            .version(TagVersion::V1)
+            .chip_data(42, 5.0, 5.0, 5.0, 5.0)
+            .build();
+        let ntag = TagProfile::NTAG213;
+        assert_eq!(
+            ntag.hops_remaining(WriteLayout::Journaled, &data, SignatureAlgorithm::Ed25519),
+            1
+        );
+        assert_eq!(
+            ntag.hops_remaining(WriteLayout::TwoSlot, &data, SignatureAlgorithm::Ed25519),
+            0
+        );
+        assert_eq!(
+            TagProfile::MIFARE_CLASSIC_1K.hops_remaining(
+                WriteLayout::TwoSlot,
+                &data,
+                SignatureAlgorithm::RsaSha3
+            ),
+            1
+        );
+    }
+
+    #[test]
+    fn test_torn_writes() {
+        let keypairs = ed25519_keypairs(6);
+        let key_map = key_map(&keypairs);
+
+        let mut tags = vec![RfidBuilder::default()
+            .version(TagVersion::V1)
+            .chip_data(42, 5.0, 5.0, 5.0, 5.0)
+            .build()];
+        for id in 0..5 {
+            let signer = FileKeySigner::from_pkey(keypairs[id as usize].clone()).unwrap();
+            let next = RfidBuilder::from(tags.last().unwrap().clone())
+                .add_entry(&signer, id, id + 1, &key_map)
+                .unwrap()
+                .build();
+            tags.push(next);
+        }
+
+        for layout in [WriteLayout::TwoSlot, WriteLayout::Journaled] {
+            for profile in [TagProfile::NTAG216, TagProfile::MIFARE_CLASSIC_1K] {
+                let mut store = TagStore::new(profile, layout, SimulatedTag::new(&profile));
+                assert!(store.read().unwrap().is_none());
+
+                let fits: Vec<_> = tags
+                    .iter()
+                    .take_while(|tag| bytes(tag).len() <= store.capacity())
+                    .collect();
+                assert!(fits.len() > 2, "{} {:?}", profile.name, layout);
+
+                store.write(fits[0]).unwrap();
+                for pair in fits.windows(2) {
+                    let (old, new) = (pair[0], pair[1]);
+
+                    // Lose power after every possible number of block writes
+                    for writes in 0.. {
+                        let before = store.into_inner().memory;
+                        let mut tag = SimulatedTag::new(&profile);
+                        tag.memory = before.clone();
+                        tag.writes_left = Some(writes);
+
+                        store = TagStore::new(profile, layout, tag);
+                        let result = store.write(new);
+
+                        let read = store.read().unwrap().unwrap();
+                        assert!(
+                            bytes(&read) == bytes(old) || bytes(&read) == bytes(new),
+                            "{} {:?} after {} writes",
+                            profile.name,
+                            layout,
+                            writes
+                        );
+
+                        // Retrying after a torn write always succeeds
+                        let mut tag = store.into_inner();
+                        tag.writes_left = None;
+                        let mut retry = TagStore::new(profile, layout, tag);
+                        retry.write(new).unwrap();
+                        assert_eq!(bytes(&retry.read().unwrap().unwrap()), bytes(new));
+
+                        let mut tag = retry.into_inner();
+                        tag.memory = before;
+                        store = TagStore::new(profile, layout, tag);
+
+                        if result.is_ok() {
+                            break;
+                        }
+                    }
+
+                    store.write(new).unwrap();
+                }
+
+                let last = store.read().unwrap().unwrap();
+                let recipient = key_map[&(last.entries.len() as u32)].clone();
+                assert!(last.validate_chain(&key_map, recipient).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_errors() {
+        let profile = TagProfile::NTAG213;
+        let data = RfidBuilder::default()
+            .version(TagVersion::V1)
+            .chip_data(42, 5.0, 5.0, 5.0, 5.0)
+            .build();
+
+        let mut store = TagStore::new(profile, WriteLayout::Journaled, SimulatedTag::new(&profile));
+        store.write(&data).unwrap();
+
+        let mut changed = data.clone();
+        changed.chip_data.temp = 6.0;
+        assert_eq!(store.write(&changed), Err(TagMemoryError::NotAppendOnly));
+
+        let mut too_large = data;
+        let entry = SupplyChainEntry {
+            pub_key: 0,
+            signature: vec![0; 64],
+            algorithm: SignatureAlgorithm::Ed25519,
+        };
+        too_large.entries = vec![entry; 2];
+        assert!(matches!(
+            store.write(&too_large),
+            Err(TagMemoryError::TooLarge { capacity: 124, .. })
+        ));
+
+        // Both layouts switch to the new tag with a single block write
+        let mut store = TagStore::new(profile, WriteLayout::TwoSlot, SimulatedTag::new(&profile));
+        store.write(&changed).unwrap();
+        let mut tag = store.into_inner();
+        tag.memory[4 * 4 + 8] ^= 1;
+        let mut store = TagStore::new(profile, WriteLayout::TwoSlot, tag);
+        assert!(matches!(store.read(), Err(TagMemoryError::Corrupt(_))));
+    }
+}
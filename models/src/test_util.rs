@@ -0,0 +1,47 @@
+//! Keys and tags for tests. Other crates' tests get them with the `test-util` feature.
+
+use crate::key::PublicKey;
+use crate::rfid::{RfidBuilder, RfidData};
+use crate::signer::file_key::FileKeySigner;
+use crate::tag_format::TagVersion;
+use openssl::pkey::{PKey, Private};
+use std::collections::HashMap;
+
+/// `n` fresh Ed25519 keypairs
+pub fn ed25519_keypairs(n: usize) -> Vec<PKey<Private>> {
+    (0..n).map(|_| PKey::generate_ed25519().unwrap()).collect()
+}
+
+/// Public half of `keypair`, registered as distributor `id`
+pub fn public_key(id: u32, keypair: &PKey<Private>) -> PublicKey {
+    let pem = keypair.public_key_to_pem().unwrap();
+    PublicKey::new(id, pem, id.to_string()).unwrap()
+}
+
+/// Public keys of `keypairs`, each registered under its index
+pub fn key_map(keypairs: &[PKey<Private>]) -> HashMap<u32, PublicKey> {
+    keypairs
+        .iter()
+        .enumerate()
+        .map(|(id, keypair)| (id as u32, public_key(id as u32, keypair)))
+        .collect()
+}
+
+/// Tag for chip 42 with an entry for every keypair but the last, each distributor signing it over
+/// to the next. The last distributor holds the tag.
+pub fn signed_tag(
+    version: TagVersion,
+    keypairs: &[PKey<Private>],
+) -> (RfidData, HashMap<u32, PublicKey>) {
+    let key_map = key_map(keypairs);
+
+    let mut builder = RfidBuilder::default()
+        .version(version)
+        .chip_data(42, 5.0, 5.0, 5.0, 5.0);
+    for id in 0..keypairs.len() as u32 - 1 {
+        let signer = FileKeySigner::from_pkey(keypairs[id as usize].clone()).unwrap();
+        builder = builder.add_entry(&signer, id, id + 1, &key_map).unwrap();
+    }
+
+    (builder.build(), key_map)
+}
@@ -1,25 +1,38 @@
-use openssl::pkey::Private;
-use openssl::rsa::{Padding, Rsa};
-use std::io::Read;
-use std::path::PathBuf;
+use crate::algorithm::SignatureAlgorithm;
+use crate::error::SignerError;
+use crate::signer::PassphraseSource;
+use openssl::pkey::{PKey, Private};
+use std::path::Path;
+use zeroize::Zeroizing;
 
-pub fn hash_from_signature(pub_key: &[u8], signature: &[u8]) -> Vec<u8> {
-    let rsa = Rsa::public_key_from_pem(pub_key).unwrap();
-    let mut output = Vec::from([0u8; 512]);
-    rsa.public_decrypt(&signature, &mut output, Padding::PKCS1)
-        .unwrap();
+/// Load a private key from a PEM file, asking for a passphrase if the key is encrypted
+pub fn open_private_key(
+    path: &Path,
+    passphrase: &dyn PassphraseSource,
+) -> Result<PKey<Private>, SignerError> {
+    let key_error = |reason: String| SignerError::KeyFileError {
+        path: path.to_path_buf(),
+        reason,
+    };
 
-    output.drain(0..19);
-    output.truncate(32);
+    let pem = Zeroizing::new(std::fs::read(path).map_err(|e| key_error(e.to_string()))?);
 
-    output
-}
+    // Encrypted PKCS#8 and legacy encrypted PEM both mark themselves in the PEM header
+    let encrypted = pem.windows(9).any(|window| window == b"ENCRYPTED");
 
-pub fn open_private_key(path: PathBuf) -> Rsa<Private> {
-    let mut file = std::fs::File::open(path).unwrap();
-    let mut private_key_str = String::new();
+    let key = if encrypted {
+        let passphrase = passphrase.passphrase(path)?;
+        PKey::private_key_from_pem_passphrase(&pem, passphrase.as_bytes())
+            .map_err(|_| SignerError::BadPassphrase(path.to_path_buf()))?
+    } else {
+        PKey::private_key_from_pem(&pem).map_err(|e| key_error(e.to_string()))?
+    };
 
-    file.read_to_string(&mut private_key_str).unwrap();
+    if SignatureAlgorithm::of_key(&key).is_none() {
+        return Err(key_error(
+            "unsupported key type, expected RSA-2048, Ed25519 or P-256".to_string(),
+        ));
+    }
 
-    Rsa::private_key_from_pem(private_key_str.as_bytes()).unwrap()
+    Ok(key)
 }
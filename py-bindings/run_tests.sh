@@ -0,0 +1,23 @@
+#!/bin/sh
+# Build the extension into a virtualenv and run the Python tests.
+#
+# The virtualenv is created in .venv unless VENV is set. maturin is installed into it if it isn't
+# on the PATH. Arguments are passed on to `maturin develop`, e.g. `./run_tests.sh --offline`.
+set -e
+
+cd "$(dirname "$0")"
+VENV="${VENV:-.venv}"
+
+if [ ! -x "$VENV/bin/python" ]; then
+    python3 -m venv "$VENV"
+fi
+
+if command -v maturin > /dev/null; then
+    MATURIN=maturin
+else
+    "$VENV/bin/pip" install -q maturin
+    MATURIN="$VENV/bin/maturin"
+fi
+
+VIRTUAL_ENV="$(cd "$VENV" && pwd)" "$MATURIN" develop --release "$@"
+"$VENV/bin/python" -m unittest discover tests
@@ -0,0 +1,567 @@
+//! Python bindings for analysing tags and central records.
+//!
+//! Build a wheel with `maturin build --release` in this directory, or install into the current
+//! environment with `maturin develop`. Keys are passed as a `dict` of key ID to PEM public key,
+//! `keys_from_json` builds one from the central server's `/api/keys` response.
+
+use models::central_record::{CentralEntry, CentralRecord};
+use models::chip_data::ChipData;
+use models::key::PublicKey;
+use models::measurement::{Measurement, MeasurementType};
+use models::requests::key_request::KeyResponse;
+use models::rfid::{RfidBuilder, RfidData};
+use models::signer::file_key::FileKeySigner;
+use models::signer::Signer;
+use models::supply_chain::SupplyChainEntry;
+use models::tag_format::TagVersion;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+create_exception!(
+    rfid_supply_chain,
+    TagParseError,
+    PyValueError,
+    "Bytes that aren't a well formed tag"
+);
+create_exception!(
+    rfid_supply_chain,
+    InspectionError,
+    PyException,
+    "A tag that fails receiving inspection"
+);
+create_exception!(
+    rfid_supply_chain,
+    SignerError,
+    PyException,
+    "A key that can't be loaded or used to sign"
+);
+
+/// Public keys by ID, as passed in from Python
+type Keys = HashMap<u32, Vec<u8>>;
+
+/// Raises `ValueError` for a key that isn't a PEM key of a supported algorithm
+fn public_keys(keys: Keys) -> PyResult<HashMap<u32, PublicKey>> {
+    keys.into_iter()
+        .map(|(id, pem)| Ok((id, public_key(id, pem, id.to_string())?)))
+        .collect()
+}
+
+fn public_key(id: u32, pem: Vec<u8>, name: String) -> PyResult<PublicKey> {
+    PublicKey::new(id, pem, name).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn get_key(keys: &HashMap<u32, PublicKey>, id: u32) -> PyResult<&PublicKey> {
+    keys.get(&id)
+        .ok_or_else(|| PyKeyError::new_err(format!("No public key for distributor {}", id)))
+}
+
+fn tag_version(version: u8) -> PyResult<TagVersion> {
+    match version {
+        0 => Ok(TagVersion::Legacy),
+        1 => Ok(TagVersion::V1),
+        _ => Err(PyValueError::new_err(format!(
+            "Unsupported tag version {}",
+            version
+        ))),
+    }
+}
+
+/// Raises `ValueError` for a chip data measurement that is NaN or infinite, which tags can't hold
+fn finite(name: &str, value: f32) -> PyResult<f32> {
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(PyValueError::new_err(format!(
+            "{} must be a finite number",
+            name
+        )))
+    }
+}
+
+/// Parse the central server's `/api/keys` JSON into a `dict` of key ID to PEM public key
+#[pyfunction]
+fn keys_from_json(json: &str) -> PyResult<HashMap<u32, Vec<u8>>> {
+    let response: KeyResponse =
+        serde_json::from_str(json).map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+    Ok(response
+        .keys
+        .into_iter()
+        .map(|(id, key)| (id, key.key))
+        .collect())
+}
+
+/// Parametric measurements of a chip, signed by the first entry on its tag
+#[pyclass(name = "ChipData", module = "rfid_supply_chain")]
+#[derive(Clone)]
+struct PyChipData {
+    inner: ChipData,
+}
+
+#[pymethods]
+impl PyChipData {
+    #[new]
+    fn new(chip_id: u128, freq: f32, voltage: f32, temp: f32, time: f32) -> PyResult<Self> {
+        Ok(Self {
+            inner: ChipData {
+                chip_id,
+                freq: finite("freq", freq)?,
+                voltage: finite("voltage", voltage)?,
+                temp: finite("temp", temp)?,
+                time: finite("time", time)?,
+                measurements: Vec::new(),
+            },
+        })
+    }
+
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        ChipData::from_bytes(bytes)
+            .map(|inner| Self { inner })
+            .map_err(|e| TagParseError::new_err(e.to_string()))
+    }
+
+    /// The bytes stored on a tag, 32 unless there are measurements
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let bytes: Vec<u8> = self.inner.clone().into();
+        PyBytes::new(py, &bytes)
+    }
+
+    #[getter]
+    fn chip_id(&self) -> u128 {
+        self.inner.chip_id
+    }
+
+    #[setter]
+    fn set_chip_id(&mut self, chip_id: u128) {
+        self.inner.chip_id = chip_id;
+    }
+
+    #[getter]
+    fn freq(&self) -> f32 {
+        self.inner.freq
+    }
+
+    #[setter]
+    fn set_freq(&mut self, freq: f32) -> PyResult<()> {
+        self.inner.freq = finite("freq", freq)?;
+        Ok(())
+    }
+
+    #[getter]
+    fn voltage(&self) -> f32 {
+        self.inner.voltage
+    }
+
+    #[setter]
+    fn set_voltage(&mut self, voltage: f32) -> PyResult<()> {
+        self.inner.voltage = finite("voltage", voltage)?;
+        Ok(())
+    }
+
+    #[getter]
+    fn temp(&self) -> f32 {
+        self.inner.temp
+    }
+
+    #[setter]
+    fn set_temp(&mut self, temp: f32) -> PyResult<()> {
+        self.inner.temp = finite("temp", temp)?;
+        Ok(())
+    }
+
+    #[getter]
+    fn time(&self) -> f32 {
+        self.inner.time
+    }
+
+    #[setter]
+    fn set_time(&mut self, time: f32) -> PyResult<()> {
+        self.inner.time = finite("time", time)?;
+        Ok(())
+    }
+
+    /// Registered measurements as `(name, value)` pairs, in the order they are stored
+    #[getter]
+    fn measurements(&self) -> Vec<(&'static str, f32)> {
+        self.inner
+            .measurements
+            .iter()
+            .filter_map(|measurement| Some((measurement.kind()?.name(), measurement.value()?)))
+            .collect()
+    }
+
+    /// Append a measurement, `name` is one of the registered types such as `"ring_oscillator"`
+    fn add_measurement(&mut self, name: &str, value: f32) -> PyResult<()> {
+        let kind = MeasurementType::from_name(name)
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown measurement type {}", name)))?;
+        self.inner
+            .measurements
+            .push(Measurement::new(kind, finite(name, value)?));
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ChipData(chip_id={}, freq={}, voltage={}, temp={}, time={})",
+            self.inner.chip_id,
+            self.inner.freq,
+            self.inner.voltage,
+            self.inner.temp,
+            self.inner.time
+        )
+    }
+}
+
+/// One signed hop on a tag
+#[pyclass(name = "SupplyChainEntry", module = "rfid_supply_chain", frozen)]
+struct PySupplyChainEntry {
+    inner: SupplyChainEntry,
+}
+
+#[pymethods]
+impl PySupplyChainEntry {
+    /// ID of the distributor that signed the entry
+    #[getter]
+    fn key_id(&self) -> u32 {
+        self.inner.pub_key
+    }
+
+    #[getter]
+    fn signature<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.inner.signature)
+    }
+
+    /// `RsaSha3`, `Ed25519` or `EcdsaP256`
+    #[getter]
+    fn algorithm(&self) -> String {
+        format!("{:?}", self.inner.algorithm)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "SupplyChainEntry(key_id={}, algorithm={:?})",
+            self.inner.pub_key, self.inner.algorithm
+        )
+    }
+}
+
+/// A distributor's private key, for building tags and records to test against
+#[pyclass(name = "Signer", module = "rfid_supply_chain")]
+struct PySigner {
+    inner: FileKeySigner,
+}
+
+#[pymethods]
+impl PySigner {
+    /// Load an RSA-2048, Ed25519 or P-256 private key
+    #[staticmethod]
+    fn from_pem(pem: &[u8]) -> PyResult<Self> {
+        FileKeySigner::from_pem(pem)
+            .map(|inner| Self { inner })
+            .map_err(|e| SignerError::new_err(e.to_string()))
+    }
+
+    #[getter]
+    fn algorithm(&self) -> String {
+        format!("{:?}", self.inner.algorithm())
+    }
+
+    fn sign<'py>(&self, py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+        let signature = self
+            .inner
+            .sign(&[data])
+            .map_err(|e| SignerError::new_err(e.to_string()))?;
+
+        Ok(PyBytes::new(py, &signature))
+    }
+}
+
+/// Contents of a tag
+#[pyclass(name = "RfidData", module = "rfid_supply_chain")]
+#[derive(Clone)]
+struct PyRfidData {
+    inner: RfidData,
+}
+
+#[pymethods]
+impl PyRfidData {
+    /// A tag with no entries yet, `version` 0 is the legacy format
+    #[new]
+    #[pyo3(signature = (chip_data, version = 1))]
+    fn new(chip_data: PyChipData, version: u8) -> PyResult<Self> {
+        let inner = RfidBuilder::from(RfidData {
+            chip_data: chip_data.inner,
+            ..Default::default()
+        })
+        .version(tag_version(version)?)
+        .build();
+
+        Ok(Self { inner })
+    }
+
+    /// Parse a tag in any supported format, checking its size, layout, CRC and chip data
+    #[staticmethod]
+    fn from_bytes(bytes: Vec<u8>) -> PyResult<Self> {
+        RfidData::try_from(bytes)
+            .map(|inner| Self { inner })
+            .map_err(|e| TagParseError::new_err(e.to_string()))
+    }
+
+    /// Encode the tag as it is written to the chip
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let bytes: Vec<u8> = self.inner.clone().into();
+        PyBytes::new(py, &bytes)
+    }
+
+    /// Parse the JSON form used by the servers' requests and database
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        serde_json::from_str(json)
+            .map(|inner| Self { inner })
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string(&self.inner).unwrap()
+    }
+
+    #[getter]
+    fn version(&self) -> u8 {
+        self.inner.version.id()
+    }
+
+    #[getter]
+    fn crc(&self) -> u16 {
+        self.inner.crc
+    }
+
+    #[getter]
+    fn chip_data(&self) -> PyChipData {
+        PyChipData {
+            inner: self.inner.chip_data.clone(),
+        }
+    }
+
+    /// Entries in the order they were signed
+    #[getter]
+    fn entries(&self) -> Vec<PySupplyChainEntry> {
+        self.inner
+            .entries
+            .iter()
+            .map(|entry| PySupplyChainEntry {
+                inner: entry.clone(),
+            })
+            .collect()
+    }
+
+    fn calc_crc(&self) -> u16 {
+        self.inner.calc_crc()
+    }
+
+    fn valid_crc(&self) -> bool {
+        self.inner.valid_crc()
+    }
+
+    /// Index of the first entry that fails to validate, or `None` if the chain is valid with the
+    /// last entry signed over to `recipient_id`
+    fn validate_chain(&self, keys: Keys, recipient_id: u32) -> PyResult<Option<usize>> {
+        let keys = public_keys(keys)?;
+        let recipient = get_key(&keys, recipient_id)?;
+
+        Ok(self.inner.validate_chain(&keys, recipient.clone()).err())
+    }
+
+    /// Receiving inspection by `recipient_id`, raises `InspectionError` if the tag is rejected
+    fn inspect(&self, keys: Keys, recipient_id: u32) -> PyResult<()> {
+        let keys = public_keys(keys)?;
+        let recipient = get_key(&keys, recipient_id)?;
+
+        self.inner
+            .inspect(&keys, recipient)
+            .map_err(|e| InspectionError::new_err(e.to_string()))
+    }
+
+    /// Sign the tag over from `dist_id` to `next_dist_id`, both and every earlier signer must be in
+    /// `keys`
+    fn add_entry(
+        &mut self,
+        signer: &PySigner,
+        dist_id: u32,
+        next_dist_id: u32,
+        keys: Keys,
+    ) -> PyResult<()> {
+        let keys = public_keys(keys)?;
+
+        self.inner = RfidBuilder::from(self.inner.clone())
+            .add_entry(&signer.inner, dist_id, next_dist_id, &keys)
+            .map_err(|e| match e {
+                models::error::SignerError::UnknownKey(_) => PyKeyError::new_err(e.to_string()),
+                _ => SignerError::new_err(e.to_string()),
+            })?
+            .build();
+
+        Ok(())
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.entries.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "RfidData(chip_id={}, version={}, entries={})",
+            self.inner.chip_data.chip_id,
+            self.inner.version.id(),
+            self.inner.entries.len()
+        )
+    }
+}
+
+/// One hop of a chip's history on the central server
+#[pyclass(name = "CentralEntry", module = "rfid_supply_chain", frozen)]
+struct PyCentralEntry {
+    inner: CentralEntry,
+}
+
+#[pymethods]
+impl PyCentralEntry {
+    #[getter]
+    fn dist_id(&self) -> u32 {
+        self.inner.dist_id
+    }
+
+    #[getter]
+    fn next_dist_id(&self) -> u32 {
+        self.inner.next_dist_id
+    }
+
+    /// The tag as it was reported at this hop
+    #[getter]
+    fn rfid_data(&self) -> PyRfidData {
+        PyRfidData {
+            inner: self.inner.rfid_data.clone(),
+        }
+    }
+
+    #[getter]
+    fn signature<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.inner.signature)
+    }
+
+    /// `Json` or `Canonical`
+    #[getter]
+    fn encoding(&self) -> String {
+        format!("{:?}", self.inner.encoding)
+    }
+
+    /// Hash linking the entry to the next one in the record
+    fn hash<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.inner.hash())
+    }
+}
+
+/// The central server's history of a chip
+#[pyclass(name = "CentralRecord", module = "rfid_supply_chain")]
+struct PyCentralRecord {
+    inner: CentralRecord,
+}
+
+#[pymethods]
+impl PyCentralRecord {
+    #[new]
+    fn new(chip_id: u128) -> Self {
+        Self {
+            inner: CentralRecord::new(chip_id),
+        }
+    }
+
+    /// Parse a record as stored in the central server's database
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        serde_json::from_str(json)
+            .map(|inner| Self { inner })
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string(&self.inner).unwrap()
+    }
+
+    #[getter]
+    fn chip_id(&self) -> u128 {
+        self.inner.chip_id
+    }
+
+    #[getter]
+    fn entries(&self) -> Vec<PyCentralEntry> {
+        self.inner
+            .entries
+            .iter()
+            .map(|entry| PyCentralEntry {
+                inner: entry.clone(),
+            })
+            .collect()
+    }
+
+    /// Record a hop, signed with the central server's key
+    fn add_entry(
+        &mut self,
+        signer: &PySigner,
+        dist_id: u32,
+        next_dist_id: u32,
+        next_dist_pk: Vec<u8>,
+        rfid_data: &PyRfidData,
+    ) -> PyResult<()> {
+        self.inner
+            .add_entry(
+                &signer.inner,
+                dist_id,
+                next_dist_id,
+                next_dist_pk,
+                rfid_data.inner.clone(),
+            )
+            .map_err(|e| SignerError::new_err(e.to_string()))
+    }
+
+    /// Index of the first entry that fails to validate against the central server's PEM public
+    /// key, or `None` if the record is valid. Every next distributor must be in `keys`.
+    fn validate_chain(&self, keys: Keys, central_key: Vec<u8>) -> PyResult<Option<usize>> {
+        let keys = public_keys(keys)?;
+        let central_key = public_key(0, central_key, "central".to_string())?;
+
+        Ok(self.inner.validate_chain(&keys, central_key).err())
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.entries.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CentralRecord(chip_id={}, entries={})",
+            self.inner.chip_id,
+            self.inner.entries.len()
+        )
+    }
+}
+
+#[pymodule]
+fn rfid_supply_chain(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyChipData>()?;
+    m.add_class::<PySupplyChainEntry>()?;
+    m.add_class::<PySigner>()?;
+    m.add_class::<PyRfidData>()?;
+    m.add_class::<PyCentralEntry>()?;
+    m.add_class::<PyCentralRecord>()?;
+    m.add_function(wrap_pyfunction!(keys_from_json, m)?)?;
+    m.add("TagParseError", m.py().get_type::<TagParseError>())?;
+    m.add("InspectionError", m.py().get_type::<InspectionError>())?;
+    m.add("SignerError", m.py().get_type::<SignerError>())?;
+
+    Ok(())
+}
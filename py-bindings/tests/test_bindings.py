@@ -0,0 +1,139 @@
+"""Tests for the Python bindings, run them with `./run_tests.sh` in py-bindings"""
+
+import base64
+import json
+import subprocess
+import unittest
+
+import rfid_supply_chain as rsc
+
+
+def generate_key(algorithm):
+    """Private and public PEM keys made with the openssl command line tool"""
+    args = {
+        "RSA": ["-algorithm", "RSA", "-pkeyopt", "rsa_keygen_bits:2048"],
+        "ED25519": ["-algorithm", "ED25519"],
+        "P-256": ["-algorithm", "EC", "-pkeyopt", "ec_paramgen_curve:P-256"],
+    }[algorithm]
+
+    private = subprocess.run(
+        ["openssl", "genpkey", *args], check=True, capture_output=True
+    ).stdout
+    public = subprocess.run(
+        ["openssl", "pkey", "-pubout"], input=private, check=True, capture_output=True
+    ).stdout
+    return private, public
+
+
+class TestBindings(unittest.TestCase):
+    @classmethod
+    def setUpClass(cls):
+        pairs = [generate_key(algorithm) for algorithm in ["RSA", "ED25519", "P-256", "ED25519"]]
+        cls.signers = [rsc.Signer.from_pem(private) for private, _ in pairs]
+        cls.keys = {id: public for id, (_, public) in enumerate(pairs)}
+        cls.central = generate_key("ED25519")
+
+    def build_tag(self, version=1):
+        tag = rsc.RfidData(rsc.ChipData(2**100 + 42, 5.0, 3.3, 25.0, 1.5), version)
+        for id in range(3):
+            tag.add_entry(self.signers[id], id, id + 1, self.keys)
+        return tag
+
+    def test_chip_data(self):
+        chip_data = rsc.ChipData(2**127, 1.0, 2.0, 3.0, 4.0)
+        bytes = chip_data.to_bytes()
+        self.assertEqual(len(bytes), 32)
+        self.assertEqual(rsc.ChipData.from_bytes(bytes).chip_id, 2**127)
+
+        with self.assertRaises(rsc.TagParseError):
+            rsc.ChipData.from_bytes(bytes[:31])
+
+        with self.assertRaises(ValueError):
+            rsc.ChipData(1, float("nan"), 2.0, 3.0, 4.0)
+        with self.assertRaises(ValueError):
+            chip_data.temp = float("inf")
+        self.assertEqual(chip_data.temp, 3.0)
+
+        chip_data.add_measurement("ring_oscillator", 1.5e9)
+        chip_data.add_measurement("leakage_current", 0.5)
+        self.assertEqual(len(chip_data.to_bytes()), 32 + 2 + 2 * 6)
+        parsed = rsc.ChipData.from_bytes(chip_data.to_bytes())
+        self.assertEqual(parsed.measurements, [("ring_oscillator", 1.5e9), ("leakage_current", 0.5)])
+
+        with self.assertRaises(ValueError):
+            chip_data.add_measurement("unknown", 1.0)
+        with self.assertRaises(ValueError):
+            chip_data.add_measurement("path_delay", float("nan"))
+
+        # Measurements are kept when the chip data is put on a tag
+        tag = rsc.RfidData(chip_data)
+        self.assertEqual(
+            rsc.RfidData.from_bytes(tag.to_bytes()).chip_data.measurements,
+            [("ring_oscillator", 1.5e9), ("leakage_current", 0.5)],
+        )
+
+    def test_tag(self):
+        for version in [0, 1]:
+            tag = self.build_tag(version)
+            self.assertEqual(len(tag), 3)
+            self.assertEqual(
+                [entry.algorithm for entry in tag.entries], ["RsaSha3", "Ed25519", "EcdsaP256"]
+            )
+            self.assertTrue(tag.valid_crc())
+
+            # The legacy layout only holds RSA entries, so mixed tags are written as version 1
+            parsed = rsc.RfidData.from_bytes(tag.to_bytes())
+            self.assertEqual(parsed.version, 1)
+            self.assertEqual(parsed.crc, tag.crc)
+            self.assertEqual(parsed.chip_data.chip_id, 2**100 + 42)
+            self.assertEqual(parsed.entries[1].signature, tag.entries[1].signature)
+            self.assertEqual(rsc.RfidData.from_json(tag.to_json()).to_bytes(), tag.to_bytes())
+
+            self.assertIsNone(parsed.validate_chain(self.keys, 3))
+            self.assertEqual(parsed.validate_chain(self.keys, 2), 2)
+            parsed.inspect(self.keys, 3)
+            with self.assertRaises(rsc.InspectionError):
+                parsed.inspect(self.keys, 2)
+            with self.assertRaises(KeyError):
+                parsed.inspect(self.keys, 9)
+
+        corrupted = bytearray(self.build_tag().to_bytes())
+        corrupted[-1] ^= 1
+        with self.assertRaises(rsc.TagParseError):
+            rsc.RfidData.from_bytes(bytes(corrupted))
+
+        with self.assertRaises(KeyError):
+            self.build_tag().add_entry(self.signers[3], 3, 9, self.keys)
+
+    def test_keys_from_json(self):
+        response = {
+            "keys": {
+                str(id): {
+                    "id": id,
+                    "key": base64.b64encode(pem).decode(),
+                    "distributor_name": str(id),
+                }
+                for id, pem in self.keys.items()
+            }
+        }
+        self.assertEqual(rsc.keys_from_json(json.dumps(response)), self.keys)
+
+    def test_central_record(self):
+        central_private, central_public = self.central
+        central = rsc.Signer.from_pem(central_private)
+        tag = self.build_tag()
+
+        record = rsc.CentralRecord(tag.chip_data.chip_id)
+        record.add_entry(central, 2, 3, self.keys[3], tag)
+        record.add_entry(central, 3, 0, self.keys[0], tag)
+        self.assertEqual(len(record), 2)
+        self.assertEqual(record.entries[0].encoding, "Canonical")
+        self.assertIsNone(record.validate_chain(self.keys, central_public))
+
+        parsed = rsc.CentralRecord.from_json(record.to_json())
+        self.assertEqual(parsed.entries[1].hash(), record.entries[1].hash())
+        self.assertEqual(parsed.validate_chain(self.keys, self.keys[3]), 0)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -1,3 +1,6 @@
+pub mod passphrase;
+
+use passphrase::PassphraseArgs;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -9,7 +12,9 @@ use structopt::StructOpt;
 pub struct Args {
     #[structopt(subcommand)]
     pub mode: Mode,
+    #[structopt(default_value = "127.0.0.1")]
     pub address: String,
+    #[structopt(default_value = "8080")]
     pub port: u16,
 }
 
@@ -17,14 +22,41 @@ pub struct Args {
 pub enum Mode {
     DistributorServer(DistributorServerArgs),
     CentralServer(CentralServerArgs),
+    SigningDaemon(SigningDaemonArgs),
 }
 
 #[derive(Debug, StructOpt)]
 pub struct DistributorServerArgs {
-    pub key_id: u32,
-    #[structopt(parse(from_os_str))]
-    pub private_key: PathBuf,
-    pub central_server_addr: String,
+    #[structopt(
+        short = "d",
+        long = "database",
+        default_value = "dist_db",
+        parse(from_os_str)
+    )]
+    pub database_path: PathBuf,
+    /// Config file listing every identity hosted by this server, replaces the identity arguments
+    #[structopt(long = "identities", parse(from_os_str))]
+    pub identities_path: Option<PathBuf>,
+    #[structopt(required_unless = "identities-path")]
+    pub key_id: Option<u32>,
+    #[structopt(required_unless = "identities-path", parse(from_os_str))]
+    pub private_key: Option<PathBuf>,
+    #[structopt(required_unless = "identities-path")]
+    pub central_server_addr: Option<String>,
+    /// Bearer token required to register and revoke readers, reader management is disabled without it
+    #[structopt(long = "admin-token")]
+    pub admin_token: Option<String>,
+    /// Seconds before a cached public key is refreshed from the central server
+    #[structopt(long = "key-ttl", default_value = "600")]
+    pub key_ttl: u64,
+    /// Seconds a cached public key can still be used while the central server is unreachable
+    #[structopt(long = "key-max-stale", default_value = "3600")]
+    pub key_max_stale: u64,
+    /// Seconds between syncs of the whole key registry
+    #[structopt(long = "key-sync-interval", default_value = "60")]
+    pub key_sync_interval: u64,
+    #[structopt(flatten)]
+    pub passphrase: PassphraseArgs,
 }
 
 #[derive(Debug, StructOpt)]
@@ -39,4 +71,41 @@ pub struct CentralServerArgs {
     pub private_key: PathBuf,
     #[structopt(short = "i", long = "import", parse(from_os_str))]
     pub import_path: Option<PathBuf>,
+    /// Revoke these key IDs and exit, for keys that are lost and can't sign their own revocation
+    #[structopt(long = "revoke")]
+    pub revoke_key_ids: Vec<u32>,
+    /// Re-sign records stored before entries were signed over their canonical encoding and exit
+    #[structopt(long = "resign-records")]
+    pub resign_records: bool,
+    /// Config file with the aging model used to flag recycled chips, the defaults are used without one
+    #[structopt(long = "aging-model", parse(from_os_str))]
+    pub aging_model_path: Option<PathBuf>,
+    #[structopt(flatten)]
+    pub passphrase: PassphraseArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct SigningDaemonArgs {
+    #[structopt(
+        short = "d",
+        long = "database",
+        default_value = "signer_db",
+        parse(from_os_str)
+    )]
+    pub database_path: PathBuf,
+    /// Unix socket the distributor server connects to
+    #[structopt(parse(from_os_str))]
+    pub socket_path: PathBuf,
+    /// Key ID of the distributor whose key the daemon holds
+    pub key_id: u32,
+    /// Private key file or PKCS#11 URI
+    #[structopt(parse(from_os_str))]
+    pub private_key: PathBuf,
+    /// Central server the keys of the distributors in a tag's chain are fetched from
+    pub central_server_addr: String,
+    /// Config file with the signing policy, everything is signed without one
+    #[structopt(long = "policy", parse(from_os_str))]
+    pub policy_path: Option<PathBuf>,
+    #[structopt(flatten)]
+    pub passphrase: PassphraseArgs,
 }
@@ -0,0 +1,43 @@
+use models::error::SignerError;
+use models::signer::PassphraseSource;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+use zeroize::Zeroizing;
+
+/// Where to get the passphrase of an encrypted private key, prompts on the terminal by default
+#[derive(Debug, StructOpt)]
+pub struct PassphraseArgs {
+    /// Environment variable holding the key passphrase
+    #[structopt(long = "passphrase-env")]
+    pub passphrase_env: Option<String>,
+    /// File holding the key passphrase
+    #[structopt(long = "passphrase-file", parse(from_os_str))]
+    pub passphrase_file: Option<PathBuf>,
+}
+
+impl PassphraseSource for PassphraseArgs {
+    fn passphrase(&self, key_path: &Path) -> Result<Zeroizing<String>, SignerError> {
+        let passphrase_error = |reason: String| SignerError::KeyFileError {
+            path: key_path.to_path_buf(),
+            reason,
+        };
+
+        if let Some(var) = &self.passphrase_env {
+            let passphrase =
+                std::env::var(var).map_err(|_| passphrase_error(format!("{} is not set", var)))?;
+            return Ok(Zeroizing::new(passphrase));
+        }
+
+        if let Some(path) = &self.passphrase_file {
+            let passphrase = Zeroizing::new(std::fs::read_to_string(path).map_err(|e| {
+                passphrase_error(format!("unable to read {}: {}", path.display(), e))
+            })?);
+            return Ok(Zeroizing::new(passphrase.trim_end().to_string()));
+        }
+
+        let prompt = format!("Passphrase for {}: ", key_path.display());
+        rpassword::read_password_from_tty(Some(&prompt))
+            .map(Zeroizing::new)
+            .map_err(|e| passphrase_error(format!("unable to read passphrase: {}", e)))
+    }
+}
@@ -1,21 +1,33 @@
 use crate::args::{Args, CentralServerArgs};
+use crate::config::aging_config::AgingConfig;
 use crate::config::import_config::ImportConfig;
 use crate::database;
 use crate::database::Database;
 use crate::error::ApiError;
+use models::aging::{AgingModel, AgingReport};
+use models::algorithm::SignatureAlgorithm;
 use models::central_record::CentralRecord;
+use models::checkpoint::CHECKPOINT_KEY_ID;
 use models::key::PublicKey;
+use models::requests::aging::{AgingCheckRequest, AgingCheckResponse};
+use models::requests::checkpoint::{CheckpointRequest, CheckpointResponse};
 use models::requests::key_request::{KeyRequest, KeyResponse};
+use models::requests::revoke_key::{RevokeKeyRequest, RevokeKeyResponse};
 use models::requests::update_record::{UpdateRecordRequest, UpdateRecordResponse};
-use models::utility::open_private_key;
-use openssl::pkey::Private;
-use openssl::rsa::Rsa;
+use models::rfid::RfidData;
+use models::signer::{open_signer, Signer};
+use models::{BlockChainEntry, DatabaseModel};
 use std::collections::HashMap;
 use std::net::Ipv4Addr;
 use std::str::FromStr;
 use std::sync::Arc;
 use warp::Filter;
 
+/// A distributor's key, unless it is unknown or revoked
+fn valid_key(db: &Database, key_id: u32) -> Option<PublicKey> {
+    db.fetch::<PublicKey>(key_id).filter(|pk| !pk.revoked)
+}
+
 fn request_keys_filter(
     db: Arc<Database>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
@@ -30,63 +42,393 @@ fn request_keys_filter(
                 let pk = db.fetch::<PublicKey>(key_id);
 
                 if let Some(pk) = pk {
-                    key_response.keys.insert(key_id, pk);
+                    if !pk.revoked {
+                        key_response.keys.insert(key_id, pk);
+                    }
                 }
             }
             warp::reply::json(&key_response)
         })
 }
 
+fn key_registry_filter(
+    db: Arc<Database>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path("api"))
+        .and(warp::path("keys"))
+        .and(warp::any().map(move || db.clone()))
+        .map(|db: Arc<Database>| {
+            let mut key_response = KeyResponse::default();
+            for pk in db.fetch_all::<PublicKey>() {
+                key_response.keys.insert(pk.id(), pk);
+            }
+            warp::reply::json(&key_response)
+        })
+}
+
+fn record_filter(
+    db: Arc<Database>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path("api"))
+        .and(warp::path("record"))
+        .and(warp::path::param::<u128>())
+        .and(warp::path::end())
+        .and(warp::any().map(move || db.clone()))
+        .map(
+            |chip_id: u128, db: Arc<Database>| match db.fetch::<CentralRecord>(chip_id) {
+                Some(record) => {
+                    warp::reply::with_status(warp::reply::json(&record), warp::http::StatusCode::OK)
+                }
+                None => warp::reply::with_status(
+                    warp::reply::json(&format!("No record for chip {}", chip_id)),
+                    warp::http::StatusCode::NOT_FOUND,
+                ),
+            },
+        )
+}
+
+fn revoke_key_filter(
+    db: Arc<Database>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::post()
+        .and(warp::path("api"))
+        .and(warp::path("revoke_key"))
+        .and(warp::body::json())
+        .and(warp::any().map(move || db.clone()))
+        .map(|revoke_req: RevokeKeyRequest, db: Arc<Database>| {
+            let success = match db.fetch::<PublicKey>(revoke_req.key_id) {
+                Some(mut pk) => {
+                    let bytes: Vec<u8> = revoke_req.clone().into();
+                    if revoke_req.verify_signature(&bytes, &pk) {
+                        println!("Revoking key for {}", pk.distributor_name);
+                        pk.revoked = true;
+                        db.insert::<PublicKey>(pk);
+                        true
+                    } else {
+                        false
+                    }
+                }
+                None => false,
+            };
+
+            warp::reply::json(&RevokeKeyResponse { success })
+        })
+}
+
+/// Check that the key registered under `CHECKPOINT_KEY_ID` verifies the signer's signatures, so
+/// compacted tags can be validated. Returns whether a checkpoint key is registered.
+fn check_checkpoint_key(db: &Database, signer: &dyn Signer) -> Result<bool, ApiError> {
+    let central_key = match db.fetch::<PublicKey>(CHECKPOINT_KEY_ID) {
+        Some(central_key) => central_key,
+        None => return Ok(false),
+    };
+
+    let probe = b"RFSC checkpoint key check";
+    let signature = signer.sign(&[probe])?;
+    if central_key
+        .algorithm
+        .verify(&central_key.key, probe, &signature)
+    {
+        Ok(true)
+    } else {
+        Err(ApiError::CheckpointKeyMismatch)
+    }
+}
+
+/// Add a distributor's hop to the chip's record, returns the record and whether the chip looks
+/// recycled. The aging report of a recycled chip is stored for `/api/aging`.
+fn record_update(
+    request: UpdateRecordRequest,
+    db: &Database,
+    signer: &dyn Signer,
+    aging_model: &AgingModel,
+) -> Result<(CentralRecord, bool), String> {
+    let dist_key = valid_key(db, request.dist_id)
+        .ok_or_else(|| format!("No valid public key for distributor {}", request.dist_id))?;
+
+    let bytes: Vec<u8> = request.clone().into();
+    if !request.verify_signature(&bytes, &dist_key) {
+        return Err("Invalid request signature".to_string());
+    }
+
+    if request
+        .measurement
+        .is_some_and(|measurement| !measurement.is_valid())
+    {
+        return Err("Invalid measurement".to_string());
+    }
+
+    let mut keys = HashMap::new();
+    for entry in request.rfid_data.entries.iter() {
+        let pk = valid_key(db, entry.pub_key)
+            .ok_or_else(|| format!("No valid public key for distributor {}", entry.pub_key))?;
+        keys.insert(entry.pub_key, pk);
+    }
+
+    let next_dist_key = valid_key(db, request.next_dist_id).ok_or_else(|| {
+        format!(
+            "No valid public key for distributor {}",
+            request.next_dist_id
+        )
+    })?;
+
+    let chip_id = request.rfid_data.chip_data.chip_id;
+    let mut central_record = db
+        .fetch::<CentralRecord>(chip_id)
+        .unwrap_or_else(|| CentralRecord::new(chip_id));
+
+    // A checkpoint must have been issued from this chip's record
+    if let Some(checkpoint) = request.rfid_data.checkpoint {
+        if !central_record.covers(&checkpoint) {
+            return Err("Unknown checkpoint".to_string());
+        }
+    }
+
+    request
+        .rfid_data
+        .validate_chain(&keys, next_dist_key.clone())
+        .map_err(|i| format!("Invalid chain at position {}", i))?;
+
+    central_record
+        .add_measured_entry(
+            signer,
+            request.dist_id,
+            request.next_dist_id,
+            next_dist_key.key,
+            request.rfid_data,
+            request.measurement,
+        )
+        .map_err(|e| format!("Failed to sign record: {}", e))?;
+
+    db.insert::<CentralRecord>(central_record.clone());
+
+    let recycled = match aging_model.analyze(&central_record) {
+        Some(report) if report.recycled => {
+            println!(
+                "Chip {} has aged beyond its baseline and may be recycled",
+                chip_id
+            );
+            db.insert::<AgingReport>(report);
+            true
+        }
+        _ => {
+            db.remove::<AgingReport>(chip_id);
+            false
+        }
+    };
+
+    Ok((central_record, recycled))
+}
+
+/// Analyse a distributor's measurement of a chip as if it were the chip's next hop
+fn check_aging(
+    request: &AgingCheckRequest,
+    db: &Database,
+    aging_model: &AgingModel,
+) -> Result<Option<AgingReport>, String> {
+    if !request.measurement.is_valid() {
+        return Err("Invalid measurement".to_string());
+    }
+
+    Ok(db
+        .fetch::<CentralRecord>(request.chip_id)
+        .and_then(|record| {
+            aging_model.analyze_candidate(&record, request.dist_id, request.measurement)
+        }))
+}
+
+fn aging_check_filter(
+    db: Arc<Database>,
+    aging_model: Arc<AgingModel>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::post()
+        .and(warp::path("api"))
+        .and(warp::path("aging"))
+        .and(warp::path("check"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(warp::any().map(move || db.clone()))
+        .and(warp::any().map(move || aging_model.clone()))
+        .map(
+            |request: AgingCheckRequest, db: Arc<Database>, aging_model: Arc<AgingModel>| {
+                let response = match check_aging(&request, &db, &aging_model) {
+                    Ok(report) => AgingCheckResponse {
+                        success: true,
+                        report,
+                        error: None,
+                    },
+                    Err(error) => AgingCheckResponse {
+                        success: false,
+                        report: None,
+                        error: Some(error),
+                    },
+                };
+
+                warp::reply::json(&response)
+            },
+        )
+}
+
 fn update_record(
     db: Arc<Database>,
-    private_key: Rsa<Private>,
+    signer: Arc<dyn Signer>,
+    aging_model: Arc<AgingModel>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::post()
         .and(warp::path("api"))
         .and(warp::path("update_record"))
         .and(warp::body::json())
         .and(warp::any().map(move || db.clone()))
-        .and(warp::any().map(move || private_key.clone()))
+        .and(warp::any().map(move || signer.clone()))
+        .and(warp::any().map(move || aging_model.clone()))
         .map(
-            |update_req: UpdateRecordRequest, db: Arc<Database>, private_key: Rsa<Private>| {
-                let mut keys = HashMap::new();
+            |update_req: UpdateRecordRequest,
+             db: Arc<Database>,
+             signer: Arc<dyn Signer>,
+             aging_model: Arc<AgingModel>| {
+                let chip_id = update_req.rfid_data.chip_data.chip_id;
 
-                for entry in update_req.rfid_data.entries.iter() {
-                    keys.insert(entry.pub_key, db.fetch::<PublicKey>(entry.pub_key).unwrap());
-                }
+                let response = match record_update(update_req, &db, signer.as_ref(), &aging_model) {
+                    Ok((record, recycled)) => UpdateRecordResponse {
+                        success: true,
+                        record: Some(record),
+                        recycled,
+                        error: None,
+                    },
+                    Err(error) => {
+                        println!("Rejected record update for chip {}: {}", chip_id, error);
 
-                let next_dist_key = db.fetch::<PublicKey>(update_req.next_dist_id).unwrap();
+                        UpdateRecordResponse {
+                            success: false,
+                            record: None,
+                            recycled: false,
+                            error: Some(error),
+                        }
+                    }
+                };
 
-                if update_req
-                    .rfid_data
-                    .validate_chain(&keys, next_dist_key.clone())
-                    .is_err()
-                {
-                    warp::reply::json(&UpdateRecordResponse {
-                        record: None,
-                        success: false,
-                    })
-                } else {
-                    let chip_id = update_req.rfid_data.chip_data.chip_id;
-                    let mut central_record = db
-                        .fetch::<CentralRecord>(chip_id)
-                        .unwrap_or_else(|| CentralRecord::new(chip_id));
-
-                    central_record.add_entry(
-                        private_key.private_key_to_pem().unwrap(),
-                        update_req.dist_id,
-                        update_req.next_dist_id,
-                        next_dist_key.key,
-                        update_req.rfid_data,
-                    );
-
-                    db.insert::<CentralRecord>(central_record.clone());
-
-                    warp::reply::json(&UpdateRecordResponse {
-                        record: Some(central_record),
-                        success: true,
-                    })
+                warp::reply::json(&response)
+            },
+        )
+}
+
+/// Stored aging reports of every chip flagged as recycled, or the report of a single chip
+fn aging_filter(
+    db: Arc<Database>,
+    aging_model: Arc<AgingModel>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let base = warp::get().and(warp::path("api")).and(warp::path("aging"));
+
+    let flagged_db = db.clone();
+    let flagged = base
+        .and(warp::path::end())
+        .and(warp::any().map(move || flagged_db.clone()))
+        .map(|db: Arc<Database>| warp::reply::json(&db.fetch_all::<AgingReport>()));
+
+    let chip = base
+        .and(warp::path::param::<u128>())
+        .and(warp::path::end())
+        .and(warp::any().map(move || db.clone()))
+        .and(warp::any().map(move || aging_model.clone()))
+        .map(
+            |chip_id: u128, db: Arc<Database>, aging_model: Arc<AgingModel>| match db
+                .fetch::<CentralRecord>(chip_id)
+                .and_then(|record| aging_model.analyze(&record))
+            {
+                Some(report) => {
+                    warp::reply::with_status(warp::reply::json(&report), warp::http::StatusCode::OK)
                 }
+                None => warp::reply::with_status(
+                    warp::reply::json(&format!("No record for chip {}", chip_id)),
+                    warp::http::StatusCode::NOT_FOUND,
+                ),
+            },
+        );
+
+    flagged.or(chip)
+}
+
+/// Compact a tag for the distributor holding it, the tag's last hop must already be recorded
+fn issue_checkpoint(
+    request: &CheckpointRequest,
+    db: &Database,
+    signer: &dyn Signer,
+) -> Result<RfidData, String> {
+    let holder_key = valid_key(db, request.dist_id)
+        .ok_or_else(|| format!("No valid public key for distributor {}", request.dist_id))?;
+
+    let bytes: Vec<u8> = request.clone().into();
+    if !request.verify_signature(&bytes, &holder_key) {
+        return Err("Invalid request signature".to_string());
+    }
+
+    let central_key = db
+        .fetch::<PublicKey>(CHECKPOINT_KEY_ID)
+        .ok_or_else(|| "The central server's checkpoint key is not registered".to_string())?;
+
+    let mut keys = HashMap::new();
+    for entry in request.rfid_data.entries.iter() {
+        if let Some(pk) = valid_key(db, entry.pub_key) {
+            keys.insert(entry.pub_key, pk);
+        }
+    }
+
+    request
+        .rfid_data
+        .inspect(&keys, &holder_key)
+        .map_err(|e| format!("Rejected tag: {}", e))?;
+
+    let chip_id = request.rfid_data.chip_data.chip_id;
+    let record = db
+        .fetch::<CentralRecord>(chip_id)
+        .ok_or_else(|| format!("No record for chip {}", chip_id))?;
+
+    record
+        .checkpoint(signer, &central_key, &request.rfid_data, &holder_key)
+        .map_err(|e| e.to_string())
+}
+
+fn checkpoint_filter(
+    db: Arc<Database>,
+    signer: Arc<dyn Signer>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::post()
+        .and(warp::path("api"))
+        .and(warp::path("checkpoint"))
+        .and(warp::body::json())
+        .and(warp::any().map(move || db.clone()))
+        .and(warp::any().map(move || signer.clone()))
+        .map(
+            |request: CheckpointRequest, db: Arc<Database>, signer: Arc<dyn Signer>| {
+                let chip_id = request.rfid_data.chip_data.chip_id;
+
+                let response = match issue_checkpoint(&request, &db, signer.as_ref()) {
+                    Ok(rfid_data) => {
+                        println!(
+                            "Compacted chip {} for distributor {}",
+                            chip_id, request.dist_id
+                        );
+
+                        CheckpointResponse {
+                            success: true,
+                            rfid_data: Some(rfid_data),
+                            error: None,
+                        }
+                    }
+                    Err(error) => {
+                        println!("Refused to compact chip {}: {}", chip_id, error);
+
+                        CheckpointResponse {
+                            success: false,
+                            rfid_data: None,
+                            error: Some(error),
+                        }
+                    }
+                };
+
+                warp::reply::json(&response)
             },
         )
 }
@@ -97,22 +439,206 @@ pub async fn central_server(args: &Args, cent_args: &CentralServerArgs) -> Resul
     if let Some(import) = &cent_args.import_path {
         let import_cfg = ImportConfig::new(import)?;
 
-        for public_key in import_cfg.import {
-            if Rsa::public_key_from_pem(&public_key.key).is_ok() {
+        for mut public_key in import_cfg.import {
+            if let Some(algorithm) = SignatureAlgorithm::of_public_key(&public_key.key) {
+                public_key.algorithm = algorithm;
                 db.insert::<PublicKey>(public_key);
             } else {
-                println!("{} has an invalid RSA key!", public_key.distributor_name)
+                println!(
+                    "{} has an invalid or unsupported key!",
+                    public_key.distributor_name
+                )
+            }
+        }
+    } else if !cent_args.revoke_key_ids.is_empty() {
+        for key_id in &cent_args.revoke_key_ids {
+            match db.fetch::<PublicKey>(*key_id) {
+                Some(mut pk) => {
+                    println!("Revoking key for {}", pk.distributor_name);
+                    pk.revoked = true;
+                    db.insert::<PublicKey>(pk);
+                }
+                None => println!("No key with ID {} to revoke", key_id),
+            }
+        }
+    } else if cent_args.resign_records {
+        let signer = open_signer(
+            &cent_args.private_key.to_string_lossy(),
+            &cent_args.passphrase,
+        )?;
+        let keys: HashMap<u32, PublicKey> = db
+            .fetch_all::<PublicKey>()
+            .into_iter()
+            .map(|pk| (pk.id(), pk))
+            .collect();
+
+        for mut record in db.fetch_all::<CentralRecord>() {
+            if !record.is_legacy() {
+                continue;
+            }
+
+            match record.resign(signer.as_ref(), &keys) {
+                Ok(()) => {
+                    println!("Re-signed record for chip {}", record.chip_id);
+                    db.insert::<CentralRecord>(record);
+                }
+                Err(e) => println!(
+                    "Unable to re-sign record for chip {}: {}",
+                    record.chip_id, e
+                ),
             }
         }
     } else {
+        let aging_model = Arc::new(match &cent_args.aging_model_path {
+            Some(path) => AgingConfig::new(path)?.aging_model,
+            None => AgingModel::default(),
+        });
+
         println!("Starting central server...");
 
-        let private_key = open_private_key(cent_args.private_key.clone());
+        let signer: Arc<dyn Signer> = Arc::from(open_signer(
+            &cent_args.private_key.to_string_lossy(),
+            &cent_args.passphrase,
+        )?);
+
+        if !check_checkpoint_key(&db, signer.as_ref())? {
+            println!(
+                "No checkpoint key registered under {}, tags can't be compacted",
+                CHECKPOINT_KEY_ID
+            );
+        }
 
-        warp::serve(request_keys_filter(db.clone()).or(update_record(db, private_key)))
-            .run((Ipv4Addr::from_str(&args.address).unwrap(), args.port))
-            .await;
+        warp::serve(
+            request_keys_filter(db.clone())
+                .or(key_registry_filter(db.clone()))
+                .or(revoke_key_filter(db.clone()))
+                .or(record_filter(db.clone()))
+                .or(checkpoint_filter(db.clone(), signer.clone()))
+                .or(aging_filter(db.clone(), aging_model.clone()))
+                .or(aging_check_filter(db.clone(), aging_model.clone()))
+                .or(update_record(db, signer, aging_model)),
+        )
+        .run((Ipv4Addr::from_str(&args.address).unwrap(), args.port))
+        .await;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::central_server::{check_aging, check_checkpoint_key, record_update};
+    use crate::database::Database;
+    use crate::error::ApiError;
+    use models::aging::{AgingModel, AgingReport, HopMeasurement};
+    use models::checkpoint::CHECKPOINT_KEY_ID;
+    use models::key::PublicKey;
+    use models::requests::aging::AgingCheckRequest;
+    use models::requests::update_record::UpdateRecordRequest;
+    use models::rfid::RfidBuilder;
+    use models::signer::file_key::FileKeySigner;
+    use models::test_util::{ed25519_keypairs, key_map};
+    use openssl::pkey::PKey;
+
+    #[test]
+    fn test_checkpoint_key() {
+        let db = Database::new(&std::env::temp_dir().join("rfsc_test_checkpoint_key_db"));
+        db.clear();
+
+        let keypair = PKey::generate_ed25519().unwrap();
+        let signer = FileKeySigner::from_pkey(keypair.clone()).unwrap();
+        let register = |keypair: &PKey<_>| {
+            let pem = keypair.public_key_to_pem().unwrap();
+            db.insert(PublicKey::new(CHECKPOINT_KEY_ID, pem, "central".to_string()).unwrap());
+        };
+
+        assert!(!check_checkpoint_key(&db, &signer).unwrap());
+
+        register(&PKey::generate_ed25519().unwrap());
+        assert!(matches!(
+            check_checkpoint_key(&db, &signer),
+            Err(ApiError::CheckpointKeyMismatch)
+        ));
+
+        register(&keypair);
+        assert!(check_checkpoint_key(&db, &signer).unwrap());
+
+        db.clear();
+    }
+
+    #[test]
+    fn test_record_update() {
+        let db = Database::new(&std::env::temp_dir().join("rfsc_test_record_update_db"));
+        db.clear();
+
+        let keypairs = ed25519_keypairs(3);
+        let keys = key_map(&keypairs);
+        for pk in keys.values() {
+            db.insert(pk.clone());
+        }
+        let signers: Vec<FileKeySigner> = keypairs
+            .iter()
+            .map(|keypair| FileKeySigner::from_pkey(keypair.clone()).unwrap())
+            .collect();
+        let central = &signers[0];
+
+        let rfid_data = RfidBuilder::default()
+            .chip_data(42, 100e6, 1.0, 25.0, 5.0)
+            .add_entry(&signers[1], 1, 2, &keys)
+            .unwrap()
+            .build();
+        let measure = |freq: f32| HopMeasurement {
+            freq,
+            voltage: 1.0,
+            temp: 25.0,
+        };
+        let update = |signer: &FileKeySigner, measurement: HopMeasurement| {
+            let request =
+                UpdateRecordRequest::new(1, 2, rfid_data.clone(), Some(measurement), signer)
+                    .unwrap();
+            record_update(request, &db, central, &AgingModel::default())
+        };
+
+        let check = |measurement| {
+            check_aging(
+                &AgingCheckRequest {
+                    dist_id: 2,
+                    chip_id: 42,
+                    measurement,
+                },
+                &db,
+                &AgingModel::default(),
+            )
+        };
+
+        // Signed by someone other than the distributor
+        assert!(update(&signers[2], measure(100e6)).is_err());
+        assert!(update(&signers[1], measure(f32::NAN)).is_err());
+
+        // Nothing to compare with before the enrollment is recorded
+        assert!(check(measure(96e6)).unwrap().is_none());
+
+        let (record, recycled) = update(&signers[1], measure(100e6)).unwrap();
+        assert_eq!(record.entries.len(), 1);
+        assert!(!recycled);
+        assert!(db.fetch::<AgingReport>(42).is_none());
+
+        // Checking a measurement does not record it
+        assert!(check(measure(96e6)).unwrap().unwrap().recycled);
+        assert!(!check(measure(100e6)).unwrap().unwrap().recycled);
+        assert!(check(measure(-1.0)).is_err());
+        assert!(db.fetch::<AgingReport>(42).is_none());
+
+        let (record, recycled) = update(&signers[1], measure(96e6)).unwrap();
+        assert_eq!(record.entries.len(), 2);
+        assert!(recycled);
+        assert!(db.fetch::<AgingReport>(42).unwrap().recycled);
+
+        let mut revoked = keys[&1].clone();
+        revoked.revoked = true;
+        db.insert(revoked);
+        assert!(update(&signers[1], measure(100e6)).is_err());
+
+        db.clear();
+    }
+}
@@ -0,0 +1,20 @@
+use config::{Config, ConfigError, File};
+use models::aging::AgingModel;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Aging model the central server flags recycled chips with
+#[derive(Debug, Deserialize, Clone)]
+pub struct AgingConfig {
+    #[serde(default)]
+    pub aging_model: AgingModel,
+}
+
+impl AgingConfig {
+    pub fn new(config_path: &Path) -> Result<Self, ConfigError> {
+        let mut cfg = Config::new();
+        cfg.merge(File::with_name(config_path.to_str().unwrap()))?;
+
+        cfg.try_into()
+    }
+}
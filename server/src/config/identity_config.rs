@@ -0,0 +1,25 @@
+use config::{Config, ConfigError, File};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// A distributor identity hosted by the distributor server
+#[derive(Debug, Deserialize, Clone)]
+pub struct IdentitySettings {
+    pub key_id: u32,
+    pub private_key: PathBuf,
+    pub central_server_addr: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct IdentityConfig {
+    pub identities: Vec<IdentitySettings>,
+}
+
+impl IdentityConfig {
+    pub fn new(config_path: &Path) -> Result<Self, ConfigError> {
+        let mut cfg = Config::new();
+        cfg.merge(File::with_name(config_path.to_str().unwrap()))?;
+
+        cfg.try_into()
+    }
+}
@@ -1 +1,4 @@
+pub mod aging_config;
+pub mod identity_config;
 pub mod import_config;
+pub mod signing_policy_config;
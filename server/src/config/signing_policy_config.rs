@@ -0,0 +1,72 @@
+use config::{Config, ConfigError, File};
+use serde::{Deserialize, Deserializer};
+use std::path::Path;
+
+/// Inclusive range of chip IDs a signing daemon will sign.
+///
+/// IDs that don't fit in a TOML integer can be written as decimal strings.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ChipIdRange {
+    #[serde(deserialize_with = "deserialize_chip_id")]
+    pub start: u128,
+    #[serde(deserialize_with = "deserialize_chip_id")]
+    pub end: u128,
+}
+
+fn deserialize_chip_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ChipId {
+        Integer(u64),
+        String(String),
+    }
+
+    match ChipId::deserialize(deserializer)? {
+        ChipId::Integer(id) => Ok(id as u128),
+        ChipId::String(id) => id.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+/// Limits enforced by the signing daemon, everything is allowed when a limit is left out
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct SigningPolicyConfig {
+    #[serde(default)]
+    pub max_signatures_per_minute: Option<usize>,
+    #[serde(default)]
+    pub allowed_next_distributors: Option<Vec<u32>>,
+    #[serde(default)]
+    pub chip_id_ranges: Option<Vec<ChipIdRange>>,
+}
+
+impl SigningPolicyConfig {
+    pub fn new(config_path: &Path) -> Result<Self, ConfigError> {
+        let mut cfg = Config::new();
+        cfg.merge(File::with_name(config_path.to_str().unwrap()))?;
+
+        cfg.try_into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::signing_policy_config::SigningPolicyConfig;
+
+    #[test]
+    fn test_chip_id_ranges() {
+        let path = std::env::temp_dir().join("rfsc_test_signing_policy.toml");
+        std::fs::write(
+            &path,
+            "[[chip_id_ranges]]\nstart = 1000\nend = 1999\n\n\
+             [[chip_id_ranges]]\nstart = \"18446744073709551616\"\nend = \"340282366920938463463374607431768211455\"\n",
+        )
+        .unwrap();
+
+        let config = SigningPolicyConfig::new(&path).unwrap();
+        let ranges = config.chip_id_ranges.unwrap();
+        assert_eq!((ranges[0].start, ranges[0].end), (1000, 1999));
+        assert_eq!(ranges[1].start, u64::MAX as u128 + 1);
+        assert_eq!(ranges[1].end, u128::MAX);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -29,30 +29,101 @@ impl Database {
         let tree = self.db.open_tree(T::tree()).unwrap();
         let bytes = tree.get(T::id_type_to_bytes(id)).unwrap();
 
-        if let Some(bytes) = bytes {
-            Some(serde_json::from_slice::<T>(&bytes).unwrap())
-        } else {
-            None
+        bytes.map(|bytes| serde_json::from_slice::<T>(&bytes).unwrap())
+    }
+
+    pub fn fetch_all<T>(&self) -> Vec<T>
+    where
+        T: DatabaseModel,
+    {
+        let tree = self.db.open_tree(T::tree()).unwrap();
+
+        tree.iter()
+            .values()
+            .map(|bytes| serde_json::from_slice::<T>(&bytes.unwrap()).unwrap())
+            .collect()
+    }
+
+    /// Atomically replace the model stored under `id` with `f` applied to it, returns the new model.
+    ///
+    /// `f` is called again if another writer changed the model in the meantime.
+    pub fn update<T, F>(&self, id: T::ID, f: F) -> T
+    where
+        T: DatabaseModel,
+        F: Fn(Option<T>) -> T,
+    {
+        let tree = self.db.open_tree(T::tree()).unwrap();
+        let bytes = tree
+            .update_and_fetch(T::id_type_to_bytes(id), |bytes| {
+                let model = bytes.map(|bytes| serde_json::from_slice::<T>(bytes).unwrap());
+                Some(serde_json::to_vec(&f(model)).unwrap())
+            })
+            .unwrap()
+            .unwrap();
+
+        serde_json::from_slice::<T>(&bytes).unwrap()
+    }
+
+    pub fn remove<T>(&self, id: T::ID)
+    where
+        T: DatabaseModel,
+    {
+        let tree = self.db.open_tree(T::tree()).unwrap();
+        tree.remove(T::id_type_to_bytes(id)).unwrap();
+    }
+
+    pub fn count<T>(&self) -> usize
+    where
+        T: DatabaseModel,
+    {
+        let tree = self.db.open_tree(T::tree()).unwrap();
+        tree.len()
+    }
+
+    pub fn clear(&self) {
+        for name in self.db.tree_names() {
+            self.db.drop_tree(name).ok();
         }
+        self.db.clear().unwrap();
+    }
+
+    pub fn generate_id(&self) -> u64 {
+        self.db.generate_id().unwrap()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::database::Database;
-    use crate::models::key::PublicKey;
+    use models::key::PublicKey;
+    use openssl::pkey::PKey;
     use std::path::Path;
 
     #[test]
     fn test_db() {
         let db = Database::new(Path::new("test_db"));
-        let public_key = PublicKey::new(0, vec![0, 1], "dist1".to_string());
+        let pem = || {
+            PKey::generate_ed25519()
+                .unwrap()
+                .public_key_to_pem()
+                .unwrap()
+        };
+        let public_key = PublicKey::new(0, pem(), "dist1".to_string()).unwrap();
         db.insert::<PublicKey>(public_key.clone());
 
         let public_key2 = db.fetch::<PublicKey>(public_key.id).unwrap();
 
         assert_eq!(public_key.id, public_key2.id);
 
+        let public_key3 = PublicKey::new(1, pem(), "dist2".to_string()).unwrap();
+        db.insert::<PublicKey>(public_key3.clone());
+        assert_eq!(db.count::<PublicKey>(), 2);
+        assert_eq!(db.fetch_all::<PublicKey>().len(), 2);
+
+        db.remove::<PublicKey>(public_key.id);
+        assert!(db.fetch::<PublicKey>(public_key.id).is_none());
+        assert_eq!(db.count::<PublicKey>(), 1);
+
         db.db.clear().unwrap();
     }
 }
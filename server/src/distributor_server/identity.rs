@@ -0,0 +1,263 @@
+use crate::args::DistributorServerArgs;
+use crate::config::identity_config::IdentitySettings;
+use crate::database::Database;
+use crate::distributor_server::key_cache::KeyCache;
+use crate::distributor_server::record_queue::RecordQueue;
+use crate::error::ApiError;
+use crate::signing_daemon::client::RemoteSigner;
+use models::error::SignerError;
+use models::reader::Reader;
+use models::requests::sign::SignRequest;
+use models::signer::{open_signer, PassphraseSource, Signer};
+use reqwest::Url;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdentityError {
+    UnknownIdentity(u32),
+    NotPermitted { reader_id: u64, dist_id: u32 },
+}
+
+impl Error for IdentityError {}
+
+impl Display for IdentityError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdentityError::UnknownIdentity(id) => {
+                write!(f, "Distributor {} is not hosted by this server", id)
+            }
+            IdentityError::NotPermitted { reader_id, dist_id } => write!(
+                f,
+                "Reader {} is not allowed to sign as distributor {}",
+                reader_id, dist_id
+            ),
+        }
+    }
+}
+
+/// Where an identity's signatures come from
+pub enum IdentitySigner {
+    Local(Arc<dyn Signer>),
+    Remote(RemoteSigner),
+}
+
+impl IdentitySigner {
+    /// Open a `unix:<socket path>` signing daemon, or a key file or PKCS#11 URI held locally
+    pub fn open(spec: &str, passphrase: &dyn PassphraseSource) -> Result<Self, ApiError> {
+        match spec.strip_prefix("unix:") {
+            Some(socket_path) => Ok(Self::Remote(RemoteSigner::new(socket_path.into()))),
+            None => Ok(Self::Local(Arc::from(open_signer(spec, passphrase)?))),
+        }
+    }
+}
+
+/// A distributor identity with its own key and central server connection
+pub struct Identity {
+    pub key_id: u32,
+    signer: IdentitySigner,
+    pub central_server_addr: Url,
+    /// Shared connection pool for requests to the central server
+    pub client: reqwest::Client,
+    pub key_cache: Arc<KeyCache>,
+    pub record_queue: Arc<RecordQueue>,
+}
+
+impl Identity {
+    /// Open the identity's signer, sync its key cache and start its background tasks
+    pub async fn start(
+        settings: &IdentitySettings,
+        db: Arc<Database>,
+        dist_args: &DistributorServerArgs,
+    ) -> Result<Arc<Self>, ApiError> {
+        let signer = IdentitySigner::open(
+            &settings.private_key.to_string_lossy(),
+            &dist_args.passphrase,
+        )?;
+        let central_server_addr = Url::from_str(settings.central_server_addr.as_str()).unwrap();
+
+        let key_cache = KeyCache::new(
+            central_server_addr.clone(),
+            Duration::from_secs(dist_args.key_ttl),
+            Duration::from_secs(dist_args.key_max_stale),
+        );
+
+        if let Err(e) = key_cache.sync().await {
+            println!(
+                "Failed to sync keys with the central server for distributor {}: {}",
+                settings.key_id, e
+            );
+        }
+
+        tokio::spawn(
+            key_cache
+                .clone()
+                .run(Duration::from_secs(dist_args.key_sync_interval)),
+        );
+
+        let record_queue = RecordQueue::new(settings.key_id, db, central_server_addr.clone());
+        tokio::spawn(record_queue.clone().run());
+
+        Ok(Arc::new(Self {
+            key_id: settings.key_id,
+            signer,
+            central_server_addr,
+            client: reqwest::Client::new(),
+            key_cache,
+            record_queue,
+        }))
+    }
+
+    /// Signer for the data described by `request`.
+    ///
+    /// A signing daemon builds and signs the data itself, the returned signer hands back its
+    /// signature.
+    pub async fn signer(&self, request: SignRequest) -> Result<Arc<dyn Signer>, SignerError> {
+        match &self.signer {
+            IdentitySigner::Local(signer) => Ok(signer.clone()),
+            IdentitySigner::Remote(remote) => Ok(Arc::new(remote.sign(&request).await?)),
+        }
+    }
+}
+
+/// Every identity hosted by the distributor server, the first one is the default
+pub struct Identities {
+    identities: Vec<Arc<Identity>>,
+}
+
+impl Identities {
+    pub fn new(identities: Vec<Arc<Identity>>) -> Arc<Self> {
+        Arc::new(Self { identities })
+    }
+
+    pub fn get(&self, dist_id: u32) -> Option<Arc<Identity>> {
+        self.identities
+            .iter()
+            .find(|identity| identity.key_id == dist_id)
+            .cloned()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Identity>> {
+        self.identities.iter()
+    }
+
+    /// Pick the identity to use for a request.
+    ///
+    /// Readers bound to an identity always use it, other readers only use the default identity.
+    /// A request may name the identity it expects, which has to be the one the reader uses.
+    pub fn select(
+        &self,
+        reader: &Reader,
+        requested: Option<u32>,
+    ) -> Result<Arc<Identity>, IdentityError> {
+        let identity = match reader.identity {
+            Some(bound) => self
+                .get(bound)
+                .ok_or(IdentityError::UnknownIdentity(bound))?,
+            None => self.identities[0].clone(),
+        };
+
+        match requested {
+            Some(requested) if requested != identity.key_id => Err(match self.get(requested) {
+                Some(_) => IdentityError::NotPermitted {
+                    reader_id: reader.id,
+                    dist_id: requested,
+                },
+                None => IdentityError::UnknownIdentity(requested),
+            }),
+            _ => Ok(identity),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::database::Database;
+    use crate::distributor_server::identity::{
+        Identities, Identity, IdentityError, IdentitySigner,
+    };
+    use crate::distributor_server::key_cache::KeyCache;
+    use crate::distributor_server::record_queue::RecordQueue;
+    use models::reader::Reader;
+    use models::signer::file_key::FileKeySigner;
+    use openssl::pkey::PKey;
+    use reqwest::Url;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// An identity with a fresh key and a central server that is never contacted
+    pub fn identity(key_id: u32, db: Arc<Database>) -> Arc<Identity> {
+        let signer = FileKeySigner::from_pkey(PKey::generate_ed25519().unwrap()).unwrap();
+        let central_server_addr = Url::parse("http://127.0.0.1:1/").unwrap();
+
+        Arc::new(Identity {
+            key_id,
+            signer: IdentitySigner::Local(Arc::new(signer)),
+            central_server_addr: central_server_addr.clone(),
+            client: reqwest::Client::new(),
+            key_cache: KeyCache::new(
+                central_server_addr.clone(),
+                Duration::from_secs(60),
+                Duration::from_secs(60),
+            ),
+            record_queue: RecordQueue::new(key_id, db, central_server_addr),
+        })
+    }
+
+    fn reader(id: u64, identity: Option<u32>) -> Reader {
+        Reader {
+            id,
+            name: format!("reader {}", id),
+            token_hash: vec![],
+            revoked: false,
+            registered_at: 0,
+            identity,
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let db = Database::new(&std::env::temp_dir().join("rfsc_test_identity_db"));
+        db.clear();
+        let identities = Identities::new(vec![identity(3, db.clone()), identity(5, db.clone())]);
+
+        // Unbound readers only sign as the default identity
+        let unbound = reader(1, None);
+        assert_eq!(identities.select(&unbound, None).unwrap().key_id, 3);
+        assert_eq!(identities.select(&unbound, Some(3)).unwrap().key_id, 3);
+        assert_eq!(
+            identities.select(&unbound, Some(5)).err(),
+            Some(IdentityError::NotPermitted {
+                reader_id: 1,
+                dist_id: 5
+            })
+        );
+        assert_eq!(
+            identities.select(&unbound, Some(9)).err(),
+            Some(IdentityError::UnknownIdentity(9))
+        );
+
+        let bound = reader(2, Some(5));
+        assert_eq!(identities.select(&bound, None).unwrap().key_id, 5);
+        assert_eq!(identities.select(&bound, Some(5)).unwrap().key_id, 5);
+        assert_eq!(
+            identities.select(&bound, Some(3)).err(),
+            Some(IdentityError::NotPermitted {
+                reader_id: 2,
+                dist_id: 3
+            })
+        );
+
+        // A reader bound to an identity the server no longer hosts can't sign at all
+        let stale = reader(3, Some(7));
+        assert_eq!(
+            identities.select(&stale, None).err(),
+            Some(IdentityError::UnknownIdentity(7))
+        );
+
+        db.clear();
+    }
+}
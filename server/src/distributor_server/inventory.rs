@@ -0,0 +1,210 @@
+use crate::database::Database;
+use models::inventory::{InventoryItem, Shipment};
+use models::requests::inventory::InventoryReconciliation;
+use models::rfid::RfidData;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Local record of the chips each identity of a distributor has received and signed out
+pub struct Inventory {
+    db: Arc<Database>,
+}
+
+impl Inventory {
+    pub fn new(db: Arc<Database>) -> Arc<Self> {
+        Arc::new(Self { db })
+    }
+
+    /// Record a chip as received by `dist_id`. A chip already in stock keeps its record, a chip
+    /// that was shipped is received again.
+    pub fn receive(&self, dist_id: u32, rfid_data: &RfidData) -> InventoryItem {
+        let chip_id = rfid_data.chip_data.chip_id;
+        let received_from = rfid_data.entries.last().map(|entry| entry.pub_key);
+        let received_at = timestamp();
+
+        self.db
+            .update::<InventoryItem, _>((dist_id, chip_id), |item| match item {
+                Some(mut item) => {
+                    item.receive(received_from, received_at);
+                    item
+                }
+                None => InventoryItem::new(dist_id, chip_id, received_from, received_at),
+            })
+    }
+
+    /// Record a chip as signed out by `dist_id` to the next distributor
+    pub fn ship(&self, rfid_data: &RfidData, dist_id: u32, next_dist_id: u32, reader_id: u64) {
+        let chip_id = rfid_data.chip_data.chip_id;
+        let received_from = rfid_data.entries.last().map(|entry| entry.pub_key);
+        let shipment = Shipment {
+            dist_id: Some(dist_id),
+            next_dist_id,
+            shipped_at: timestamp(),
+            reader_id: Some(reader_id),
+        };
+
+        self.db
+            .update::<InventoryItem, _>((dist_id, chip_id), |item| {
+                let mut item = item.unwrap_or_else(|| {
+                    InventoryItem::new(dist_id, chip_id, received_from, shipment.shipped_at)
+                });
+                item.shipments.push(shipment.clone());
+                item
+            });
+    }
+
+    pub fn lookup(&self, dist_id: u32, chip_id: u128) -> Option<InventoryItem> {
+        self.db.fetch::<InventoryItem>((dist_id, chip_id))
+    }
+
+    fn items(&self, dist_id: u32) -> impl Iterator<Item = InventoryItem> {
+        self.db
+            .fetch_all::<InventoryItem>()
+            .into_iter()
+            .filter(move |item| item.dist_id == dist_id)
+    }
+
+    pub fn stock(&self, dist_id: u32) -> Vec<InventoryItem> {
+        self.items(dist_id).filter(|item| item.in_stock()).collect()
+    }
+
+    pub fn reconcile(&self, dist_id: u32) -> InventoryReconciliation {
+        let mut reconciliation = InventoryReconciliation::default();
+
+        for item in self.items(dist_id) {
+            reconciliation.received += 1;
+
+            if item.in_stock() {
+                reconciliation.in_stock += 1;
+            } else {
+                reconciliation.shipped += 1;
+            }
+
+            if item.reshipped() {
+                reconciliation.reshipped.push(item.chip_id);
+            }
+        }
+
+        reconciliation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::Database;
+    use crate::distributor_server::inventory::Inventory;
+    use models::algorithm::SignatureAlgorithm;
+    use models::rfid::{RfidBuilder, RfidData};
+    use models::supply_chain::SupplyChainEntry;
+
+    fn tag(chip_id: u128, last_dist_id: Option<u32>) -> RfidData {
+        let mut rfid_data = RfidBuilder::default()
+            .chip_data(chip_id, 5.0, 5.0, 5.0, 5.0)
+            .build();
+
+        if let Some(dist_id) = last_dist_id {
+            rfid_data.entries.push(SupplyChainEntry {
+                pub_key: dist_id,
+                signature: vec![0; 64],
+                algorithm: SignatureAlgorithm::Ed25519,
+            });
+        }
+
+        rfid_data
+    }
+
+    #[test]
+    fn test_inventory() {
+        let db = Database::new(&std::env::temp_dir().join("rfsc_test_inventory_db"));
+        db.clear();
+        let inventory = Inventory::new(db.clone());
+
+        let first = inventory.receive(0, &tag(1, Some(4)));
+        assert_eq!(first.received_from, Some(4));
+        assert!(first.in_stock());
+
+        // Receiving a chip again keeps the original record
+        let again = inventory.receive(0, &tag(1, Some(5)));
+        assert_eq!(again.received_from, Some(4));
+        assert_eq!(again.received_at, first.received_at);
+
+        inventory.receive(0, &tag(2, None));
+        inventory.ship(&tag(2, None), 0, 3, 7);
+
+        // Shipping a chip that was never received records it as received too
+        inventory.ship(&tag(3, Some(4)), 0, 3, 7);
+        inventory.ship(&tag(3, Some(4)), 0, 6, 7);
+
+        let stock = inventory.stock(0);
+        assert_eq!(stock.len(), 1);
+        assert_eq!(stock[0].chip_id, 1);
+
+        let shipped = inventory.lookup(0, 2).unwrap();
+        assert_eq!(shipped.received_from, None);
+        assert_eq!(shipped.next_dist_id(), Some(3));
+        assert_eq!(shipped.shipments[0].reader_id, Some(7));
+        assert!(inventory.lookup(0, 9).is_none());
+
+        let reconciliation = inventory.reconcile(0);
+        assert_eq!(reconciliation.received, 3);
+        assert_eq!(reconciliation.in_stock, 1);
+        assert_eq!(reconciliation.shipped, 2);
+        assert_eq!(reconciliation.reshipped, vec![3]);
+
+        // Another identity of the same server keeps its own record of the chip
+        assert!(inventory.lookup(1, 2).is_none());
+        inventory.receive(1, &tag(2, Some(0)));
+        assert_eq!(inventory.lookup(1, 2).unwrap().received_from, Some(0));
+        assert_eq!(inventory.lookup(0, 2).unwrap().next_dist_id(), Some(3));
+        assert_eq!(inventory.stock(1).len(), 1);
+        assert_eq!(inventory.reconcile(0).received, 3);
+
+        // A chip that comes back after it was shipped is in stock again
+        let returned = inventory.receive(0, &tag(2, Some(3)));
+        assert!(returned.in_stock());
+        assert_eq!(returned.received_from, None);
+        assert_eq!(returned.returns[0].received_from, Some(3));
+        assert_eq!(inventory.stock(0).len(), 2);
+        assert_eq!(inventory.reconcile(0).in_stock, 2);
+
+        inventory.ship(&tag(2, Some(3)), 0, 3, 7);
+        let reshipped = inventory.lookup(0, 2).unwrap();
+        assert!(!reshipped.in_stock());
+        assert!(!reshipped.reshipped());
+        assert_eq!(inventory.reconcile(0).reshipped, vec![3]);
+
+        db.clear();
+    }
+
+    #[test]
+    fn test_concurrent_shipments() {
+        let db = Database::new(&std::env::temp_dir().join("rfsc_test_inventory_concurrent_db"));
+        db.clear();
+        let inventory = Inventory::new(db.clone());
+
+        let threads: Vec<_> = (0..8)
+            .map(|reader_id| {
+                let inventory = inventory.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        inventory.ship(&tag(1, None), 0, 3, reader_id);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(inventory.lookup(0, 1).unwrap().shipments.len(), 80);
+
+        db.clear();
+    }
+}
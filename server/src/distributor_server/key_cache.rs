@@ -0,0 +1,235 @@
+use crate::error::ApiError;
+use models::key::PublicKey;
+use models::requests::key_request::{KeyRequest, KeyResponse};
+use reqwest::Url;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+struct CachedKey {
+    key: PublicKey,
+    fetched: Instant,
+}
+
+/// Local cache of distributor public keys fetched from the central server
+pub struct KeyCache {
+    client: reqwest::Client,
+    central_server_addr: Url,
+    ttl: Duration,
+    max_stale: Duration,
+    keys: RwLock<HashMap<u32, CachedKey>>,
+}
+
+impl KeyCache {
+    pub fn new(central_server_addr: Url, ttl: Duration, max_stale: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            client: reqwest::Client::new(),
+            central_server_addr,
+            ttl,
+            max_stale,
+            keys: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Replace the cache with the central server's whole key registry
+    pub async fn sync(&self) -> Result<(), ApiError> {
+        let url = self.central_server_addr.join("api/keys").unwrap();
+        let res: KeyResponse = self.client.get(url).send().await?.json().await?;
+
+        let now = Instant::now();
+        let keys = res
+            .keys
+            .into_iter()
+            .filter(|(_, key)| !key.revoked)
+            .map(|(id, key)| (id, CachedKey { key, fetched: now }))
+            .collect();
+
+        *self.keys.write().unwrap() = keys;
+
+        Ok(())
+    }
+
+    /// Re-sync the registry on an interval so revoked keys are dropped from the cache
+    pub async fn run(self: Arc<Self>, interval: Duration) {
+        loop {
+            if let Err(e) = self.sync().await {
+                println!("Failed to sync keys with the central server: {}", e);
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    pub fn revoke(&self, key_id: u32) {
+        self.keys.write().unwrap().remove(&key_id);
+    }
+
+    /// Get the requested keys, refreshing expired entries from the central server.
+    ///
+    /// If the central server can't be reached, cached keys younger than `max_stale` are still
+    /// returned. Keys that can't be found are left out of the result.
+    pub async fn get_keys(&self, key_ids: &[u32]) -> HashMap<u32, PublicKey> {
+        let expired: Vec<u32> = {
+            let keys = self.keys.read().unwrap();
+            key_ids
+                .iter()
+                .filter(|id| match keys.get(id) {
+                    Some(cached) => cached.fetched.elapsed() > self.ttl,
+                    None => true,
+                })
+                .cloned()
+                .collect()
+        };
+
+        if !expired.is_empty() {
+            match self.fetch_keys(expired.clone()).await {
+                Ok(res) => {
+                    let now = Instant::now();
+                    let mut keys = self.keys.write().unwrap();
+                    for key_id in expired {
+                        // Keys missing from the response are unknown or revoked
+                        match res.keys.get(&key_id) {
+                            Some(key) => {
+                                keys.insert(
+                                    key_id,
+                                    CachedKey {
+                                        key: key.clone(),
+                                        fetched: now,
+                                    },
+                                );
+                            }
+                            None => {
+                                keys.remove(&key_id);
+                            }
+                        }
+                    }
+                }
+                Err(e) => println!("Failed to refresh keys from the central server: {}", e),
+            }
+        }
+
+        let keys = self.keys.read().unwrap();
+        key_ids
+            .iter()
+            .filter_map(|id| keys.get(id))
+            .filter(|cached| cached.fetched.elapsed() <= self.max_stale)
+            .map(|cached| (cached.key.id, cached.key.clone()))
+            .collect()
+    }
+
+    async fn fetch_keys(&self, key_ids: Vec<u32>) -> Result<KeyResponse, ApiError> {
+        let url = self.central_server_addr.join("api/request_keys").unwrap();
+        let key_request = KeyRequest { key_ids };
+
+        Ok(self
+            .client
+            .get(url)
+            .json(&key_request)
+            .send()
+            .await?
+            .json()
+            .await?)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::distributor_server::key_cache::KeyCache;
+    use models::key::PublicKey;
+    use models::requests::key_request::{KeyRequest, KeyResponse};
+    use models::test_util::public_key;
+    use openssl::pkey::PKey;
+    use reqwest::Url;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use warp::http::StatusCode;
+    use warp::{Filter, Reply};
+
+    /// The central server's key registry, `None` while it is down
+    pub type Registry = Arc<Mutex<Option<HashMap<u32, PublicKey>>>>;
+
+    pub fn mock_central(registry: Registry) -> Url {
+        let reply =
+            |registry: &Registry, key_ids: Option<Vec<u32>>| match &*registry.lock().unwrap() {
+                Some(keys) => warp::reply::json(&KeyResponse {
+                    keys: keys
+                        .values()
+                        .filter(|pk| match &key_ids {
+                            Some(key_ids) => key_ids.contains(&pk.id) && !pk.revoked,
+                            None => true,
+                        })
+                        .map(|pk| (pk.id, pk.clone()))
+                        .collect(),
+                })
+                .into_response(),
+                None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+            };
+
+        let all = registry.clone();
+        let keys = warp::get()
+            .and(warp::path!("api" / "keys"))
+            .map(move || reply(&all, None));
+        let request_keys = warp::get()
+            .and(warp::path!("api" / "request_keys"))
+            .and(warp::body::json())
+            .map(move |request: KeyRequest| reply(&registry, Some(request.key_ids)));
+
+        let (addr, server) = warp::serve(keys.or(request_keys)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        Url::parse(&format!("http://{}/", addr)).unwrap()
+    }
+
+    fn key(id: u32) -> PublicKey {
+        public_key(id, &PKey::generate_ed25519().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_key_cache() {
+        let mut revoked = key(1);
+        revoked.revoked = true;
+        let registry: Registry = Arc::new(Mutex::new(Some(
+            vec![(0, key(0)), (1, revoked)].into_iter().collect(),
+        )));
+
+        // Every lookup refreshes the requested keys
+        let central = mock_central(registry.clone());
+        let cache = KeyCache::new(central, Duration::ZERO, Duration::from_secs(3600));
+
+        cache.sync().await.unwrap();
+        let keys = cache.get_keys(&[0, 1, 2]).await;
+        assert_eq!(keys.len(), 1);
+        assert!(keys.contains_key(&0));
+
+        // Keys revoked at the central server drop out on the next refresh
+        registry.lock().unwrap().as_mut().unwrap().insert(2, key(2));
+        registry
+            .lock()
+            .unwrap()
+            .as_mut()
+            .unwrap()
+            .get_mut(&0)
+            .unwrap()
+            .revoked = true;
+        let keys = cache.get_keys(&[0, 2]).await;
+        assert_eq!(keys.keys().collect::<Vec<_>>(), vec![&2]);
+
+        // Cached keys outlive an outage of the central server until they are too stale
+        *registry.lock().unwrap() = None;
+        assert!(cache.get_keys(&[2]).await.contains_key(&2));
+
+        cache.revoke(2);
+        assert!(cache.get_keys(&[2]).await.is_empty());
+
+        let stale = KeyCache::new(
+            cache.central_server_addr.clone(),
+            Duration::ZERO,
+            Duration::ZERO,
+        );
+        *registry.lock().unwrap() = Some(vec![(3, key(3))].into_iter().collect());
+        stale.sync().await.unwrap();
+        *registry.lock().unwrap() = None;
+        assert!(stale.get_keys(&[3]).await.is_empty());
+    }
+}
@@ -1,40 +1,180 @@
+mod identity;
+pub mod inventory;
+pub mod key_cache;
+mod readers;
+mod record_queue;
+mod tag_image;
+mod verify;
+
 use crate::args::{Args, DistributorServerArgs};
+use crate::config::identity_config::{IdentityConfig, IdentitySettings};
+use crate::database;
 use crate::error::ApiError;
-use models::requests::key_request::{KeyRequest, KeyResponse};
+use identity::{Identities, Identity, IdentityError};
+use inventory::Inventory;
+use models::aging::{AgingReport, HopMeasurement};
+use models::checkpoint::CHECKPOINT_KEY_ID;
+use models::error::{InspectionError, SignerError};
+use models::key::PublicKey;
+use models::reader::Reader;
+use models::requests::aging::{AgingCheckRequest, AgingCheckResponse};
+use models::requests::checkpoint::{CheckpointRequest, CheckpointResponse};
+use models::requests::inventory::InventoryQuery;
+use models::requests::reader::{
+    RegisterReaderRequest, RegisterReaderResponse, RevokeReaderResponse,
+};
+use models::requests::revoke_key::{RevokeKeyRequest, RevokeKeyResponse};
+use models::requests::sign::SignRequest;
 use models::requests::update_blockchain::UpdateBlockChainRequest;
-use models::requests::update_record::{UpdateRecordRequest, UpdateRecordResponse};
+use models::requests::update_record::UpdateRecordRequest;
+use models::requests::verify::VerifyTagRequest;
 use models::rfid::{RfidBuilder, RfidData};
-use models::utility::open_private_key;
-use openssl::pkey::Private;
-use openssl::rsa::Rsa;
+use models::BlockChainEntry;
+use readers::ReaderRegistry;
 use reqwest::Url;
+use std::collections::HashMap;
 use std::net::Ipv4Addr;
 use std::str::FromStr;
-use warp::Filter;
+use std::sync::Arc;
+use std::time::Duration;
+use tag_image::TagFormat;
+use warp::http::StatusCode;
+use warp::{Filter, Reply};
+
+/// How long a tag waits on the central server's aging check before we sign without it
+const AGING_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Check an incoming tag before we sign it, the tag must be intact and signed over to us
+fn inspect_request(
+    request: &UpdateBlockChainRequest,
+    keys: &HashMap<u32, PublicKey>,
+    key_id: u32,
+) -> Result<(), InspectionError> {
+    let our_key = keys
+        .get(&key_id)
+        .ok_or(InspectionError::UnknownDistributor(key_id))?;
+
+    request.rfid_data.inspect(keys, our_key)
+}
+
+async fn request_checkpoint(
+    client: &reqwest::Client,
+    central_server_addr: &Url,
+    req: &CheckpointRequest,
+) -> Result<CheckpointResponse, ApiError> {
+    let url = central_server_addr.join("api/checkpoint").unwrap();
+
+    Ok(client.post(url).json(req).send().await?.json().await?)
+}
+
+async fn request_aging_check(
+    client: &reqwest::Client,
+    central_server_addr: &Url,
+    req: &AgingCheckRequest,
+) -> Result<AgingCheckResponse, ApiError> {
+    let url = central_server_addr.join("api/aging/check").unwrap();
+
+    Ok(client
+        .post(url)
+        .timeout(AGING_CHECK_TIMEOUT)
+        .json(req)
+        .send()
+        .await?
+        .json()
+        .await?)
+}
 
-async fn update_record(
-    client: reqwest::Client,
-    central_server_addr: Url,
-    dist_id: u32,
-    next_dist_id: u32,
+/// Ask the central server whether our measurement of a chip says it was recycled, before we sign.
+///
+/// There is no report for a chip whose enrollment the central server has not recorded yet.
+async fn check_aging(
+    identity: &Identity,
+    rfid_data: &RfidData,
+    measurement: HopMeasurement,
+) -> Result<Option<AgingReport>, String> {
+    let request = AgingCheckRequest {
+        dist_id: identity.key_id,
+        chip_id: rfid_data.chip_data.chip_id,
+        measurement,
+    };
+
+    let response = request_aging_check(&identity.client, &identity.central_server_addr, &request)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.success {
+        Ok(response.report)
+    } else {
+        Err(response.error.unwrap_or_default())
+    }
+}
+
+/// Swap a tag we hold for one compacted by the central server, so our entry fits on the tag
+async fn compact_tag(
+    identity: &Identity,
     rfid_data: RfidData,
-    private_key: Rsa<Private>,
-) -> Result<UpdateRecordResponse, ApiError> {
-    let req = UpdateRecordRequest::new(dist_id, next_dist_id, rfid_data, &private_key);
-    let url = central_server_addr.join("api/update_record").unwrap();
+    keys: &HashMap<u32, PublicKey>,
+) -> Result<RfidData, String> {
+    let signer = identity
+        .signer(SignRequest::Checkpoint {
+            rfid_data: rfid_data.clone(),
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+    let request = CheckpointRequest::new(identity.key_id, rfid_data, signer.as_ref())
+        .map_err(|e| e.to_string())?;
+
+    let response = request_checkpoint(&identity.client, &identity.central_server_addr, &request)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let compacted = match response.rfid_data {
+        Some(rfid_data) if response.success => rfid_data,
+        _ => return Err(response.error.unwrap_or_default()),
+    };
+
+    let holder_key = keys
+        .get(&identity.key_id)
+        .ok_or_else(|| format!("No valid public key for distributor {}", identity.key_id))?;
+    compacted
+        .inspect(keys, holder_key)
+        .map_err(|e| format!("Invalid checkpoint: {}", e))?;
 
-    Ok(client.post(url).json(&req).send().await?.json().await?)
+    Ok(compacted)
+}
+
+fn identity_error_reply(e: IdentityError) -> warp::reply::Response {
+    let status = match e {
+        IdentityError::UnknownIdentity(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        IdentityError::NotPermitted { .. } => StatusCode::FORBIDDEN,
+    };
+
+    tag_image::error_reply(status, e.to_string())
 }
 
 async fn update_blockchain(
+    reader: Reader,
     request: UpdateBlockChainRequest,
-    central_server_addr: Url,
-    key_id: u32,
-    private_key: Rsa<Private>,
-) -> Result<impl warp::Reply, warp::Rejection> {
-    let url = central_server_addr.join("api/request_keys").unwrap();
+    identities: Arc<Identities>,
+    inventory: Arc<Inventory>,
+    accept: Option<String>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let format = match TagFormat::from_accept(accept) {
+        Some(format) => format,
+        None => {
+            return Ok(tag_image::error_reply(
+                StatusCode::NOT_ACCEPTABLE,
+                "Tags are served as application/json, application/octet-stream or text/plain"
+                    .to_string(),
+            ))
+        }
+    };
 
-    let client = reqwest::Client::new();
+    let identity = match identities.select(&reader, request.dist_id) {
+        Ok(identity) => identity,
+        Err(e) => return Ok(identity_error_reply(e)),
+    };
+    let key_id = identity.key_id;
 
     let mut pk_ids: Vec<u32> = request
         .rfid_data
@@ -45,81 +185,547 @@ async fn update_blockchain(
 
     pk_ids.push(request.next_distributor);
     pk_ids.push(key_id);
+    if request.compact {
+        pk_ids.push(CHECKPOINT_KEY_ID);
+    }
 
-    let key_request = KeyRequest { key_ids: pk_ids };
+    let keys = identity.key_cache.get_keys(&pk_ids).await;
 
-    let res: KeyResponse = client
-        .get(url)
-        .json(&key_request)
-        .send()
-        .await
-        .map_err(|e| warp::reject::custom(ApiError::from(e)))?
-        .json()
-        .await
-        .map_err(|e| warp::reject::custom(ApiError::from(e)))?;
+    if let Err(e) = inspect_request(&request, &keys, key_id) {
+        return Ok(tag_image::error_reply(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("Rejected tag: {}", e),
+        ));
+    }
+
+    // Only tags that passed inspection count as received stock
+    inventory.receive(key_id, &request.rfid_data);
+    let next_dist_id = request.next_distributor;
+    let measurement = request.measurement;
+
+    if let Some(measurement) = measurement {
+        if !measurement.is_valid() {
+            return Ok(tag_image::error_reply(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Invalid measurement".to_string(),
+            ));
+        }
+
+        // Best effort, the central server still flags the chip when it records our hop
+        match check_aging(&identity, &request.rfid_data, measurement).await {
+            Ok(Some(report)) if report.recycled => {
+                return Ok(tag_image::error_reply(
+                    StatusCode::CONFLICT,
+                    format!(
+                        "Chip {} has aged beyond its baseline and may be recycled",
+                        report.chip_id
+                    ),
+                ))
+            }
+            Ok(_) => {}
+            Err(e) => println!(
+                "Skipped the aging check of chip {}: {}",
+                request.rfid_data.chip_data.chip_id, e
+            ),
+        }
+    }
+
+    let rfid_data = if request.compact {
+        match compact_tag(&identity, request.rfid_data, &keys).await {
+            Ok(rfid_data) => rfid_data,
+            Err(e) => {
+                return Ok(tag_image::error_reply(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    format!("Failed to compact tag: {}", e),
+                ))
+            }
+        }
+    } else {
+        request.rfid_data
+    };
 
-    let rfid_builder = RfidBuilder::from(request.rfid_data);
+    let signed = identity
+        .signer(SignRequest::Entry {
+            rfid_data: rfid_data.clone(),
+            next_dist_id,
+        })
+        .await
+        .and_then(|signer| {
+            RfidBuilder::from(rfid_data).add_entry(signer.as_ref(), key_id, next_dist_id, &keys)
+        });
 
-    let rfid_data = rfid_builder
-        .add_entry(
-            private_key.private_key_to_pem().unwrap(),
-            key_id,
-            request.next_distributor,
-            &res.keys,
-        )
-        .build();
+    let rfid_data = match signed {
+        Ok(rfid_builder) => rfid_builder.build(),
+        Err(e @ SignerError::UnknownKey(_)) => {
+            return Ok(tag_image::error_reply(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Rejected tag: {}", e),
+            ))
+        }
+        Err(e) => {
+            return Ok(tag_image::error_reply(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to sign tag: {}", e),
+            ))
+        }
+    };
 
     Ok(
-        match rfid_data.validate_chain(
-            &res.keys,
-            res.keys.get(&request.next_distributor).unwrap().clone(),
-        ) {
+        match rfid_data.validate_chain(&keys, keys.get(&next_dist_id).unwrap().clone()) {
             Ok(_) => {
-                update_record(
-                    client,
-                    central_server_addr,
-                    key_id,
-                    request.next_distributor,
-                    rfid_data.clone(),
-                    private_key,
-                )
-                .await?;
-                serde_json::to_string(&rfid_data).unwrap()
+                let req = identity
+                    .signer(SignRequest::UpdateRecord {
+                        rfid_data: rfid_data.clone(),
+                        next_dist_id,
+                        measurement,
+                    })
+                    .await
+                    .and_then(|signer| {
+                        UpdateRecordRequest::new(
+                            key_id,
+                            next_dist_id,
+                            rfid_data.clone(),
+                            measurement,
+                            signer.as_ref(),
+                        )
+                    });
+                let req = match req {
+                    Ok(req) => req,
+                    Err(e) => {
+                        return Ok(tag_image::error_reply(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            format!("Failed to sign central server update: {}", e),
+                        ))
+                    }
+                };
+                identity.record_queue.submit(req).await;
+                println!(
+                    "Signed chip {} as distributor {} for reader {} ({})",
+                    rfid_data.chip_data.chip_id, key_id, reader.name, reader.id
+                );
+                inventory.ship(&rfid_data, key_id, next_dist_id, reader.id);
+                tag_image::tag_reply(rfid_data, format)
             }
-            Err(e) => format!("Failed to validate at position {}", e),
+            Err(e) => tag_image::error_reply(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to validate at position {}", e),
+            ),
         },
     )
 }
 
 fn update_blockchain_filter(
-    central_server_addr: Url,
-    key_id: u32,
-    key: Rsa<Private>,
+    identities: Arc<Identities>,
+    inventory: Arc<Inventory>,
+    readers: Arc<ReaderRegistry>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::post()
         .and(warp::path("api"))
         .and(warp::path("update_blockchain"))
+        .and(readers::with_reader(readers))
         .and(warp::body::content_length_limit(1024 * 16))
         .and(warp::body::json())
-        .and(warp::any().map(move || central_server_addr.clone()))
-        .and(warp::any().map(move || key_id))
-        .and(warp::any().map(move || key.clone()))
+        .and(warp::any().map(move || identities.clone()))
+        .and(warp::any().map(move || inventory.clone()))
+        .and(warp::header::optional::<String>("accept"))
         .and_then(update_blockchain)
 }
 
+fn inventory_filter(
+    identities: Arc<Identities>,
+    inventory: Arc<Inventory>,
+    readers: Arc<ReaderRegistry>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    // Readers see the inventory of the identity they are allowed to sign as
+    let base = warp::get()
+        .and(warp::path("api"))
+        .and(warp::path("inventory"))
+        .and(readers::with_reader(readers))
+        .and(warp::query::<InventoryQuery>())
+        .and(warp::any().map(move || identities.clone()))
+        .map(
+            |reader: Reader, query: InventoryQuery, identities: Arc<Identities>| {
+                identities
+                    .select(&reader, query.dist_id)
+                    .map(|identity| identity.key_id)
+            },
+        );
+
+    let inv = inventory.clone();
+    let stock = base
+        .clone()
+        .and(warp::path::end())
+        .and(warp::any().map(move || inv.clone()))
+        .map(
+            |dist_id: Result<u32, IdentityError>, inventory: Arc<Inventory>| match dist_id {
+                Ok(dist_id) => warp::reply::json(&inventory.stock(dist_id)).into_response(),
+                Err(e) => identity_error_reply(e),
+            },
+        );
+
+    let inv = inventory.clone();
+    let reconcile = base
+        .clone()
+        .and(warp::path("reconcile"))
+        .and(warp::path::end())
+        .and(warp::any().map(move || inv.clone()))
+        .map(
+            |dist_id: Result<u32, IdentityError>, inventory: Arc<Inventory>| match dist_id {
+                Ok(dist_id) => warp::reply::json(&inventory.reconcile(dist_id)).into_response(),
+                Err(e) => identity_error_reply(e),
+            },
+        );
+
+    let lookup = base
+        .and(warp::path::param::<u128>())
+        .and(warp::path::end())
+        .and(warp::any().map(move || inventory.clone()))
+        .map(
+            |dist_id: Result<u32, IdentityError>, chip_id: u128, inventory: Arc<Inventory>| {
+                let dist_id = match dist_id {
+                    Ok(dist_id) => dist_id,
+                    Err(e) => return identity_error_reply(e),
+                };
+
+                match inventory.lookup(dist_id, chip_id) {
+                    Some(item) => warp::reply::json(&item).into_response(),
+                    None => warp::reply::with_status(
+                        warp::reply::json(&format!("Chip {} is not in the inventory", chip_id)),
+                        warp::http::StatusCode::NOT_FOUND,
+                    )
+                    .into_response(),
+                }
+            },
+        );
+
+    stock.or(reconcile).or(lookup)
+}
+
+async fn verify_tag(
+    reader: Reader,
+    request: VerifyTagRequest,
+    identities: Arc<Identities>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let identity = match identities.select(&reader, request.dist_id) {
+        Ok(identity) => identity,
+        Err(e) => return Ok(identity_error_reply(e)),
+    };
+    let key_id = identity.key_id;
+
+    let mut pk_ids: Vec<u32> = request
+        .rfid_data
+        .entries
+        .iter()
+        .map(|entry| entry.pub_key)
+        .collect();
+
+    pk_ids.push(key_id);
+
+    let record = verify::fetch_central_record(
+        &identity.client,
+        &identity.central_server_addr,
+        request.rfid_data.chip_data.chip_id,
+    )
+    .await;
+    pk_ids.append(&mut verify::record_key_ids(&record));
+
+    let keys = identity.key_cache.get_keys(&pk_ids).await;
+
+    Ok(warp::reply::json(&verify::verify_tag(
+        &request.rfid_data,
+        &keys,
+        key_id,
+        record,
+    ))
+    .into_response())
+}
+
+fn verify_tag_filter(
+    identities: Arc<Identities>,
+    readers: Arc<ReaderRegistry>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::post()
+        .and(warp::path("api"))
+        .and(warp::path("verify"))
+        .and(readers::with_reader(readers))
+        .and(warp::body::content_length_limit(1024 * 16))
+        .and(warp::body::json())
+        .and(warp::any().map(move || identities.clone()))
+        .and_then(verify_tag)
+}
+
+fn record_queue_filter(
+    identities: Arc<Identities>,
+    readers: Arc<ReaderRegistry>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let base = warp::get()
+        .and(warp::path("api"))
+        .and(warp::path("record_queue"))
+        .and(readers::require_reader(readers));
+
+    let queues = identities.clone();
+    let status = base
+        .clone()
+        .and(warp::path::end())
+        .and(warp::any().map(move || queues.clone()))
+        .map(|identities: Arc<Identities>| {
+            let status: Vec<_> = identities
+                .iter()
+                .map(|identity| identity.record_queue.status())
+                .collect();
+            warp::reply::json(&status)
+        });
+
+    let rejected = base
+        .and(warp::path("rejected"))
+        .and(warp::path::end())
+        .and(warp::any().map(move || identities.clone()))
+        .map(|identities: Arc<Identities>| {
+            let rejected: Vec<_> = identities
+                .iter()
+                .flat_map(|identity| identity.record_queue.rejected())
+                .collect();
+            warp::reply::json(&rejected)
+        });
+
+    status.or(rejected)
+}
+
+fn readers_filter(
+    readers: Arc<ReaderRegistry>,
+    identities: Arc<Identities>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let base = warp::path("api")
+        .and(warp::path("readers"))
+        .and(readers::with_admin(readers.clone()));
+
+    let registry = readers.clone();
+    let list = warp::get()
+        .and(base.clone())
+        .and(warp::path::end())
+        .and(warp::any().map(move || registry.clone()))
+        .map(|readers: Arc<ReaderRegistry>| warp::reply::json(&readers.list()));
+
+    let registry = readers.clone();
+    let register = warp::post()
+        .and(base.clone())
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(warp::any().map(move || registry.clone()))
+        .and(warp::any().map(move || identities.clone()))
+        .map(
+            |request: RegisterReaderRequest,
+             readers: Arc<ReaderRegistry>,
+             identities: Arc<Identities>| {
+                if let Some(dist_id) = request.identity {
+                    if identities.get(dist_id).is_none() {
+                        return identity_error_reply(IdentityError::UnknownIdentity(dist_id));
+                    }
+                }
+
+                let (reader, token) = readers.register(request.name, request.identity);
+                warp::reply::json(&RegisterReaderResponse {
+                    reader_id: reader.id,
+                    token,
+                })
+                .into_response()
+            },
+        );
+
+    let revoke = warp::post()
+        .and(base)
+        .and(warp::path::param::<u64>())
+        .and(warp::path("revoke"))
+        .and(warp::path::end())
+        .and(warp::any().map(move || readers.clone()))
+        .map(|reader_id: u64, readers: Arc<ReaderRegistry>| {
+            warp::reply::json(&RevokeReaderResponse {
+                success: readers.revoke(reader_id),
+            })
+        });
+
+    list.or(register).or(revoke)
+}
+
+fn revoke_key_filter(
+    identities: Arc<Identities>,
+    readers: Arc<ReaderRegistry>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::post()
+        .and(warp::path("api"))
+        .and(warp::path("revoke_key"))
+        .and(readers::with_admin(readers))
+        .and(warp::body::json())
+        .and(warp::any().map(move || identities.clone()))
+        .and_then(revoke_key)
+}
+
+/// Drop a revoked key from the cache before the next poll. Only the operator can post it, and the
+/// request must be signed by the revoked key.
+async fn revoke_key(
+    revoke_req: RevokeKeyRequest,
+    identities: Arc<Identities>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let bytes: Vec<u8> = revoke_req.clone().into();
+    let mut success = false;
+
+    for identity in identities.iter() {
+        let keys = identity.key_cache.get_keys(&[revoke_req.key_id]).await;
+
+        if let Some(pk) = keys.get(&revoke_req.key_id) {
+            if revoke_req.verify_signature(&bytes, pk) {
+                success = true;
+                break;
+            }
+        }
+    }
+
+    if success {
+        for identity in identities.iter() {
+            identity.key_cache.revoke(revoke_req.key_id);
+        }
+    }
+
+    Ok(warp::reply::json(&RevokeKeyResponse { success }))
+}
+
+/// Every endpoint of the distributor server, reader management and key revocation need the admin
+/// token and everything else a reader token
+fn routes(
+    identities: Arc<Identities>,
+    inventory: Arc<Inventory>,
+    readers: Arc<ReaderRegistry>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    update_blockchain_filter(identities.clone(), inventory.clone(), readers.clone())
+        .or(verify_tag_filter(identities.clone(), readers.clone()))
+        .or(record_queue_filter(identities.clone(), readers.clone()))
+        .or(inventory_filter(
+            identities.clone(),
+            inventory,
+            readers.clone(),
+        ))
+        .or(readers_filter(readers.clone(), identities.clone()))
+        .or(revoke_key_filter(identities, readers))
+        .recover(readers::handle_rejection)
+}
+
+/// Identities from the identity config, or the single identity given on the command line
+fn identity_settings(dist_args: &DistributorServerArgs) -> Result<Vec<IdentitySettings>, ApiError> {
+    match &dist_args.identities_path {
+        Some(path) => Ok(IdentityConfig::new(path)?.identities),
+        None => Ok(vec![IdentitySettings {
+            key_id: dist_args.key_id.unwrap(),
+            private_key: dist_args.private_key.clone().unwrap(),
+            central_server_addr: dist_args.central_server_addr.clone().unwrap(),
+        }]),
+    }
+}
+
 pub async fn distributor_server(
     args: &Args,
     dist_args: &DistributorServerArgs,
 ) -> Result<(), ApiError> {
-    let private_key = open_private_key(dist_args.private_key.clone());
+    let db = database::Database::new(&dist_args.database_path);
+
+    let mut identities = Vec::new();
+    for settings in identity_settings(dist_args)? {
+        identities.push(Identity::start(&settings, db.clone(), dist_args).await?);
+    }
+
+    if identities.is_empty() {
+        println!("No distributor identities configured");
+        return Ok(());
+    }
+
+    let identities = Identities::new(identities);
+    let inventory = Inventory::new(db.clone());
+    let readers = ReaderRegistry::new(db, dist_args.admin_token.clone());
+
     println!("Starting dist server...");
-    warp::serve(update_blockchain_filter(
-        Url::from_str(dist_args.central_server_addr.as_str()).unwrap(),
-        dist_args.key_id,
-        private_key,
-    ))
-    .run((Ipv4Addr::from_str(&args.address).unwrap(), args.port))
-    .await;
+    warp::serve(routes(identities, inventory, readers))
+        .run((Ipv4Addr::from_str(&args.address).unwrap(), args.port))
+        .await;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::database::Database;
+    use crate::distributor_server::identity::tests::identity;
+    use crate::distributor_server::identity::Identities;
+    use crate::distributor_server::inventory::Inventory;
+    use crate::distributor_server::readers::ReaderRegistry;
+    use crate::distributor_server::routes;
+    use models::requests::revoke_key::RevokeKeyResponse;
+    use warp::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_reader_authentication() {
+        let db = Database::new(&std::env::temp_dir().join("rfsc_test_routes_db"));
+        db.clear();
+        let readers = ReaderRegistry::new(db.clone(), Some("admin".to_string()));
+        let (_, token) = readers.register("dock 1".to_string(), None);
+        let authorization = format!("Bearer {}", token);
+        let identities = Identities::new(vec![identity(0, db.clone()), identity(1, db.clone())]);
+        let routes = routes(identities, Inventory::new(db.clone()), readers);
+
+        // The reader is checked before the body is parsed
+        for (method, path) in [
+            ("POST", "/api/update_blockchain"),
+            ("POST", "/api/verify"),
+            ("GET", "/api/inventory"),
+            ("GET", "/api/inventory/reconcile"),
+            ("GET", "/api/inventory/42"),
+            ("GET", "/api/record_queue"),
+            ("GET", "/api/record_queue/rejected"),
+            ("POST", "/api/revoke_key"),
+        ] {
+            let res = warp::test::request()
+                .method(method)
+                .path(path)
+                .body("not json")
+                .reply(&routes)
+                .await;
+            assert_eq!(
+                res.status(),
+                StatusCode::UNAUTHORIZED,
+                "{} {}",
+                method,
+                path
+            );
+        }
+
+        let res = warp::test::request()
+            .path("/api/inventory")
+            .header("authorization", &authorization)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // Unbound readers only see the default identity's inventory
+        let res = warp::test::request()
+            .path("/api/inventory/reconcile?dist_id=1")
+            .header("authorization", &authorization)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        // Readers can't drop keys from the cache
+        let revoke = |authorization: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/api/revoke_key")
+                .header("authorization", authorization)
+                .json(&serde_json::json!({ "key_id": 1, "signature": "" }))
+        };
+        let res = revoke(&authorization).reply(&routes).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = revoke("Bearer admin").reply(&routes).await;
+        let response: RevokeKeyResponse = serde_json::from_slice(res.body()).unwrap();
+        assert!(!response.success);
+
+        db.clear();
+    }
+}
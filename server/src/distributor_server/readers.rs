@@ -0,0 +1,187 @@
+use crate::database::Database;
+use crate::distributor_server::inventory::timestamp;
+use crate::distributor_server::tag_image;
+use crate::error::ApiError;
+use models::reader::Reader;
+use openssl::hash::{hash, MessageDigest};
+use std::sync::Arc;
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+const TOKEN_SECRET_SIZE: usize = 32;
+
+fn hash_secret(secret: &[u8]) -> Vec<u8> {
+    hash(MessageDigest::sha3_256(), secret).unwrap().to_vec()
+}
+
+fn bearer_token(authorization: &Option<String>) -> Option<&str> {
+    authorization
+        .as_ref()
+        .and_then(|authorization| authorization.strip_prefix("Bearer "))
+        .map(|token| token.trim())
+}
+
+/// Registry of readers allowed to request signatures from the distributor server.
+///
+/// Reader tokens have the form `<reader id>.<secret>`, only a hash of the secret is stored.
+pub struct ReaderRegistry {
+    db: Arc<Database>,
+    admin_token: Option<String>,
+}
+
+impl ReaderRegistry {
+    pub fn new(db: Arc<Database>, admin_token: Option<String>) -> Arc<Self> {
+        Arc::new(Self { db, admin_token })
+    }
+
+    /// Register a new reader, returns the reader and its API token
+    pub fn register(&self, name: String, identity: Option<u32>) -> (Reader, String) {
+        let mut secret = [0u8; TOKEN_SECRET_SIZE];
+        openssl::rand::rand_bytes(&mut secret).unwrap();
+
+        let reader = Reader {
+            id: self.db.generate_id(),
+            name,
+            token_hash: hash_secret(&secret),
+            revoked: false,
+            registered_at: timestamp(),
+            identity,
+        };
+
+        self.db.insert::<Reader>(reader.clone());
+
+        let token = format!(
+            "{}.{}",
+            reader.id,
+            base64::encode_config(secret, base64::URL_SAFE_NO_PAD)
+        );
+
+        (reader, token)
+    }
+
+    pub fn revoke(&self, reader_id: u64) -> bool {
+        match self.db.fetch::<Reader>(reader_id) {
+            Some(mut reader) => {
+                reader.revoked = true;
+                self.db.insert::<Reader>(reader);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn list(&self) -> Vec<Reader> {
+        self.db.fetch_all::<Reader>()
+    }
+
+    /// Find the reader a bearer token belongs to, revoked readers are never returned
+    pub fn authenticate(&self, authorization: &Option<String>) -> Option<Reader> {
+        let mut token = bearer_token(authorization)?.splitn(2, '.');
+
+        let reader_id = token.next()?.parse::<u64>().ok()?;
+        let secret = base64::decode_config(token.next()?, base64::URL_SAFE_NO_PAD).ok()?;
+
+        let reader = self.db.fetch::<Reader>(reader_id)?;
+        let token_hash = hash_secret(&secret);
+
+        if !reader.revoked && openssl::memcmp::eq(&token_hash, &reader.token_hash) {
+            Some(reader)
+        } else {
+            None
+        }
+    }
+
+    pub fn authorize_admin(&self, authorization: &Option<String>) -> bool {
+        match (&self.admin_token, bearer_token(authorization)) {
+            (Some(admin_token), Some(token)) => {
+                admin_token.len() == token.len()
+                    && openssl::memcmp::eq(admin_token.as_bytes(), token.as_bytes())
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Require a registered reader's token, extracts the authenticated reader
+pub fn with_reader(
+    registry: Arc<ReaderRegistry>,
+) -> impl Filter<Extract = (Reader,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and(warp::any().map(move || registry.clone()))
+        .and_then(
+            |authorization: Option<String>, registry: Arc<ReaderRegistry>| async move {
+                registry
+                    .authenticate(&authorization)
+                    .ok_or_else(|| warp::reject::custom(ApiError::Unauthorized))
+            },
+        )
+}
+
+/// Require a registered reader's token, for endpoints that don't need to know which reader it is
+pub fn require_reader(
+    registry: Arc<ReaderRegistry>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    with_reader(registry).map(|_: Reader| ()).untuple_one()
+}
+
+/// Require the distributor server's admin token
+pub fn with_admin(
+    registry: Arc<ReaderRegistry>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and(warp::any().map(move || registry.clone()))
+        .and_then(
+            |authorization: Option<String>, registry: Arc<ReaderRegistry>| async move {
+                if registry.authorize_admin(&authorization) {
+                    Ok(())
+                } else {
+                    Err(warp::reject::custom(ApiError::Unauthorized))
+                }
+            },
+        )
+        .untuple_one()
+}
+
+/// Turn `ApiError` rejections into JSON error responses
+pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Rejection> {
+    match err.find::<ApiError>() {
+        Some(ApiError::Unauthorized) => Ok(tag_image::error_reply(
+            StatusCode::UNAUTHORIZED,
+            ApiError::Unauthorized.to_string(),
+        )),
+        Some(e) => Ok(tag_image::error_reply(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            e.to_string(),
+        )),
+        None => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::Database;
+    use crate::distributor_server::readers::ReaderRegistry;
+
+    #[test]
+    fn test_reader_registry() {
+        let db = Database::new(&std::env::temp_dir().join("rfsc_test_readers_db"));
+        let registry = ReaderRegistry::new(db.clone(), Some("admin".to_string()));
+
+        let (reader, token) = registry.register("dock 1".to_string(), None);
+        let authorization = Some(format!("Bearer {}", token));
+
+        assert_eq!(registry.authenticate(&authorization).unwrap().id, reader.id);
+        assert!(registry
+            .authenticate(&Some(format!("Bearer {}.AAAA", reader.id)))
+            .is_none());
+        assert!(registry.authenticate(&None).is_none());
+
+        assert!(registry.authorize_admin(&Some("Bearer admin".to_string())));
+        assert!(!registry.authorize_admin(&authorization));
+
+        assert!(registry.revoke(reader.id));
+        assert!(registry.authenticate(&authorization).is_none());
+
+        db.clear();
+    }
+}
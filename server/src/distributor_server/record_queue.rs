@@ -0,0 +1,412 @@
+use crate::database::Database;
+use crate::error::ApiError;
+use byteorder::{BigEndian, WriteBytesExt};
+use models::requests::record_queue::RecordQueueStatus;
+use models::requests::update_record::{UpdateRecordRequest, UpdateRecordResponse};
+use models::DatabaseModel;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+/// Attempts the central server gets to take an update before it is set aside
+const MAX_ATTEMPTS: u32 = 10;
+
+/// Tree a queued update is stored in
+pub trait QueueTree {
+    const TREE: &'static str;
+}
+
+/// Signed locally but not yet accepted by the central server
+#[derive(Debug, Clone)]
+pub struct Pending;
+
+impl QueueTree for Pending {
+    const TREE: &'static str = "pending_record_updates";
+}
+
+/// Rejected by the central server or kept failing, kept for an operator to inspect
+#[derive(Debug, Clone)]
+pub struct Rejected;
+
+impl QueueTree for Rejected {
+    const TREE: &'static str = "rejected_record_updates";
+}
+
+/// Central server update in one of the queue's trees
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct QueuedRecordUpdate<S: QueueTree> {
+    pub id: u64,
+    pub request: UpdateRecordRequest,
+    pub attempts: u32,
+    /// Why the update was set aside
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip)]
+    tree: PhantomData<S>,
+}
+
+pub type PendingRecordUpdate = QueuedRecordUpdate<Pending>;
+pub type RejectedRecordUpdate = QueuedRecordUpdate<Rejected>;
+
+impl<S: QueueTree> DatabaseModel for QueuedRecordUpdate<S> {
+    type ID = u64;
+
+    fn id(&self) -> Self::ID {
+        self.id
+    }
+
+    fn set_id(&mut self, id: Self::ID) {
+        self.id = id
+    }
+
+    fn id_type_to_bytes(id: Self::ID) -> Vec<u8> {
+        // Big endian so the tree iterates in the order updates were queued
+        let mut bytes = Vec::new();
+        bytes.write_u64::<BigEndian>(id).unwrap();
+        bytes
+    }
+
+    fn tree() -> String {
+        S::TREE.to_string()
+    }
+}
+
+/// What became of an attempt to deliver an update
+enum Delivery {
+    Accepted,
+    /// The central server can't be reached, later updates won't get through either
+    Unreachable,
+    /// The central server failed on this update, it is retried until it runs out of attempts
+    Failed,
+}
+
+pub async fn send_update_record(
+    client: &reqwest::Client,
+    central_server_addr: &Url,
+    req: &UpdateRecordRequest,
+) -> Result<UpdateRecordResponse, ApiError> {
+    let url = central_server_addr.join("api/update_record").unwrap();
+
+    Ok(client
+        .post(url)
+        .json(req)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+/// Persistent queue of central server updates, flushed in the background.
+///
+/// Every identity hosted by the server has its own queue, they share a tree in the database and
+/// each only handles the updates signed by its identity. Only the flush task sends updates, so
+/// hops of a chip reach the central server in the order they were signed.
+pub struct RecordQueue {
+    dist_id: u32,
+    db: Arc<Database>,
+    client: reqwest::Client,
+    central_server_addr: Url,
+    notify: Notify,
+    depth: AtomicUsize,
+    failed_attempts: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+impl RecordQueue {
+    pub fn new(dist_id: u32, db: Arc<Database>, central_server_addr: Url) -> Arc<Self> {
+        let queue = Self {
+            dist_id,
+            db,
+            client: reqwest::Client::new(),
+            central_server_addr,
+            notify: Notify::new(),
+            depth: AtomicUsize::new(0),
+            failed_attempts: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+        };
+        queue.depth.store(queue.pending().len(), Ordering::Relaxed);
+
+        Arc::new(queue)
+    }
+
+    /// Queue an update for the central server and wake the flush task
+    pub async fn submit(&self, request: UpdateRecordRequest) {
+        self.db.insert::<PendingRecordUpdate>(PendingRecordUpdate {
+            id: self.db.generate_id(),
+            request,
+            attempts: 0,
+            error: None,
+            tree: PhantomData,
+        });
+        self.depth.fetch_add(1, Ordering::Relaxed);
+        self.notify.notify_one();
+    }
+
+    /// Send a single update, setting it aside if the central server rejects it or it runs out of
+    /// attempts
+    async fn deliver(&self, pending: &mut PendingRecordUpdate) -> Delivery {
+        let error =
+            match send_update_record(&self.client, &self.central_server_addr, &pending.request)
+                .await
+            {
+                Ok(res) if res.success => {
+                    Self::log_response(&pending.request, &res);
+                    self.remove(pending.id);
+                    return Delivery::Accepted;
+                }
+                Ok(_) => {
+                    println!(
+                        "Central server rejected record update for chip {}",
+                        pending.request.rfid_data.chip_data.chip_id
+                    );
+                    pending.attempts = MAX_ATTEMPTS;
+                    "Rejected by the central server".to_string()
+                }
+                Err(e) => {
+                    self.record_failure(&e);
+
+                    if let ApiError::ReqwestError(e) = &e {
+                        if e.is_connect() || e.is_timeout() {
+                            return Delivery::Unreachable;
+                        }
+                    }
+
+                    pending.attempts += 1;
+                    e.to_string()
+                }
+            };
+
+        if pending.attempts >= MAX_ATTEMPTS {
+            self.remove(pending.id);
+            self.db
+                .insert::<RejectedRecordUpdate>(RejectedRecordUpdate {
+                    id: pending.id,
+                    request: pending.request.clone(),
+                    attempts: pending.attempts,
+                    error: Some(error),
+                    tree: PhantomData,
+                });
+        }
+
+        Delivery::Failed
+    }
+
+    fn remove(&self, id: u64) {
+        self.db.remove::<PendingRecordUpdate>(id);
+        self.depth.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn pending(&self) -> Vec<PendingRecordUpdate> {
+        self.db
+            .fetch_all::<PendingRecordUpdate>()
+            .into_iter()
+            .filter(|pending| pending.request.dist_id == self.dist_id)
+            .collect()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth.load(Ordering::Relaxed)
+    }
+
+    /// Updates set aside for this identity
+    pub fn rejected(&self) -> Vec<RejectedRecordUpdate> {
+        self.db
+            .fetch_all::<RejectedRecordUpdate>()
+            .into_iter()
+            .filter(|rejected| rejected.request.dist_id == self.dist_id)
+            .collect()
+    }
+
+    pub fn status(&self) -> RecordQueueStatus {
+        RecordQueueStatus {
+            dist_id: self.dist_id,
+            depth: self.depth(),
+            rejected: self.rejected().len(),
+            failed_attempts: self.failed_attempts.load(Ordering::Relaxed),
+            last_error: self.last_error.lock().unwrap().clone(),
+        }
+    }
+
+    /// Try to send every queued update, returns true if the queue was emptied.
+    ///
+    /// An update the central server fails on is skipped so it doesn't hold up the ones after it.
+    async fn flush(&self) -> bool {
+        let mut emptied = true;
+
+        for mut pending in self.pending() {
+            match self.deliver(&mut pending).await {
+                Delivery::Accepted => (),
+                Delivery::Unreachable => return false,
+                Delivery::Failed => {
+                    if pending.attempts < MAX_ATTEMPTS {
+                        self.db.insert::<PendingRecordUpdate>(pending);
+                        emptied = false;
+                    }
+                }
+            }
+        }
+
+        emptied
+    }
+
+    /// Flush the queue whenever new updates arrive, backing off while the central server is down
+    pub async fn run(self: Arc<Self>) {
+        let mut delay = INITIAL_RETRY_DELAY;
+
+        loop {
+            if self.flush().await {
+                delay = INITIAL_RETRY_DELAY;
+                self.notify.notified().await;
+            } else {
+                tokio::time::sleep(delay).await;
+                delay = std::cmp::min(delay * 2, MAX_RETRY_DELAY);
+            }
+        }
+    }
+
+    fn record_failure(&self, e: &ApiError) {
+        self.failed_attempts.fetch_add(1, Ordering::Relaxed);
+        *self.last_error.lock().unwrap() = Some(e.to_string());
+    }
+
+    fn log_response(request: &UpdateRecordRequest, res: &UpdateRecordResponse) {
+        if res.recycled {
+            println!(
+                "Central server flagged chip {} as possibly recycled",
+                request.rfid_data.chip_data.chip_id
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::Database;
+    use crate::distributor_server::record_queue::{RecordQueue, MAX_ATTEMPTS};
+    use models::requests::update_record::{UpdateRecordRequest, UpdateRecordResponse};
+    use models::rfid::RfidBuilder;
+    use models::signer::file_key::FileKeySigner;
+    use openssl::pkey::PKey;
+    use reqwest::Url;
+    use std::net::TcpListener;
+    use warp::http::StatusCode;
+    use warp::{Filter, Reply};
+
+    /// Central server that fails on chip 1, rejects chip 2 and accepts any other chip
+    fn mock_central() -> Url {
+        let filter = warp::post()
+            .and(warp::path!("api" / "update_record"))
+            .and(warp::body::json())
+            .map(|request: UpdateRecordRequest| {
+                let success = match request.rfid_data.chip_data.chip_id {
+                    1 => {
+                        return warp::reply::with_status(
+                            "panicked",
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                        )
+                        .into_response()
+                    }
+                    2 => false,
+                    _ => true,
+                };
+
+                warp::reply::json(&UpdateRecordResponse {
+                    success,
+                    record: None,
+                    recycled: false,
+                    error: None,
+                })
+                .into_response()
+            });
+
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        Url::parse(&format!("http://{}/", addr)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_record_queue() {
+        let db = Database::new(&std::env::temp_dir().join("rfsc_test_record_queue_db"));
+        db.clear();
+
+        let signer = FileKeySigner::from_pkey(PKey::generate_ed25519().unwrap()).unwrap();
+        let request = |dist_id: u32, chip_id: u128| {
+            let rfid_data = RfidBuilder::default()
+                .chip_data(chip_id, 5.0, 5.0, 5.0, 5.0)
+                .build();
+            UpdateRecordRequest::new(dist_id, 1, rfid_data, None, &signer).unwrap()
+        };
+
+        // Nothing listens on a port that was just released
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let down = Url::parse(&format!("http://127.0.0.1:{}/", port)).unwrap();
+        let unreachable = RecordQueue::new(7, db.clone(), down);
+
+        unreachable.submit(request(7, 3)).await;
+        assert!(!unreachable.flush().await);
+        assert_eq!(unreachable.depth(), 1);
+        assert_eq!(unreachable.pending()[0].attempts, 0);
+        assert!(unreachable.rejected().is_empty());
+
+        let queue = RecordQueue::new(0, db.clone(), mock_central());
+        queue.submit(request(0, 1)).await;
+        queue.submit(request(0, 2)).await;
+        queue.submit(request(0, 3)).await;
+        assert_eq!(queue.depth(), 3);
+
+        // Updates are only sent by the flush task, in the order they were submitted
+        let order: Vec<u128> = queue
+            .pending()
+            .iter()
+            .map(|pending| pending.request.rfid_data.chip_data.chip_id)
+            .collect();
+        assert_eq!(order, vec![1, 2, 3]);
+
+        // The failing update doesn't hold up the ones queued after it
+        assert!(!queue.flush().await);
+        assert_eq!(queue.depth(), 1);
+        assert_eq!(queue.pending()[0].attempts, 1);
+
+        let rejected = queue.rejected();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].request.rfid_data.chip_data.chip_id, 2);
+
+        let mut flushes = 0;
+        while !queue.flush().await {
+            flushes += 1;
+        }
+        assert_eq!(flushes, MAX_ATTEMPTS - 2);
+        assert_eq!(queue.depth(), 0);
+
+        let status = queue.status();
+        assert_eq!(status.rejected, 2);
+        assert!(status.last_error.unwrap().contains("500"));
+
+        let failed = queue
+            .rejected()
+            .into_iter()
+            .find(|rejected| rejected.request.rfid_data.chip_data.chip_id == 1)
+            .unwrap();
+        assert_eq!(failed.attempts, MAX_ATTEMPTS);
+
+        // The other identity's queue is untouched, and the depth survives a restart
+        assert_eq!(unreachable.depth(), 1);
+        assert_eq!(RecordQueue::new(7, db.clone(), mock_central()).depth(), 1);
+        assert_eq!(RecordQueue::new(0, db.clone(), mock_central()).depth(), 0);
+
+        db.clear();
+    }
+}
@@ -0,0 +1,109 @@
+use models::requests::error_response::ErrorResponse;
+use models::rfid::RfidData;
+use warp::http::header::CONTENT_TYPE;
+use warp::http::StatusCode;
+use warp::reply::Response;
+use warp::Reply;
+
+/// Encoding of a tag returned to a reader, picked from the request's `Accept` header
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TagFormat {
+    /// `RfidData` as JSON, mostly useful for debugging
+    Json,
+    /// The exact bytes to program on the tag
+    Binary,
+    /// The bytes to program on the tag, base64 encoded and served as `text/plain`
+    Base64,
+}
+
+impl TagFormat {
+    /// The format with the highest `q` weight in the header, JSON without one. Returns `None` if no
+    /// acceptable media type is supported, wildcards pick JSON or base64 text.
+    pub fn from_accept(accept: Option<String>) -> Option<Self> {
+        let accept = match accept {
+            Some(accept) => accept,
+            None => return Some(Self::Json),
+        };
+
+        let mut formats: Vec<(f32, Self)> = accept
+            .split(',')
+            .filter_map(|media_range| {
+                let mut params = media_range.split(';');
+                let format = match params.next().unwrap().trim() {
+                    "application/json" | "application/*" | "*/*" => Self::Json,
+                    "application/octet-stream" => Self::Binary,
+                    "text/plain" | "text/*" => Self::Base64,
+                    _ => return None,
+                };
+
+                let q = match params.find_map(|param| param.trim().strip_prefix("q=")) {
+                    Some(q) => q.parse::<f32>().ok()?,
+                    None => 1.0,
+                };
+
+                Some((q, format))
+            })
+            .filter(|(q, _)| *q > 0.0)
+            .collect();
+
+        // Stable, so equally weighted formats keep the header's order
+        formats.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap());
+        formats.first().map(|(_, format)| *format)
+    }
+}
+
+pub fn tag_reply(rfid_data: RfidData, format: TagFormat) -> Response {
+    match format {
+        TagFormat::Json => warp::reply::json(&rfid_data).into_response(),
+        TagFormat::Binary => {
+            let bytes: Vec<u8> = rfid_data.into();
+            warp::reply::with_header(bytes, CONTENT_TYPE, "application/octet-stream")
+                .into_response()
+        }
+        TagFormat::Base64 => {
+            let bytes: Vec<u8> = rfid_data.into();
+            warp::reply::with_header(base64::encode(bytes), CONTENT_TYPE, "text/plain")
+                .into_response()
+        }
+    }
+}
+
+pub fn error_reply(status: StatusCode, error: String) -> Response {
+    warp::reply::with_status(warp::reply::json(&ErrorResponse { error }), status).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::distributor_server::tag_image::TagFormat;
+
+    #[test]
+    fn test_from_accept() {
+        let from_accept = |accept: &str| TagFormat::from_accept(Some(accept.to_string()));
+
+        assert_eq!(TagFormat::from_accept(None), Some(TagFormat::Json));
+        assert_eq!(from_accept("*/*"), Some(TagFormat::Json));
+        assert_eq!(
+            from_accept("application/octet-stream"),
+            Some(TagFormat::Binary)
+        );
+        assert_eq!(
+            from_accept("text/html, text/plain;q=0.9"),
+            Some(TagFormat::Base64)
+        );
+        assert_eq!(
+            from_accept("application/json;q=0.1, application/octet-stream"),
+            Some(TagFormat::Binary)
+        );
+        assert_eq!(
+            from_accept("text/plain;q=0.5, application/json;q=0.5, */*;q=0.1"),
+            Some(TagFormat::Base64)
+        );
+        assert_eq!(
+            from_accept("application/octet-stream;q=0, */*;q=0.2"),
+            Some(TagFormat::Json)
+        );
+        assert_eq!(from_accept("text/html"), None);
+        assert_eq!(from_accept("application/base64"), None);
+        assert_eq!(from_accept("application/json;q=0"), None);
+    }
+}
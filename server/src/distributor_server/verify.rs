@@ -0,0 +1,444 @@
+use crate::error::ApiError;
+use models::central_record::CentralRecord;
+use models::checkpoint::CHECKPOINT_KEY_ID;
+use models::error::InspectionError;
+use models::key::PublicKey;
+use models::requests::verify::{ProvenanceHop, VerifyTagResponse};
+use models::rfid::RfidData;
+use reqwest::{StatusCode, Url};
+use std::collections::HashMap;
+
+pub async fn fetch_central_record(
+    client: &reqwest::Client,
+    central_server_addr: &Url,
+    chip_id: u128,
+) -> Result<Option<CentralRecord>, ApiError> {
+    let url = central_server_addr
+        .join(&format!("api/record/{}", chip_id))
+        .unwrap();
+
+    let res = client.get(url).send().await?;
+
+    if res.status() == StatusCode::NOT_FOUND {
+        Ok(None)
+    } else {
+        Ok(Some(res.error_for_status()?.json().await?))
+    }
+}
+
+/// Keys needed to check a central record's signatures
+pub fn record_key_ids(record: &Result<Option<CentralRecord>, ApiError>) -> Vec<u32> {
+    let mut key_ids = vec![CHECKPOINT_KEY_ID];
+
+    if let Ok(Some(record)) = record {
+        for entry in record.entries.iter() {
+            key_ids.push(entry.dist_id);
+            key_ids.push(entry.next_dist_id);
+        }
+    }
+
+    key_ids
+}
+
+/// Check that the record is the chip's and was signed by the central server, a record that fails
+/// isn't trusted at all
+fn authenticate_record(
+    rfid_data: &RfidData,
+    keys: &HashMap<u32, PublicKey>,
+    record: &CentralRecord,
+) -> Result<(), String> {
+    if record.chip_id != rfid_data.chip_data.chip_id {
+        return Err(format!(
+            "The central server returned the record of chip {}",
+            record.chip_id
+        ));
+    }
+
+    let central_key = keys.get(&CHECKPOINT_KEY_ID).ok_or_else(|| {
+        "The central server's public key is unknown, the central record can't be verified"
+            .to_string()
+    })?;
+
+    record
+        .validate_chain(keys, central_key.clone())
+        .map_err(|ndx| {
+            format!(
+                "The central record has an invalid signature at hop {}",
+                ndx + 1
+            )
+        })
+}
+
+/// Compare the tag with the central server's record of the chip
+fn cross_check(rfid_data: &RfidData, record: &Option<CentralRecord>) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let record = match record {
+        Some(record) => record,
+        None => {
+            if !rfid_data.entries.is_empty() {
+                problems.push("The central server has no record of this chip".to_string());
+            }
+            return problems;
+        }
+    };
+
+    let tag_hops = rfid_data.hops();
+    let record_hops = record.entries.len();
+
+    if record_hops > tag_hops {
+        problems.push(format!(
+            "The central server has recorded {} hops but the tag only has {}, the tag may be an old copy",
+            record_hops, tag_hops
+        ));
+    } else if record_hops < tag_hops {
+        problems.push(format!(
+            "The tag has {} hops but the central server has only recorded {}",
+            tag_hops, record_hops
+        ));
+    }
+
+    if let Some(checkpoint) = &rfid_data.checkpoint {
+        if !record.covers(checkpoint) {
+            problems.push("The tag's checkpoint wasn't issued from the central record".to_string());
+        }
+    }
+
+    let first_hop = tag_hops - rfid_data.distributor_entries().len();
+    for (ndx, (tag_entry, record_entry)) in rfid_data
+        .distributor_entries()
+        .iter()
+        .zip(record.entries.iter().skip(first_hop))
+        .enumerate()
+    {
+        if tag_entry.pub_key != record_entry.dist_id {
+            problems.push(format!(
+                "Hop {} was signed by distributor {} but the central server recorded distributor {}",
+                first_hop + ndx + 1,
+                tag_entry.pub_key,
+                record_entry.dist_id
+            ));
+        }
+    }
+
+    if record_hops == tag_hops {
+        if let Some(last_entry) = record.entries.last() {
+            let recorded_signatures = last_entry
+                .rfid_data
+                .entries
+                .iter()
+                .map(|entry| &entry.signature);
+            let tag_signatures = rfid_data.entries.iter().map(|entry| &entry.signature);
+
+            if !recorded_signatures.eq(tag_signatures) {
+                problems.push("The tag's signatures don't match the central record".to_string());
+            }
+        }
+    }
+
+    problems
+}
+
+fn provenance(
+    rfid_data: &RfidData,
+    keys: &HashMap<u32, PublicKey>,
+    record: &Option<CentralRecord>,
+    recipient: Option<u32>,
+) -> Vec<ProvenanceHop> {
+    let distributor_name = |id: u32| keys.get(&id).map(|key| key.distributor_name.clone());
+    let entries = rfid_data.distributor_entries();
+    let first_hop = rfid_data.hops() - entries.len();
+
+    // Hops dropped by a checkpoint are only known from the central record
+    let mut hops: Vec<ProvenanceHop> = record
+        .iter()
+        .flat_map(|record| record.entries.iter().take(first_hop))
+        .map(|record_entry| ProvenanceHop {
+            dist_id: record_entry.dist_id,
+            distributor_name: distributor_name(record_entry.dist_id),
+            next_dist_id: Some(record_entry.next_dist_id),
+        })
+        .collect();
+
+    hops.extend(entries.iter().enumerate().map(|(ndx, entry)| {
+        let next_dist_id = match entries.get(ndx + 1) {
+            Some(next_entry) => Some(next_entry.pub_key),
+            None => record
+                .as_ref()
+                .and_then(|record| record.entries.get(first_hop + ndx))
+                .map(|record_entry| record_entry.next_dist_id)
+                .or(recipient),
+        };
+
+        ProvenanceHop {
+            dist_id: entry.pub_key,
+            distributor_name: distributor_name(entry.pub_key),
+            next_dist_id,
+        }
+    }));
+
+    hops
+}
+
+fn summarize(
+    rfid_data: &RfidData,
+    provenance: &[ProvenanceHop],
+    keys: &HashMap<u32, PublicKey>,
+    problems: &[String],
+) -> String {
+    let name = |id: u32| match keys.get(&id) {
+        Some(key) => format!("{} ({})", key.distributor_name, id),
+        None => format!("Unknown distributor ({})", id),
+    };
+
+    let mut summary = format!("Chip {}", rfid_data.chip_data.chip_id);
+
+    if provenance.is_empty() {
+        summary.push_str(" has not been signed by any distributor.");
+    } else {
+        let mut route: Vec<String> = provenance.iter().map(|hop| name(hop.dist_id)).collect();
+        if let Some(next_dist_id) = provenance.last().unwrap().next_dist_id {
+            route.push(name(next_dist_id));
+        }
+
+        summary.push_str(&format!(
+            " passed through {} distributor(s): {}.",
+            provenance.len(),
+            route.join(" -> ")
+        ));
+    }
+
+    if problems.is_empty() {
+        summary.push_str(" The tag is valid and matches the central record.");
+    } else {
+        summary.push_str(&format!(
+            " The tag could not be verified: {}.",
+            problems.join("; ")
+        ));
+    }
+
+    summary
+}
+
+/// Read-only receiving inspection of a tag addressed to the distributor with `key_id`.
+///
+/// `keys` needs the central server's key and the keys of every distributor in the record to trust
+/// the record, see `record_key_ids`.
+pub fn verify_tag(
+    rfid_data: &RfidData,
+    keys: &HashMap<u32, PublicKey>,
+    key_id: u32,
+    record: Result<Option<CentralRecord>, ApiError>,
+) -> VerifyTagResponse {
+    let mut problems = Vec::new();
+
+    let inspection = match keys.get(&key_id) {
+        Some(our_key) => rfid_data.inspect(keys, our_key),
+        None => Err(InspectionError::UnknownDistributor(key_id)),
+    };
+
+    if let Err(e) = &inspection {
+        problems.push(e.to_string());
+    }
+
+    let record = match record {
+        Ok(record) => match record
+            .map(|record| authenticate_record(rfid_data, keys, &record).map(|_| record))
+            .transpose()
+        {
+            Ok(record) => {
+                problems.append(&mut cross_check(rfid_data, &record));
+                record
+            }
+            Err(problem) => {
+                problems.push(problem);
+                None
+            }
+        },
+        Err(e) => {
+            problems.push(format!("Unable to fetch the central record: {}", e));
+            None
+        }
+    };
+
+    let recipient = inspection.ok().map(|_| key_id);
+    let provenance = provenance(rfid_data, keys, &record, recipient);
+    let summary = summarize(rfid_data, &provenance, keys, &problems);
+
+    VerifyTagResponse {
+        valid: problems.is_empty(),
+        problems,
+        provenance,
+        summary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::distributor_server::verify::{cross_check, provenance, summarize, verify_tag};
+    use models::central_record::CentralRecord;
+    use models::checkpoint::CHECKPOINT_KEY_ID;
+    use models::key::PublicKey;
+    use models::rfid::{RfidBuilder, RfidData};
+    use models::signer::file_key::FileKeySigner;
+    use openssl::pkey::{PKey, Private};
+    use std::collections::HashMap;
+
+    struct Fixture {
+        keys: HashMap<u32, PublicKey>,
+        tags: Vec<RfidData>,
+        record: CentralRecord,
+    }
+
+    /// A chip signed from distributor 0 to 1 to 2, with every hop recorded by the central server
+    fn fixture() -> Fixture {
+        let key_ids = [0, 1, 2, CHECKPOINT_KEY_ID];
+        let keypairs: Vec<PKey<Private>> = key_ids
+            .iter()
+            .map(|_| PKey::generate_ed25519().unwrap())
+            .collect();
+        let keys: HashMap<u32, PublicKey> = key_ids
+            .iter()
+            .zip(keypairs.iter())
+            .map(|(&id, keypair)| {
+                let pem = keypair.public_key_to_pem().unwrap();
+                (id, PublicKey::new(id, pem, format!("dist{}", id)).unwrap())
+            })
+            .collect();
+        let signer = |id: usize| FileKeySigner::from_pkey(keypairs[id].clone()).unwrap();
+
+        let mut builder = RfidBuilder::default().chip_data(42, 5.0, 5.0, 5.0, 5.0);
+        let mut tags = Vec::new();
+        let mut record = CentralRecord::new(42);
+        for id in 0..2 {
+            builder = builder
+                .add_entry(&signer(id), id as u32, id as u32 + 1, &keys)
+                .unwrap();
+            let tag = RfidBuilder::from(builder.build()).build();
+            builder = RfidBuilder::from(tag.clone());
+
+            record
+                .add_entry(
+                    &signer(3),
+                    id as u32,
+                    id as u32 + 1,
+                    keys[&(id as u32 + 1)].key.clone(),
+                    tag.clone(),
+                )
+                .unwrap();
+            tags.push(tag);
+        }
+
+        Fixture { keys, tags, record }
+    }
+
+    #[test]
+    fn test_verify_tag() {
+        let Fixture { keys, tags, record } = fixture();
+
+        let response = verify_tag(&tags[1], &keys, 2, Ok(Some(record.clone())));
+        assert!(response.valid, "{:?}", response.problems);
+        assert_eq!(response.provenance.len(), 2);
+        assert!(response
+            .summary
+            .contains("dist0 (0) -> dist1 (1) -> dist2 (2)"));
+
+        // A record that wasn't signed by the central server isn't trusted
+        let mut forged = record.clone();
+        forged.entries[0].dist_id = 2;
+        let response = verify_tag(&tags[1], &keys, 2, Ok(Some(forged.clone())));
+        assert!(!response.valid);
+        assert_eq!(
+            response.problems,
+            vec!["The central record has an invalid signature at hop 1".to_string()]
+        );
+
+        let mut without_central = keys.clone();
+        without_central.remove(&CHECKPOINT_KEY_ID);
+        let response = verify_tag(&tags[1], &without_central, 2, Ok(Some(record.clone())));
+        assert!(response.problems[0].contains("can't be verified"));
+
+        let mut other_chip = record.clone();
+        other_chip.chip_id = 7;
+        let response = verify_tag(&tags[1], &keys, 2, Ok(Some(other_chip)));
+        assert!(response.problems[0].contains("record of chip 7"));
+
+        // Addressed to someone else
+        let response = verify_tag(&tags[1], &keys, 1, Ok(Some(record)));
+        assert!(!response.valid);
+        assert_eq!(response.provenance[1].next_dist_id, Some(2));
+    }
+
+    #[test]
+    fn test_cross_check() {
+        let Fixture { tags, record, .. } = fixture();
+
+        assert!(cross_check(&tags[1], &Some(record.clone())).is_empty());
+        assert_eq!(
+            cross_check(&tags[1], &None),
+            vec!["The central server has no record of this chip".to_string()]
+        );
+        assert!(cross_check(&RfidData::default(), &None).is_empty());
+
+        // An old copy of the tag
+        let problems = cross_check(&tags[0], &Some(record.clone()));
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("may be an old copy"));
+
+        let mut behind = record.clone();
+        behind.entries.pop();
+        let problems = cross_check(&tags[1], &Some(behind));
+        assert!(problems[0].contains("has only recorded 1"));
+
+        let mut other_signer = record.clone();
+        other_signer.entries[1].dist_id = 0;
+        let problems = cross_check(&tags[1], &Some(other_signer));
+        assert_eq!(
+            problems,
+            vec![
+                "Hop 2 was signed by distributor 1 but the central server recorded distributor 0"
+                    .to_string()
+            ]
+        );
+
+        let mut copied = tags[1].clone();
+        copied.entries[1].signature[0] ^= 1;
+        let problems = cross_check(&copied, &Some(record));
+        assert_eq!(
+            problems,
+            vec!["The tag's signatures don't match the central record".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_provenance() {
+        let Fixture { keys, tags, record } = fixture();
+
+        let hops = provenance(&tags[1], &keys, &None, None);
+        assert_eq!(hops.len(), 2);
+        assert_eq!(hops[0].next_dist_id, Some(1));
+        assert_eq!(hops[1].distributor_name.as_deref(), Some("dist1"));
+        assert_eq!(hops[1].next_dist_id, None);
+
+        assert_eq!(
+            provenance(&tags[1], &keys, &None, Some(2))[1].next_dist_id,
+            Some(2)
+        );
+        assert_eq!(
+            provenance(&tags[1], &keys, &Some(record), None)[1].next_dist_id,
+            Some(2)
+        );
+
+        let summary = summarize(&tags[1], &hops, &HashMap::new(), &["Broken".to_string()]);
+        assert_eq!(
+            summary,
+            "Chip 42 passed through 2 distributor(s): Unknown distributor (0) -> Unknown distributor (1). The tag could not be verified: Broken."
+        );
+
+        let blank = RfidData::default();
+        assert_eq!(
+            summarize(&blank, &provenance(&blank, &keys, &None, None), &keys, &[]),
+            "Chip 0 has not been signed by any distributor. The tag is valid and matches the central record."
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use config::ConfigError;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
-use models::error::RfidDataParseError;
+use models::error::{RfidDataParseError, SignerError};
 
 
 #[derive(Debug)]
@@ -10,6 +10,11 @@ pub enum ApiError {
     WarpError(warp::Error),
     RfidDataError(RfidDataParseError),
     ConfigError(config::ConfigError),
+    SignerError(SignerError),
+    IoError(std::io::Error),
+    Unauthorized,
+    /// The key registered under `CHECKPOINT_KEY_ID` isn't the central server's key
+    CheckpointKeyMismatch,
 }
 
 impl From<reqwest::Error> for ApiError {
@@ -36,6 +41,18 @@ impl From<config::ConfigError> for ApiError {
     }
 }
 
+impl From<SignerError> for ApiError {
+    fn from(e: SignerError) -> Self {
+        Self::SignerError(e)
+    }
+}
+
+impl From<std::io::Error> for ApiError {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
 impl Display for ApiError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -43,6 +60,14 @@ impl Display for ApiError {
             ApiError::WarpError(e) => write!(f, "Warp error: {}", e),
             ApiError::RfidDataError(e) => write!(f, "RFIDDataError: {}", e),
             ApiError::ConfigError(e) => writeln!(f, "Config error: {}", e),
+            ApiError::SignerError(e) => write!(f, "Signer error: {}", e),
+            ApiError::IoError(e) => write!(f, "IO error: {}", e),
+            ApiError::Unauthorized => write!(f, "Unauthorized"),
+            ApiError::CheckpointKeyMismatch => write!(
+                f,
+                "The checkpoint key registered under {} is not the central server's key",
+                models::checkpoint::CHECKPOINT_KEY_ID
+            ),
         }
     }
 }
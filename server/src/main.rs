@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 #![allow(clippy::from_over_into)]
+#![allow(clippy::enum_variant_names)]
 
 mod args;
 mod central_server;
@@ -7,6 +8,7 @@ mod config;
 mod database;
 mod distributor_server;
 mod error;
+mod signing_daemon;
 
 use crate::args::{Args, Mode};
 use structopt::StructOpt;
@@ -16,15 +18,17 @@ use structopt::StructOpt;
 async fn main() {
     let args: Args = Args::from_args();
 
-    match &args.mode {
+    let result = match &args.mode {
         Mode::DistributorServer(dist_args) => {
-            distributor_server::distributor_server(&args, dist_args)
-                .await
-                .unwrap()
+            distributor_server::distributor_server(&args, dist_args).await
         }
-        Mode::CentralServer(cent_args) => central_server::central_server(&args, cent_args)
-            .await
-            .unwrap(),
+        Mode::CentralServer(cent_args) => central_server::central_server(&args, cent_args).await,
+        Mode::SigningDaemon(daemon_args) => signing_daemon::signing_daemon(daemon_args).await,
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
     }
 }
 
@@ -32,35 +36,12 @@ async fn main() {
 mod tests {
     use std::collections::HashMap;
     use std::convert::TryFrom;
-    use std::path::PathBuf;
 
-    use openssl::hash::{hash, MessageDigest};
-    use openssl::pkey::PKey;
     use openssl::rsa::Rsa;
-    use openssl::sign::Signer;
 
-    use crate::models::key::PublicKey;
-    use crate::models::rfid::{RfidBuilder, RfidData};
-    use crate::utility::{hash_from_signature, open_private_key};
-
-    #[test]
-    fn test_signature_decrypt() {
-        let rsa = Rsa::generate(2048).unwrap();
-        let keypair = PKey::from_rsa(rsa.clone()).unwrap();
-        let hasher = MessageDigest::sha3_256();
-        let test_value = b"\xDE\xAD\xBE\xEF\xff";
-        let test_hash = hash(hasher, test_value).unwrap().to_vec();
-
-        let mut signer = Signer::new(hasher, &keypair).unwrap();
-
-        signer.update(test_value).unwrap();
-
-        let signature = signer.sign_to_vec().unwrap();
-
-        let sign_hash = hash_from_signature(&keypair.public_key_to_pem().unwrap(), &signature);
-
-        assert_eq!(sign_hash, test_hash)
-    }
+    use models::key::PublicKey;
+    use models::rfid::{RfidBuilder, RfidData};
+    use models::signer::file_key::FileKeySigner;
 
     #[test]
     fn test_rfid_data_build() {
@@ -79,7 +60,7 @@ mod tests {
                 key_id1,
                 keypair1.public_key_to_pem().unwrap(),
                 "1".to_string(),
-            ),
+            ).unwrap(),
         );
         key_map.insert(
             key_id2,
@@ -87,7 +68,7 @@ mod tests {
                 key_id2,
                 keypair2.public_key_to_pem().unwrap(),
                 "2".to_string(),
-            ),
+            ).unwrap(),
         );
         key_map.insert(
             key_id3,
@@ -95,23 +76,25 @@ mod tests {
                 key_id3,
                 keypair3.public_key_to_pem().unwrap(),
                 "3".to_string(),
-            ),
+            ).unwrap(),
         );
 
         let data = RfidBuilder::default()
             .chip_data(42, 5.0, 5.0, 5.0, 5.0)
             .add_entry(
-                keypair1.private_key_to_pem().unwrap(),
+                &FileKeySigner::new(keypair1.clone()).unwrap(),
                 key_id1,
                 key_id2,
                 &key_map,
             )
+            .unwrap()
             .add_entry(
-                keypair2.private_key_to_pem().unwrap(),
+                &FileKeySigner::new(keypair2.clone()).unwrap(),
                 key_id2,
                 key_id3,
                 &key_map,
             )
+            .unwrap()
             .build();
 
         assert!(data.valid_crc());
@@ -130,8 +113,8 @@ mod tests {
 
     #[test]
     fn test_to_json() {
-        let keypair1 = open_private_key(PathBuf::from("test_keys/sauce_rsa"));
-        let keypair2 = open_private_key(PathBuf::from("test_keys/cool_chip_rsa"));
+        let keypair1 = Rsa::generate(2048).unwrap();
+        let keypair2 = Rsa::generate(2048).unwrap();
 
         let key_id1: u32 = 55;
         let key_id2: u32 = 0;
@@ -143,7 +126,7 @@ mod tests {
                 key_id1,
                 keypair1.public_key_to_pem().unwrap(),
                 "Sauce Firm".to_string(),
-            ),
+            ).unwrap(),
         );
         key_map.insert(
             key_id2,
@@ -151,16 +134,17 @@ mod tests {
                 key_id2,
                 keypair2.public_key_to_pem().unwrap(),
                 "Cool Chip 123".to_string(),
-            ),
+            ).unwrap(),
         );
         let data = RfidBuilder::default()
             .chip_data(42, 5.0, 5.0, 5.0, 5.0)
             .add_entry(
-                keypair1.private_key_to_pem().unwrap(),
+                &FileKeySigner::new(keypair1.clone()).unwrap(),
                 key_id1,
                 key_id2,
                 &key_map,
             )
+            .unwrap()
             .build();
 
         data.validate_chain(&key_map, key_map.get(&key_id2).unwrap().clone())
@@ -0,0 +1,64 @@
+use models::error::SignerError;
+use models::requests::sign::{SignRequest, SignResponse};
+use models::signer::Signer;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+const SIGNING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connection details for a signing daemon listening on a Unix socket
+pub struct RemoteSigner {
+    socket_path: PathBuf,
+}
+
+fn remote_error<E: ToString>(e: E) -> SignerError {
+    SignerError::RemoteError(e.to_string())
+}
+
+impl RemoteSigner {
+    pub fn new(socket_path: PathBuf) -> Self {
+        Self { socket_path }
+    }
+
+    /// Have the daemon sign a request, it builds the signed data and checks it against its policy
+    pub async fn sign(&self, request: &SignRequest) -> Result<PresignedSigner, SignerError> {
+        let response = tokio::time::timeout(SIGNING_TIMEOUT, self.exchange(request))
+            .await
+            .map_err(|_| SignerError::RemoteError("Signing daemon timed out".to_string()))??;
+
+        match response.error {
+            Some(e) => Err(SignerError::Rejected(e)),
+            None => Ok(PresignedSigner(response.signature)),
+        }
+    }
+
+    async fn exchange(&self, request: &SignRequest) -> Result<SignResponse, SignerError> {
+        let mut stream = UnixStream::connect(&self.socket_path)
+            .await
+            .map_err(remote_error)?;
+
+        let mut bytes = serde_json::to_vec(request).unwrap();
+        bytes.push(b'\n');
+        stream.write_all(&bytes).await.map_err(remote_error)?;
+
+        let mut line = String::new();
+        BufReader::new(stream)
+            .read_line(&mut line)
+            .await
+            .map_err(remote_error)?;
+
+        serde_json::from_str(&line).map_err(remote_error)
+    }
+}
+
+/// Hands a signature made by the signing daemon to the builders in `models`, which sign the same
+/// data the daemon built
+pub struct PresignedSigner(Vec<u8>);
+
+impl Signer for PresignedSigner {
+    fn sign(&self, _members: &[&[u8]]) -> Result<Vec<u8>, SignerError> {
+        Ok(self.0.clone())
+    }
+}
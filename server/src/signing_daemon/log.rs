@@ -0,0 +1,47 @@
+use byteorder::{BigEndian, WriteBytesExt};
+use models::{deserialize_base64, serialize_base64, DatabaseModel};
+use serde::{Deserialize, Serialize};
+
+/// Record of a signature issued by the signing daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignatureLogEntry {
+    pub id: u64,
+    pub chip_id: u128,
+    /// Distributor the chip was signed over to, `None` for checkpoint requests
+    pub next_dist_id: Option<u32>,
+    pub signed_at: u64,
+    /// SHA3-256 hash of the signed data
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    pub digest: Vec<u8>,
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    pub signature: Vec<u8>,
+}
+
+impl DatabaseModel for SignatureLogEntry {
+    type ID = u64;
+
+    fn id(&self) -> Self::ID {
+        self.id
+    }
+
+    fn set_id(&mut self, id: Self::ID) {
+        self.id = id
+    }
+
+    fn id_type_to_bytes(id: Self::ID) -> Vec<u8> {
+        // Big endian so the log iterates in the order signatures were issued
+        let mut bytes = Vec::new();
+        bytes.write_u64::<BigEndian>(id).unwrap();
+        bytes
+    }
+
+    fn tree() -> String {
+        "signature_log".to_string()
+    }
+}
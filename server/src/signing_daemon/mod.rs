@@ -0,0 +1,377 @@
+pub mod client;
+mod log;
+mod policy;
+
+use crate::args::SigningDaemonArgs;
+use crate::config::signing_policy_config::SigningPolicyConfig;
+use crate::database;
+use crate::database::Database;
+use crate::distributor_server::inventory::timestamp;
+use crate::distributor_server::key_cache::KeyCache;
+use crate::error::ApiError;
+use log::SignatureLogEntry;
+use models::checkpoint::CHECKPOINT_KEY_ID;
+use models::key::PublicKey;
+use models::requests::checkpoint::CheckpointRequest;
+use models::requests::sign::{SignRequest, SignResponse};
+use models::requests::update_record::UpdateRecordRequest;
+use models::rfid::RfidData;
+use models::signer::{open_signer, Signer};
+use openssl::hash::{hash, MessageDigest};
+use policy::Policy;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+/// How long a cached public key is used before it is refreshed from the central server
+const KEY_TTL: Duration = Duration::from_secs(600);
+/// How long a cached public key can still be used while the central server is unreachable
+const KEY_MAX_STALE: Duration = Duration::from_secs(3600);
+
+/// Holds a distributor's private key and signs on behalf of the distributor server
+struct SigningDaemon {
+    db: Arc<Database>,
+    key_id: u32,
+    signer: Box<dyn Signer>,
+    key_cache: Arc<KeyCache>,
+    policy: Policy,
+}
+
+fn refusal(error: String) -> SignResponse {
+    SignResponse {
+        signature: vec![],
+        error: Some(error),
+    }
+}
+
+impl SigningDaemon {
+    /// Keys for the tag's chain, our own key and `extra`, fetched from the central server
+    async fn keys(&self, rfid_data: &RfidData, extra: &[u32]) -> HashMap<u32, PublicKey> {
+        let mut key_ids: Vec<u32> = rfid_data
+            .entries
+            .iter()
+            .map(|entry| entry.pub_key)
+            .collect();
+        key_ids.extend_from_slice(extra);
+        key_ids.push(self.key_id);
+        key_ids.push(CHECKPOINT_KEY_ID);
+
+        self.key_cache.get_keys(&key_ids).await
+    }
+
+    /// Build the data a request asks us to sign, tags are checked against the central server's keys
+    async fn signed_data(&self, request: &SignRequest) -> Result<Vec<u8>, String> {
+        let unknown = |key_id: u32| format!("Distributor {} is unknown", key_id);
+
+        match request {
+            SignRequest::Entry {
+                rfid_data,
+                next_dist_id,
+            } => {
+                let keys = self.keys(rfid_data, &[*next_dist_id]).await;
+                let our_key = keys.get(&self.key_id).ok_or_else(|| unknown(self.key_id))?;
+                let next_key = keys
+                    .get(next_dist_id)
+                    .ok_or_else(|| unknown(*next_dist_id))?;
+
+                rfid_data
+                    .inspect(&keys, our_key)
+                    .map_err(|e| format!("Rejected tag: {}", e))?;
+
+                let mut data = rfid_data
+                    .next_link_data(&keys, our_key)
+                    .map_err(|ndx| format!("Invalid tag at position {}", ndx))?;
+                data.extend_from_slice(&next_key.key);
+
+                Ok(data)
+            }
+            SignRequest::UpdateRecord {
+                rfid_data,
+                next_dist_id,
+                measurement,
+            } => {
+                // Only tags we signed over to the next distributor are reported
+                if rfid_data.entries.last().map(|entry| entry.pub_key) != Some(self.key_id) {
+                    return Err("The tag's last entry was not signed by us".to_string());
+                }
+
+                let keys = self.keys(rfid_data, &[*next_dist_id]).await;
+                let next_key = keys
+                    .get(next_dist_id)
+                    .ok_or_else(|| unknown(*next_dist_id))?;
+
+                rfid_data
+                    .validate_chain(&keys, next_key.clone())
+                    .map_err(|ndx| format!("Invalid tag at position {}", ndx))?;
+
+                Ok(UpdateRecordRequest {
+                    dist_id: self.key_id,
+                    next_dist_id: *next_dist_id,
+                    rfid_data: rfid_data.clone(),
+                    measurement: *measurement,
+                    signature: vec![],
+                }
+                .into())
+            }
+            SignRequest::Checkpoint { rfid_data } => {
+                let keys = self.keys(rfid_data, &[]).await;
+                let our_key = keys.get(&self.key_id).ok_or_else(|| unknown(self.key_id))?;
+
+                rfid_data
+                    .inspect(&keys, our_key)
+                    .map_err(|e| format!("Rejected tag: {}", e))?;
+
+                Ok(CheckpointRequest {
+                    dist_id: self.key_id,
+                    rfid_data: rfid_data.clone(),
+                    signature: vec![],
+                }
+                .into())
+            }
+        }
+    }
+
+    async fn sign(&self, request: SignRequest) -> SignResponse {
+        let chip_id = request.chip_id();
+
+        let data = match self.signed_data(&request).await {
+            Ok(data) => data,
+            Err(e) => {
+                println!("Refused to sign chip {}: {}", chip_id, e);
+                return refusal(e);
+            }
+        };
+
+        if let Err(e) = self.policy.check(&request) {
+            println!("Refused to sign chip {}: {}", chip_id, e);
+            return refusal(e.to_string());
+        }
+
+        let signature = match self.signer.sign(&[&data]) {
+            Ok(signature) => signature,
+            Err(e) => {
+                self.policy.release(&request);
+                return refusal(e.to_string());
+            }
+        };
+
+        self.db.insert::<SignatureLogEntry>(SignatureLogEntry {
+            id: self.db.generate_id(),
+            chip_id,
+            next_dist_id: request.next_dist_id(),
+            signed_at: timestamp(),
+            digest: hash(MessageDigest::sha3_256(), &data).unwrap().to_vec(),
+            signature: signature.clone(),
+        });
+
+        match request.next_dist_id() {
+            Some(next_dist_id) => {
+                println!("Signed chip {} for distributor {}", chip_id, next_dist_id)
+            }
+            None => println!("Signed checkpoint request for chip {}", chip_id),
+        }
+
+        SignResponse {
+            signature,
+            error: None,
+        }
+    }
+
+    /// Serve requests from one connection, each request and response is a line of JSON
+    async fn handle(self: Arc<Self>, stream: UnixStream) {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            let response = match serde_json::from_str::<SignRequest>(&line) {
+                Ok(request) => self.sign(request).await,
+                Err(e) => refusal(format!("Invalid request: {}", e)),
+            };
+
+            let mut bytes = serde_json::to_vec(&response).unwrap();
+            bytes.push(b'\n');
+
+            if writer.write_all(&bytes).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+pub async fn signing_daemon(daemon_args: &SigningDaemonArgs) -> Result<(), ApiError> {
+    let db = database::Database::new(&daemon_args.database_path);
+    let signer = open_signer(
+        &daemon_args.private_key.to_string_lossy(),
+        &daemon_args.passphrase,
+    )?;
+
+    let policy = match &daemon_args.policy_path {
+        Some(path) => SigningPolicyConfig::new(path)?,
+        None => SigningPolicyConfig::default(),
+    };
+
+    let central_server_addr = Url::from_str(&daemon_args.central_server_addr).unwrap();
+    let key_cache = KeyCache::new(central_server_addr, KEY_TTL, KEY_MAX_STALE);
+
+    let daemon = Arc::new(SigningDaemon {
+        db,
+        key_id: daemon_args.key_id,
+        signer,
+        key_cache,
+        policy: Policy::new(policy),
+    });
+
+    // A socket left behind by a previous run would stop us from binding
+    if daemon_args.socket_path.exists() {
+        std::fs::remove_file(&daemon_args.socket_path)?;
+    }
+
+    let listener = UnixListener::bind(&daemon_args.socket_path)?;
+    std::fs::set_permissions(
+        &daemon_args.socket_path,
+        std::fs::Permissions::from_mode(0o660),
+    )?;
+
+    println!("Starting signing daemon...");
+    serve(daemon, listener).await
+}
+
+async fn serve(daemon: Arc<SigningDaemon>, listener: UnixListener) -> Result<(), ApiError> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(daemon.clone().handle(stream));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::signing_policy_config::SigningPolicyConfig;
+    use crate::database::Database;
+    use crate::distributor_server::key_cache::tests::mock_central;
+    use crate::distributor_server::key_cache::KeyCache;
+    use crate::signing_daemon::client::RemoteSigner;
+    use crate::signing_daemon::log::SignatureLogEntry;
+    use crate::signing_daemon::policy::Policy;
+    use crate::signing_daemon::{serve, SigningDaemon, KEY_MAX_STALE, KEY_TTL};
+    use models::error::SignerError;
+    use models::key::PublicKey;
+    use models::requests::sign::SignRequest;
+    use models::requests::update_record::UpdateRecordRequest;
+    use models::rfid::RfidBuilder;
+    use models::signer::file_key::FileKeySigner;
+    use models::test_util::public_key;
+    use models::BlockChainEntry;
+    use openssl::pkey::PKey;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::UnixListener;
+
+    fn key(id: u32) -> (PublicKey, FileKeySigner) {
+        let key = PKey::generate_ed25519().unwrap();
+
+        (public_key(id, &key), FileKeySigner::from_pkey(key).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_signing_daemon() {
+        // The daemon holds the key of distributor 1
+        let (pk0, signer0) = key(0);
+        let (pk1, signer1) = key(1);
+        let (pk2, _) = key(2);
+        let keys: HashMap<u32, PublicKey> = vec![(0, pk0), (1, pk1.clone()), (2, pk2.clone())]
+            .into_iter()
+            .collect();
+        let central = mock_central(Arc::new(Mutex::new(Some(keys.clone()))));
+
+        let db = Database::new(&std::env::temp_dir().join("rfsc_test_signing_daemon_db"));
+        db.clear();
+        let daemon = Arc::new(SigningDaemon {
+            db: db.clone(),
+            key_id: 1,
+            signer: Box::new(signer1),
+            key_cache: KeyCache::new(central, KEY_TTL, KEY_MAX_STALE),
+            policy: Policy::new(SigningPolicyConfig {
+                allowed_next_distributors: Some(vec![2]),
+                ..Default::default()
+            }),
+        });
+
+        let socket_path = std::env::temp_dir().join("rfsc_test_signing_daemon.sock");
+        std::fs::remove_file(&socket_path).ok();
+        tokio::spawn(serve(daemon, UnixListener::bind(&socket_path).unwrap()));
+        let remote = RemoteSigner::new(socket_path.clone());
+
+        let tag = RfidBuilder::default()
+            .chip_data(7, 5.0, 5.0, 5.0, 5.0)
+            .add_entry(&signer0, 0, 1, &keys)
+            .unwrap()
+            .build();
+
+        // The daemon signs the same data the builder would have signed
+        let signer = remote
+            .sign(&SignRequest::Entry {
+                rfid_data: tag.clone(),
+                next_dist_id: 2,
+            })
+            .await
+            .unwrap();
+        let signed = RfidBuilder::from(tag.clone())
+            .add_entry(&signer, 1, 2, &keys)
+            .unwrap()
+            .build();
+        assert!(signed.validate_chain(&keys, pk2).is_ok());
+
+        let signer = remote
+            .sign(&SignRequest::UpdateRecord {
+                rfid_data: signed.clone(),
+                next_dist_id: 2,
+                measurement: None,
+            })
+            .await
+            .unwrap();
+        let update = UpdateRecordRequest::new(1, 2, signed, None, &signer).unwrap();
+        let update_bytes: Vec<u8> = update.clone().into();
+        assert!(update.verify_signature(&update_bytes, &pk1));
+
+        // Signing over to a distributor the policy doesn't allow
+        let refused = remote
+            .sign(&SignRequest::Entry {
+                rfid_data: tag.clone(),
+                next_dist_id: 0,
+            })
+            .await;
+        assert!(matches!(refused, Err(SignerError::Rejected(_))));
+
+        // The chip and distributor of an update come from the tag, which we didn't sign last
+        let refused = remote
+            .sign(&SignRequest::UpdateRecord {
+                rfid_data: tag.clone(),
+                next_dist_id: 2,
+                measurement: None,
+            })
+            .await;
+        assert!(matches!(refused, Err(SignerError::Rejected(_))));
+
+        // A tag that isn't signed over to us
+        let mut tampered = tag;
+        tampered.chip_data.chip_id = 8;
+        tampered.crc = tampered.calc_crc();
+        let refused = remote
+            .sign(&SignRequest::Entry {
+                rfid_data: tampered,
+                next_dist_id: 2,
+            })
+            .await;
+        assert!(matches!(refused, Err(SignerError::Rejected(_))));
+
+        assert_eq!(db.count::<SignatureLogEntry>(), 2);
+
+        db.clear();
+        std::fs::remove_file(&socket_path).ok();
+    }
+}
@@ -0,0 +1,176 @@
+use crate::config::signing_policy_config::SigningPolicyConfig;
+use models::requests::sign::SignRequest;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PolicyError {
+    RateLimited(usize),
+    NextDistributorNotAllowed(u32),
+    ChipIdNotAllowed(u128),
+}
+
+impl Error for PolicyError {}
+
+impl Display for PolicyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyError::RateLimited(limit) => {
+                write!(f, "Rate limit of {} signatures per minute reached", limit)
+            }
+            PolicyError::NextDistributorNotAllowed(id) => {
+                write!(f, "Signing over to distributor {} is not allowed", id)
+            }
+            PolicyError::ChipIdNotAllowed(id) => {
+                write!(f, "Chip {} is outside the allowed chip ID ranges", id)
+            }
+        }
+    }
+}
+
+/// Decides which signing requests the signing daemon will serve
+pub struct Policy {
+    config: SigningPolicyConfig,
+    recent_signatures: Mutex<VecDeque<Instant>>,
+}
+
+impl Policy {
+    pub fn new(config: SigningPolicyConfig) -> Self {
+        Self {
+            config,
+            recent_signatures: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Check a request against the policy.
+    ///
+    /// Only tag entries count towards the rate limit, the update and checkpoint signatures made for
+    /// a hop don't. An allowed entry takes a slot, which `release` gives back if signing fails.
+    pub fn check(&self, request: &SignRequest) -> Result<(), PolicyError> {
+        if let (Some(allowed), Some(next_dist_id)) = (
+            &self.config.allowed_next_distributors,
+            request.next_dist_id(),
+        ) {
+            if !allowed.contains(&next_dist_id) {
+                return Err(PolicyError::NextDistributorNotAllowed(next_dist_id));
+            }
+        }
+
+        if let Some(ranges) = &self.config.chip_id_ranges {
+            let chip_id = request.chip_id();
+            let in_range = ranges
+                .iter()
+                .any(|range| range.start <= chip_id && chip_id <= range.end);
+
+            if !in_range {
+                return Err(PolicyError::ChipIdNotAllowed(chip_id));
+            }
+        }
+
+        if !matches!(request, SignRequest::Entry { .. }) {
+            return Ok(());
+        }
+
+        let mut recent_signatures = self.recent_signatures.lock().unwrap();
+        let now = Instant::now();
+
+        while let Some(signed_at) = recent_signatures.front() {
+            if now.duration_since(*signed_at) < RATE_LIMIT_WINDOW {
+                break;
+            }
+            recent_signatures.pop_front();
+        }
+
+        if let Some(limit) = self.config.max_signatures_per_minute {
+            if recent_signatures.len() >= limit {
+                return Err(PolicyError::RateLimited(limit));
+            }
+        }
+
+        recent_signatures.push_back(now);
+
+        Ok(())
+    }
+
+    /// Give back the rate limit slot `check` took for a request that wasn't signed
+    pub fn release(&self, request: &SignRequest) {
+        if matches!(request, SignRequest::Entry { .. }) {
+            self.recent_signatures.lock().unwrap().pop_back();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::signing_policy_config::{ChipIdRange, SigningPolicyConfig};
+    use crate::signing_daemon::policy::{Policy, PolicyError};
+    use models::requests::sign::SignRequest;
+    use models::rfid::RfidBuilder;
+
+    fn request(chip_id: u128, next_dist_id: u32) -> SignRequest {
+        SignRequest::Entry {
+            rfid_data: RfidBuilder::default()
+                .chip_data(chip_id, 5.0, 5.0, 5.0, 5.0)
+                .build(),
+            next_dist_id,
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = Policy::new(SigningPolicyConfig {
+            max_signatures_per_minute: Some(3),
+            allowed_next_distributors: Some(vec![56]),
+            chip_id_ranges: Some(vec![
+                ChipIdRange {
+                    start: 100,
+                    end: 199,
+                },
+                ChipIdRange {
+                    start: u64::MAX as u128 + 1,
+                    end: u128::MAX,
+                },
+            ]),
+        });
+
+        assert!(policy.check(&request(100, 56)).is_ok());
+        assert_eq!(
+            policy.check(&request(100, 57)),
+            Err(PolicyError::NextDistributorNotAllowed(57))
+        );
+        assert_eq!(
+            policy.check(&request(200, 56)),
+            Err(PolicyError::ChipIdNotAllowed(200))
+        );
+        assert!(policy.check(&request(u128::MAX, 56)).is_ok());
+
+        // Checkpoints aren't signed over to anyone, only the chip is checked
+        let checkpoint = SignRequest::Checkpoint {
+            rfid_data: RfidBuilder::default()
+                .chip_data(199, 5.0, 5.0, 5.0, 5.0)
+                .build(),
+        };
+        assert!(policy.check(&checkpoint).is_ok());
+
+        // Only entries count towards the limit
+        assert!(policy.check(&request(150, 56)).is_ok());
+        assert_eq!(
+            policy.check(&request(150, 56)),
+            Err(PolicyError::RateLimited(3))
+        );
+        policy.release(&checkpoint);
+        assert!(policy.check(&request(150, 56)).is_err());
+
+        // The slot of an entry that failed to sign can be used again
+        policy.release(&request(150, 56));
+        assert!(policy.check(&request(150, 56)).is_ok());
+
+        let open = Policy::new(SigningPolicyConfig::default());
+        assert!(open.check(&request(u128::MAX, 1)).is_ok());
+    }
+}
@@ -0,0 +1,406 @@
+#ifndef RFID_TAG_H
+#define RFID_TAG_H
+
+/* Generated by cbindgen from tag-ffi/src/lib.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ RSA-2048 PKCS#1 v1.5 over SHA3-256, 256 byte signatures
+ */
+#define RFID_ALGORITHM_RSA_SHA3 0
+
+/*
+ Ed25519, 64 byte signatures
+ */
+#define RFID_ALGORITHM_ED25519 1
+
+/*
+ ECDSA on P-256 over SHA-256, 64 byte signatures holding r and s
+ */
+#define RFID_ALGORITHM_ECDSA_P256 2
+
+/*
+ Tags written before the format header existed
+ */
+#define RFID_TAG_VERSION_LEGACY 0
+
+#define RFID_TAG_VERSION_1 1
+
+/*
+ Largest encoded tag the parser accepts, in bytes
+ */
+#define RFID_MAX_TAG_SIZE 8192
+
+/*
+ Frequency of one of the chip's ring oscillators, in Hz
+ */
+#define RFID_MEASUREMENT_RING_OSCILLATOR 1
+
+/*
+ Supply current with the chip idle, in A
+ */
+#define RFID_MEASUREMENT_LEAKAGE_CURRENT 2
+
+/*
+ Delay of a critical path, in s
+ */
+#define RFID_MEASUREMENT_PATH_DELAY 3
+
+/*
+ Lowest supply voltage the chip still works at, in V
+ */
+#define RFID_MEASUREMENT_MINIMUM_VOLTAGE 4
+
+/*
+ Die temperature the other measurements were taken at, in degrees Celsius
+ */
+#define RFID_MEASUREMENT_TEMPERATURE 5
+
+/*
+ Result of a library call. Values are stable and new ones are only ever added at the end.
+ */
+typedef enum RfidStatus {
+  RFID_STATUS_OK = 0,
+  /*
+   A required pointer argument was null
+   */
+  RFID_STATUS_NULL_POINTER = 1,
+  /*
+   The tag is larger than the parser accepts, or would be with the entry being appended
+   */
+  RFID_STATUS_TOO_LARGE = 2,
+  /*
+   The tag ends before its header and chip data do
+   */
+  RFID_STATUS_TRUNCATED_HEADER = 3,
+  /*
+   The tag ends before the number of entries in its header
+   */
+  RFID_STATUS_ENTRY_COUNT_MISMATCH = 4,
+  /*
+   The tag has bytes after its last entry
+   */
+  RFID_STATUS_TRAILING_BYTES = 5,
+  RFID_STATUS_INVALID_CRC = 6,
+  /*
+   The tag format version, or a requested version, isn't supported
+   */
+  RFID_STATUS_UNSUPPORTED_VERSION = 7,
+  RFID_STATUS_UNSUPPORTED_FLAGS = 8,
+  RFID_STATUS_UNKNOWN_ALGORITHM = 9,
+  /*
+   A chip data measurement is NaN or infinite
+   */
+  RFID_STATUS_INVALID_FLOAT = 10,
+  /*
+   An entry was signed by, or signed over to, a distributor missing from the key table
+   */
+  RFID_STATUS_UNKNOWN_DISTRIBUTOR = 11,
+  /*
+   An entry's signature doesn't verify, or has the wrong size for its algorithm
+   */
+  RFID_STATUS_INVALID_SIGNATURE = 12,
+  /*
+   The last entry isn't signed over to the expected recipient
+   */
+  RFID_STATUS_WRONG_RECIPIENT = 13,
+  /*
+   A public key couldn't be parsed or isn't supported
+   */
+  RFID_STATUS_INVALID_KEY = 14,
+  RFID_STATUS_INDEX_OUT_OF_RANGE = 15,
+  /*
+   The output buffer is null or too small, the required length was written
+   */
+  RFID_STATUS_BUFFER_TOO_SMALL = 16,
+  /*
+   The chip data's measurement section is malformed or holds a value that isn't finite
+   */
+  RFID_STATUS_INVALID_MEASUREMENT = 17,
+  /*
+   The tag has entries, which signed its chip data, so the chip data can't change
+   */
+  RFID_STATUS_CHIP_DATA_SIGNED = 18,
+  /*
+   The library hit a bug, no output was written
+   */
+  RFID_STATUS_INTERNAL = 19,
+} RfidStatus;
+
+/*
+ Distributor public keys by key ID, created by `rfid_key_table_new`
+ */
+typedef struct RfidKeyTable RfidKeyTable;
+
+/*
+ Contents of a tag, created by `rfid_tag_parse` or `rfid_tag_new`
+ */
+typedef struct RfidTag RfidTag;
+
+/*
+ Measurements of the chip a tag is attached to
+ */
+typedef struct RfidChipData {
+  /*
+   128-bit chip ID, big endian
+   */
+  uint8_t chip_id[16];
+  float freq;
+  float voltage;
+  float temp;
+  float time;
+} RfidChipData;
+
+/*
+ One entry of a tag's chain
+ */
+typedef struct RfidEntry {
+  /*
+   ID of the distributor that signed the entry
+   */
+  uint32_t key_id;
+  /*
+   One of the `RFID_ALGORITHM_*` constants
+   */
+  uint8_t algorithm;
+  /*
+   Borrowed from the tag
+   */
+  const uint8_t *signature;
+  size_t signature_len;
+} RfidEntry;
+
+/*
+ One measurement from the chip data's measurement section
+ */
+typedef struct RfidMeasurement {
+  /*
+   One of the `RFID_MEASUREMENT_*` constants, or a type added after this library
+   */
+  uint8_t type_id;
+  /*
+   Whether `type_id` is known to this library, only then is `value` set
+   */
+  bool registered;
+  /*
+   Value in the unit of its type
+   */
+  float value;
+  /*
+   Encoded value, borrowed from the tag
+   */
+  const uint8_t *raw;
+  size_t raw_len;
+} RfidMeasurement;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Parse a tag in any supported format.
+
+ The size, header, entry count, CRC and chip data are all checked. On success `*out` holds a
+ tag the caller must release with `rfid_tag_free`.
+
+ # Safety
+ `bytes` must point to `len` readable bytes and `out` must be a valid pointer.
+ */
+enum RfidStatus rfid_tag_parse(const uint8_t *bytes, size_t len, struct RfidTag **out);
+
+/*
+ Create a tag with no entries, written in the `RFID_TAG_VERSION_*` format `version`.
+
+ On success `*out` holds a tag the caller must release with `rfid_tag_free`.
+
+ # Safety
+ `chip_data` and `out` must be valid pointers.
+ */
+enum RfidStatus rfid_tag_new(const struct RfidChipData *chip_data,
+                             uint8_t version,
+                             struct RfidTag **out);
+
+/*
+ Release a tag, null is ignored.
+
+ # Safety
+ `tag` must be null or a tag from this library that hasn't been freed yet.
+ */
+void rfid_tag_free(struct RfidTag *tag);
+
+/*
+ # Safety
+ `tag` and `out` must be valid pointers.
+ */
+enum RfidStatus rfid_tag_chip_data(const struct RfidTag *tag, struct RfidChipData *out);
+
+/*
+ Number of entries on the tag, zero if `tag` is null.
+
+ # Safety
+ `tag` must be null or a valid pointer.
+ */
+size_t rfid_tag_entry_count(const struct RfidTag *tag);
+
+/*
+ Read the entry at `index`, the first entry was signed first.
+
+ # Safety
+ `tag` and `out` must be valid pointers.
+ */
+enum RfidStatus rfid_tag_entry(const struct RfidTag *tag, size_t index, struct RfidEntry *out);
+
+/*
+ Number of measurements in the tag's chip data, zero if `tag` is null.
+
+ # Safety
+ `tag` must be null or a valid pointer.
+ */
+size_t rfid_tag_measurement_count(const struct RfidTag *tag);
+
+/*
+ Read the measurement at `index`, in the order they are stored.
+
+ # Safety
+ `tag` and `out` must be valid pointers.
+ */
+enum RfidStatus rfid_tag_measurement(const struct RfidTag *tag,
+                                     size_t index,
+                                     struct RfidMeasurement *out);
+
+/*
+ Append a measurement of type `type_id`, one of the `RFID_MEASUREMENT_*` constants, to the
+ chip data, and update the tag's CRC.
+
+ Only a tag without entries can be measured, the first entry signs the chip data. A tag with
+ measurements is always written in version 1.
+
+ # Safety
+ `tag` must be a valid pointer.
+ */
+enum RfidStatus rfid_tag_add_measurement(struct RfidTag *tag, uint8_t type_id, float value);
+
+/*
+ CRC the tag should have, computed from its contents. Zero if `tag` is null.
+
+ # Safety
+ `tag` must be null or a valid pointer.
+ */
+uint16_t rfid_tag_calc_crc(const struct RfidTag *tag);
+
+/*
+ Whether the tag's stored CRC matches its contents, false if `tag` is null.
+
+ # Safety
+ `tag` must be null or a valid pointer.
+ */
+bool rfid_tag_valid_crc(const struct RfidTag *tag);
+
+/*
+ Create an empty key table, release it with `rfid_key_table_free`
+ */
+struct RfidKeyTable *rfid_key_table_new(void);
+
+/*
+ Release a key table, null is ignored.
+
+ # Safety
+ `table` must be null or a key table from this library that hasn't been freed yet.
+ */
+void rfid_key_table_free(struct RfidKeyTable *table);
+
+/*
+ Add a distributor's PEM public key, replacing any key with the same ID.
+
+ The algorithm is detected from the key.
+
+ # Safety
+ `table` must be a valid pointer and `pem` must point to `pem_len` readable bytes.
+ */
+enum RfidStatus rfid_key_table_add(struct RfidKeyTable *table,
+                                   uint32_t key_id,
+                                   const uint8_t *pem,
+                                   size_t pem_len);
+
+/*
+ Receiving inspection of a tag addressed to `recipient_id`.
+
+ Checks the CRC, that every signer is in `keys` and that every signature verifies, with the last
+ entry signed over to `recipient_id`. If a signature fails its index is written to
+ `failed_index`, which may be null.
+
+ # Safety
+ `tag` and `keys` must be valid pointers, `failed_index` must be null or a valid pointer.
+ */
+enum RfidStatus rfid_tag_validate(const struct RfidTag *tag,
+                                  const struct RfidKeyTable *keys,
+                                  uint32_t recipient_id,
+                                  size_t *failed_index);
+
+/*
+ Bytes the next entry has to sign.
+
+ `holder_id` is the distributor adding the entry, which the tag was signed over to, and
+ `next_id` the distributor it is signed over to next. Both must be in `keys`. The data is copied
+ to `buffer` and its length written to `written`. If `buffer` is null or shorter than the data,
+ only the length is written and `RFID_STATUS_BUFFER_TOO_SMALL` is returned.
+
+ # Safety
+ `tag`, `keys` and `written` must be valid pointers, `buffer` must be null or point to
+ `buffer_len` writable bytes.
+ */
+enum RfidStatus rfid_tag_signing_data(const struct RfidTag *tag,
+                                      const struct RfidKeyTable *keys,
+                                      uint32_t holder_id,
+                                      uint32_t next_id,
+                                      uint8_t *buffer,
+                                      size_t buffer_len,
+                                      size_t *written);
+
+/*
+ Append an entry signed over the data from `rfid_tag_signing_data`, and update the tag's CRC.
+
+ The signature is copied and isn't verified, use `rfid_tag_validate` with the next distributor
+ as the recipient to check it. An entry that would make the encoded tag larger than
+ `RFID_MAX_TAG_SIZE`, which no reader could parse, is refused and the tag left unchanged.
+
+ # Safety
+ `tag` must be a valid pointer and `signature` must point to `signature_len` readable bytes.
+ */
+enum RfidStatus rfid_tag_append_entry(struct RfidTag *tag,
+                                      uint32_t key_id,
+                                      uint8_t algorithm,
+                                      const uint8_t *signature,
+                                      size_t signature_len);
+
+/*
+ Encode the tag in its format, ready to be written to the chip.
+
+ The CRC is recomputed. Buffer handling is the same as `rfid_tag_signing_data`.
+
+ # Safety
+ `tag` and `written` must be valid pointers, `buffer` must be null or point to `buffer_len`
+ writable bytes.
+ */
+enum RfidStatus rfid_tag_encode(const struct RfidTag *tag,
+                                uint8_t *buffer,
+                                size_t buffer_len,
+                                size_t *written);
+
+/*
+ Static, nul terminated description of a status, never freed by the caller.
+
+ Takes the status as an integer, values that aren't an `RfidStatus` are described as unknown.
+ */
+const char *rfid_status_message(uint32_t status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RFID_TAG_H */
@@ -0,0 +1,999 @@
+//! C interface for parsing, verifying and updating tags.
+//!
+//! `include/rfid_tag.h` is generated from this file, so the comments here are the documentation C
+//! callers see. The ownership rules are the same for every function:
+//!
+//! * Objects returned through an `out` pointer belong to the caller, who releases them with the
+//!   matching `_free` function.
+//! * Pointers passed in are only borrowed for the duration of the call.
+//! * Pointers inside structs filled in by the library borrow from the object they were read from,
+//!   and stay valid until that object is modified or freed.
+//!
+//! Every fallible function returns an `RfidStatus`, `RFID_STATUS_OK` is zero. Output parameters are
+//! only written on success, except for the length of buffers that were too small. A panic inside
+//! the library never unwinds into the caller, it is reported as `RFID_STATUS_INTERNAL`.
+
+use models::algorithm::SignatureAlgorithm;
+use models::chip_data::ChipData;
+use models::error::{InspectionError, RfidDataParseError};
+use models::key::PublicKey;
+use models::measurement::{Measurement, MeasurementType};
+use models::rfid::RfidData;
+use models::supply_chain::SupplyChainEntry;
+use models::tag_format::{self, TagVersion, MAX_TAG_SIZE};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// RSA-2048 PKCS#1 v1.5 over SHA3-256, 256 byte signatures
+pub const RFID_ALGORITHM_RSA_SHA3: u8 = 0;
+/// Ed25519, 64 byte signatures
+pub const RFID_ALGORITHM_ED25519: u8 = 1;
+/// ECDSA on P-256 over SHA-256, 64 byte signatures holding r and s
+pub const RFID_ALGORITHM_ECDSA_P256: u8 = 2;
+
+/// Tags written before the format header existed
+pub const RFID_TAG_VERSION_LEGACY: u8 = 0;
+pub const RFID_TAG_VERSION_1: u8 = 1;
+
+/// Largest encoded tag the parser accepts, in bytes
+pub const RFID_MAX_TAG_SIZE: usize = 8192;
+
+/// Frequency of one of the chip's ring oscillators, in Hz
+pub const RFID_MEASUREMENT_RING_OSCILLATOR: u8 = 1;
+/// Supply current with the chip idle, in A
+pub const RFID_MEASUREMENT_LEAKAGE_CURRENT: u8 = 2;
+/// Delay of a critical path, in s
+pub const RFID_MEASUREMENT_PATH_DELAY: u8 = 3;
+/// Lowest supply voltage the chip still works at, in V
+pub const RFID_MEASUREMENT_MINIMUM_VOLTAGE: u8 = 4;
+/// Die temperature the other measurements were taken at, in degrees Celsius
+pub const RFID_MEASUREMENT_TEMPERATURE: u8 = 5;
+
+/// Result of a library call. Values are stable and new ones are only ever added at the end.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RfidStatus {
+    Ok = 0,
+    /// A required pointer argument was null
+    NullPointer = 1,
+    /// The tag is larger than the parser accepts, or would be with the entry being appended
+    TooLarge = 2,
+    /// The tag ends before its header and chip data do
+    TruncatedHeader = 3,
+    /// The tag ends before the number of entries in its header
+    EntryCountMismatch = 4,
+    /// The tag has bytes after its last entry
+    TrailingBytes = 5,
+    InvalidCrc = 6,
+    /// The tag format version, or a requested version, isn't supported
+    UnsupportedVersion = 7,
+    UnsupportedFlags = 8,
+    UnknownAlgorithm = 9,
+    /// A chip data measurement is NaN or infinite
+    InvalidFloat = 10,
+    /// An entry was signed by, or signed over to, a distributor missing from the key table
+    UnknownDistributor = 11,
+    /// An entry's signature doesn't verify, or has the wrong size for its algorithm
+    InvalidSignature = 12,
+    /// The last entry isn't signed over to the expected recipient
+    WrongRecipient = 13,
+    /// A public key couldn't be parsed or isn't supported
+    InvalidKey = 14,
+    IndexOutOfRange = 15,
+    /// The output buffer is null or too small, the required length was written
+    BufferTooSmall = 16,
+    /// The chip data's measurement section is malformed or holds a value that isn't finite
+    InvalidMeasurement = 17,
+    /// The tag has entries, which signed its chip data, so the chip data can't change
+    ChipDataSigned = 18,
+    /// The library hit a bug, no output was written
+    Internal = 19,
+}
+
+impl RfidStatus {
+    /// Every status, in order of value
+    const ALL: [RfidStatus; 20] = [
+        RfidStatus::Ok,
+        RfidStatus::NullPointer,
+        RfidStatus::TooLarge,
+        RfidStatus::TruncatedHeader,
+        RfidStatus::EntryCountMismatch,
+        RfidStatus::TrailingBytes,
+        RfidStatus::InvalidCrc,
+        RfidStatus::UnsupportedVersion,
+        RfidStatus::UnsupportedFlags,
+        RfidStatus::UnknownAlgorithm,
+        RfidStatus::InvalidFloat,
+        RfidStatus::UnknownDistributor,
+        RfidStatus::InvalidSignature,
+        RfidStatus::WrongRecipient,
+        RfidStatus::InvalidKey,
+        RfidStatus::IndexOutOfRange,
+        RfidStatus::BufferTooSmall,
+        RfidStatus::InvalidMeasurement,
+        RfidStatus::ChipDataSigned,
+        RfidStatus::Internal,
+    ];
+
+    /// The status with this value, C callers can pass any integer
+    fn from_raw(status: u32) -> Option<Self> {
+        Self::ALL.get(status as usize).copied()
+    }
+}
+
+impl From<RfidDataParseError> for RfidStatus {
+    fn from(e: RfidDataParseError) -> Self {
+        match e {
+            RfidDataParseError::TooLarge { .. } => RfidStatus::TooLarge,
+            RfidDataParseError::TruncatedHeader { .. } => RfidStatus::TruncatedHeader,
+            RfidDataParseError::EntryCountMismatch { .. }
+            | RfidDataParseError::MissingCheckpointEntry => RfidStatus::EntryCountMismatch,
+            RfidDataParseError::TrailingBytes(_) => RfidStatus::TrailingBytes,
+            RfidDataParseError::InvalidCrc { .. } => RfidStatus::InvalidCrc,
+            RfidDataParseError::UnsupportedVersion(_) => RfidStatus::UnsupportedVersion,
+            RfidDataParseError::UnsupportedFlags(_) => RfidStatus::UnsupportedFlags,
+            RfidDataParseError::UnknownAlgorithm(_) => RfidStatus::UnknownAlgorithm,
+            RfidDataParseError::InvalidFloat(_) => RfidStatus::InvalidFloat,
+            RfidDataParseError::MalformedMeasurements
+            | RfidDataParseError::InvalidMeasurement(_) => RfidStatus::InvalidMeasurement,
+        }
+    }
+}
+
+impl From<&InspectionError> for RfidStatus {
+    fn from(e: &InspectionError) -> Self {
+        match e {
+            InspectionError::InvalidCrc { .. } => RfidStatus::InvalidCrc,
+            InspectionError::UnknownDistributor(_) => RfidStatus::UnknownDistributor,
+            InspectionError::InvalidSignature(_) => RfidStatus::InvalidSignature,
+            InspectionError::WrongRecipient(_) => RfidStatus::WrongRecipient,
+        }
+    }
+}
+
+/// Contents of a tag, created by `rfid_tag_parse` or `rfid_tag_new`
+pub struct RfidTag {
+    data: RfidData,
+}
+
+/// Distributor public keys by key ID, created by `rfid_key_table_new`
+pub struct RfidKeyTable {
+    keys: HashMap<u32, PublicKey>,
+}
+
+/// Measurements of the chip a tag is attached to
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RfidChipData {
+    /// 128-bit chip ID, big endian
+    pub chip_id: [u8; 16],
+    pub freq: f32,
+    pub voltage: f32,
+    pub temp: f32,
+    pub time: f32,
+}
+
+/// One entry of a tag's chain
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RfidEntry {
+    /// ID of the distributor that signed the entry
+    pub key_id: u32,
+    /// One of the `RFID_ALGORITHM_*` constants
+    pub algorithm: u8,
+    /// Borrowed from the tag
+    pub signature: *const u8,
+    pub signature_len: usize,
+}
+
+/// One measurement from the chip data's measurement section
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RfidMeasurement {
+    /// One of the `RFID_MEASUREMENT_*` constants, or a type added after this library
+    pub type_id: u8,
+    /// Whether `type_id` is known to this library, only then is `value` set
+    pub registered: bool,
+    /// Value in the unit of its type
+    pub value: f32,
+    /// Encoded value, borrowed from the tag
+    pub raw: *const u8,
+    pub raw_len: usize,
+}
+
+/// Run the body of an exported function, returning `fallback` if it panics. Unwinding into C is
+/// undefined behavior.
+fn catch_panic<T>(fallback: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(fallback)
+}
+
+/// Borrow `len` bytes at `data`, a null pointer is only allowed for an empty slice
+unsafe fn slice<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    if data.is_null() {
+        if len == 0 {
+            Some(&[])
+        } else {
+            None
+        }
+    } else {
+        Some(std::slice::from_raw_parts(data, len))
+    }
+}
+
+/// Copy `bytes` to a caller supplied buffer, always reporting the length needed
+unsafe fn write_buffer(
+    bytes: &[u8],
+    buffer: *mut u8,
+    buffer_len: usize,
+    written: *mut usize,
+) -> RfidStatus {
+    if written.is_null() {
+        return RfidStatus::NullPointer;
+    }
+
+    *written = bytes.len();
+    if buffer.is_null() || buffer_len < bytes.len() {
+        return RfidStatus::BufferTooSmall;
+    }
+
+    ptr::copy_nonoverlapping(bytes.as_ptr(), buffer, bytes.len());
+    RfidStatus::Ok
+}
+
+fn tag_version(version: u8) -> Option<TagVersion> {
+    match version {
+        RFID_TAG_VERSION_LEGACY => Some(TagVersion::Legacy),
+        RFID_TAG_VERSION_1 => Some(TagVersion::V1),
+        _ => None,
+    }
+}
+
+/// Parse a tag in any supported format.
+///
+/// The size, header, entry count, CRC and chip data are all checked. On success `*out` holds a
+/// tag the caller must release with `rfid_tag_free`.
+///
+/// # Safety
+/// `bytes` must point to `len` readable bytes and `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_parse(
+    bytes: *const u8,
+    len: usize,
+    out: *mut *mut RfidTag,
+) -> RfidStatus {
+    catch_panic(RfidStatus::Internal, || {
+        let bytes = match slice(bytes, len) {
+            Some(bytes) if !out.is_null() => bytes,
+            _ => return RfidStatus::NullPointer,
+        };
+
+        match RfidData::try_from(bytes.to_vec()) {
+            Ok(data) => {
+                *out = Box::into_raw(Box::new(RfidTag { data }));
+                RfidStatus::Ok
+            }
+            Err(e) => e.into(),
+        }
+    })
+}
+
+/// Create a tag with no entries, written in the `RFID_TAG_VERSION_*` format `version`.
+///
+/// On success `*out` holds a tag the caller must release with `rfid_tag_free`.
+///
+/// # Safety
+/// `chip_data` and `out` must be valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_new(
+    chip_data: *const RfidChipData,
+    version: u8,
+    out: *mut *mut RfidTag,
+) -> RfidStatus {
+    catch_panic(RfidStatus::Internal, || {
+        if chip_data.is_null() || out.is_null() {
+            return RfidStatus::NullPointer;
+        }
+
+        let version = match tag_version(version) {
+            Some(version) => version,
+            None => return RfidStatus::UnsupportedVersion,
+        };
+
+        let chip_data = &*chip_data;
+        let measurements = [
+            chip_data.freq,
+            chip_data.voltage,
+            chip_data.temp,
+            chip_data.time,
+        ];
+        if !measurements.iter().all(|value| value.is_finite()) {
+            return RfidStatus::InvalidFloat;
+        }
+
+        let mut data = RfidData {
+            chip_data: ChipData {
+                chip_id: u128::from_be_bytes(chip_data.chip_id),
+                freq: chip_data.freq,
+                voltage: chip_data.voltage,
+                temp: chip_data.temp,
+                time: chip_data.time,
+                measurements: Vec::new(),
+            },
+            version,
+            ..Default::default()
+        };
+        data.crc = data.calc_crc();
+
+        *out = Box::into_raw(Box::new(RfidTag { data }));
+        RfidStatus::Ok
+    })
+}
+
+/// Release a tag, null is ignored.
+///
+/// # Safety
+/// `tag` must be null or a tag from this library that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_free(tag: *mut RfidTag) {
+    catch_panic((), || {
+        if !tag.is_null() {
+            drop(Box::from_raw(tag));
+        }
+    })
+}
+
+/// # Safety
+/// `tag` and `out` must be valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_chip_data(
+    tag: *const RfidTag,
+    out: *mut RfidChipData,
+) -> RfidStatus {
+    catch_panic(RfidStatus::Internal, || {
+        if tag.is_null() || out.is_null() {
+            return RfidStatus::NullPointer;
+        }
+
+        let chip_data = &(*tag).data.chip_data;
+        *out = RfidChipData {
+            chip_id: chip_data.chip_id.to_be_bytes(),
+            freq: chip_data.freq,
+            voltage: chip_data.voltage,
+            temp: chip_data.temp,
+            time: chip_data.time,
+        };
+
+        RfidStatus::Ok
+    })
+}
+
+/// Number of entries on the tag, zero if `tag` is null.
+///
+/// # Safety
+/// `tag` must be null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_entry_count(tag: *const RfidTag) -> usize {
+    catch_panic(0, || {
+        if tag.is_null() {
+            0
+        } else {
+            (*tag).data.entries.len()
+        }
+    })
+}
+
+/// Read the entry at `index`, the first entry was signed first.
+///
+/// # Safety
+/// `tag` and `out` must be valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_entry(
+    tag: *const RfidTag,
+    index: usize,
+    out: *mut RfidEntry,
+) -> RfidStatus {
+    catch_panic(RfidStatus::Internal, || {
+        if tag.is_null() || out.is_null() {
+            return RfidStatus::NullPointer;
+        }
+
+        let entries = &(*tag).data.entries;
+        match entries.get(index) {
+            Some(entry) => {
+                *out = RfidEntry {
+                    key_id: entry.pub_key,
+                    algorithm: entry.algorithm.id(),
+                    signature: entry.signature.as_ptr(),
+                    signature_len: entry.signature.len(),
+                };
+                RfidStatus::Ok
+            }
+            None => RfidStatus::IndexOutOfRange,
+        }
+    })
+}
+
+/// Number of measurements in the tag's chip data, zero if `tag` is null.
+///
+/// # Safety
+/// `tag` must be null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_measurement_count(tag: *const RfidTag) -> usize {
+    catch_panic(0, || {
+        if tag.is_null() {
+            0
+        } else {
+            (*tag).data.chip_data.measurements.len()
+        }
+    })
+}
+
+/// Read the measurement at `index`, in the order they are stored.
+///
+/// # Safety
+/// `tag` and `out` must be valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_measurement(
+    tag: *const RfidTag,
+    index: usize,
+    out: *mut RfidMeasurement,
+) -> RfidStatus {
+    catch_panic(RfidStatus::Internal, || {
+        if tag.is_null() || out.is_null() {
+            return RfidStatus::NullPointer;
+        }
+
+        let measurements = &(*tag).data.chip_data.measurements;
+        match measurements.get(index) {
+            Some(measurement) => {
+                let raw = measurement.raw_value();
+                *out = RfidMeasurement {
+                    type_id: measurement.type_id,
+                    registered: measurement.value().is_some(),
+                    value: measurement.value().unwrap_or_default(),
+                    raw: raw.as_ptr(),
+                    raw_len: raw.len(),
+                };
+                RfidStatus::Ok
+            }
+            None => RfidStatus::IndexOutOfRange,
+        }
+    })
+}
+
+/// Append a measurement of type `type_id`, one of the `RFID_MEASUREMENT_*` constants, to the
+/// chip data, and update the tag's CRC.
+///
+/// Only a tag without entries can be measured, the first entry signs the chip data. A tag with
+/// measurements is always written in version 1.
+///
+/// # Safety
+/// `tag` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_add_measurement(
+    tag: *mut RfidTag,
+    type_id: u8,
+    value: f32,
+) -> RfidStatus {
+    catch_panic(RfidStatus::Internal, || {
+        if tag.is_null() {
+            return RfidStatus::NullPointer;
+        }
+
+        let data = &mut (*tag).data;
+        if !data.entries.is_empty() {
+            return RfidStatus::ChipDataSigned;
+        }
+
+        match MeasurementType::from_id(type_id) {
+            Some(kind) if value.is_finite() => {
+                data.chip_data
+                    .measurements
+                    .push(Measurement::new(kind, value));
+                data.crc = data.calc_crc();
+                RfidStatus::Ok
+            }
+            _ => RfidStatus::InvalidMeasurement,
+        }
+    })
+}
+
+/// CRC the tag should have, computed from its contents. Zero if `tag` is null.
+///
+/// # Safety
+/// `tag` must be null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_calc_crc(tag: *const RfidTag) -> u16 {
+    catch_panic(0, || {
+        if tag.is_null() {
+            0
+        } else {
+            (*tag).data.calc_crc()
+        }
+    })
+}
+
+/// Whether the tag's stored CRC matches its contents, false if `tag` is null.
+///
+/// # Safety
+/// `tag` must be null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_valid_crc(tag: *const RfidTag) -> bool {
+    catch_panic(false, || !tag.is_null() && (*tag).data.valid_crc())
+}
+
+/// Create an empty key table, release it with `rfid_key_table_free`
+#[no_mangle]
+pub extern "C" fn rfid_key_table_new() -> *mut RfidKeyTable {
+    catch_panic(ptr::null_mut(), || {
+        Box::into_raw(Box::new(RfidKeyTable {
+            keys: HashMap::new(),
+        }))
+    })
+}
+
+/// Release a key table, null is ignored.
+///
+/// # Safety
+/// `table` must be null or a key table from this library that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_key_table_free(table: *mut RfidKeyTable) {
+    catch_panic((), || {
+        if !table.is_null() {
+            drop(Box::from_raw(table));
+        }
+    })
+}
+
+/// Add a distributor's PEM public key, replacing any key with the same ID.
+///
+/// The algorithm is detected from the key.
+///
+/// # Safety
+/// `table` must be a valid pointer and `pem` must point to `pem_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_key_table_add(
+    table: *mut RfidKeyTable,
+    key_id: u32,
+    pem: *const u8,
+    pem_len: usize,
+) -> RfidStatus {
+    catch_panic(RfidStatus::Internal, || {
+        let pem = match slice(pem, pem_len) {
+            Some(pem) if !table.is_null() => pem,
+            _ => return RfidStatus::NullPointer,
+        };
+
+        let public_key = match PublicKey::new(key_id, pem.to_vec(), key_id.to_string()) {
+            Ok(public_key) => public_key,
+            Err(_) => return RfidStatus::InvalidKey,
+        };
+
+        (*table).keys.insert(key_id, public_key);
+
+        RfidStatus::Ok
+    })
+}
+
+/// Receiving inspection of a tag addressed to `recipient_id`.
+///
+/// Checks the CRC, that every signer is in `keys` and that every signature verifies, with the last
+/// entry signed over to `recipient_id`. If a signature fails its index is written to
+/// `failed_index`, which may be null.
+///
+/// # Safety
+/// `tag` and `keys` must be valid pointers, `failed_index` must be null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_validate(
+    tag: *const RfidTag,
+    keys: *const RfidKeyTable,
+    recipient_id: u32,
+    failed_index: *mut usize,
+) -> RfidStatus {
+    catch_panic(RfidStatus::Internal, || {
+        if tag.is_null() || keys.is_null() {
+            return RfidStatus::NullPointer;
+        }
+
+        let (data, keys) = (&(*tag).data, &(*keys).keys);
+        let recipient = match keys.get(&recipient_id) {
+            Some(recipient) => recipient,
+            None => return RfidStatus::UnknownDistributor,
+        };
+
+        match data.inspect(keys, recipient) {
+            Ok(()) => RfidStatus::Ok,
+            Err(e) => {
+                let index = match e {
+                    InspectionError::InvalidSignature(index) => Some(index),
+                    InspectionError::WrongRecipient(_) => Some(data.entries.len() - 1),
+                    _ => None,
+                };
+
+                if let (Some(index), false) = (index, failed_index.is_null()) {
+                    *failed_index = index;
+                }
+
+                (&e).into()
+            }
+        }
+    })
+}
+
+/// Bytes the next entry has to sign.
+///
+/// `holder_id` is the distributor adding the entry, which the tag was signed over to, and
+/// `next_id` the distributor it is signed over to next. Both must be in `keys`. The data is copied
+/// to `buffer` and its length written to `written`. If `buffer` is null or shorter than the data,
+/// only the length is written and `RFID_STATUS_BUFFER_TOO_SMALL` is returned.
+///
+/// # Safety
+/// `tag`, `keys` and `written` must be valid pointers, `buffer` must be null or point to
+/// `buffer_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_signing_data(
+    tag: *const RfidTag,
+    keys: *const RfidKeyTable,
+    holder_id: u32,
+    next_id: u32,
+    buffer: *mut u8,
+    buffer_len: usize,
+    written: *mut usize,
+) -> RfidStatus {
+    catch_panic(RfidStatus::Internal, || {
+        if tag.is_null() || keys.is_null() {
+            return RfidStatus::NullPointer;
+        }
+
+        let (data, keys) = (&(*tag).data, &(*keys).keys);
+        let (holder, next) = match (keys.get(&holder_id), keys.get(&next_id)) {
+            (Some(holder), Some(next)) => (holder, next),
+            _ => return RfidStatus::UnknownDistributor,
+        };
+
+        match data.next_link_data(keys, holder) {
+            Ok(mut signing_data) => {
+                signing_data.extend_from_slice(&next.key);
+                write_buffer(&signing_data, buffer, buffer_len, written)
+            }
+            Err(_) => RfidStatus::UnknownDistributor,
+        }
+    })
+}
+
+/// Append an entry signed over the data from `rfid_tag_signing_data`, and update the tag's CRC.
+///
+/// The signature is copied and isn't verified, use `rfid_tag_validate` with the next distributor
+/// as the recipient to check it. An entry that would make the encoded tag larger than
+/// `RFID_MAX_TAG_SIZE`, which no reader could parse, is refused and the tag left unchanged.
+///
+/// # Safety
+/// `tag` must be a valid pointer and `signature` must point to `signature_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_append_entry(
+    tag: *mut RfidTag,
+    key_id: u32,
+    algorithm: u8,
+    signature: *const u8,
+    signature_len: usize,
+) -> RfidStatus {
+    catch_panic(RfidStatus::Internal, || {
+        let signature = match slice(signature, signature_len) {
+            Some(signature) if !tag.is_null() => signature,
+            _ => return RfidStatus::NullPointer,
+        };
+
+        let algorithm = match SignatureAlgorithm::from_id(algorithm) {
+            Some(algorithm) => algorithm,
+            None => return RfidStatus::UnknownAlgorithm,
+        };
+
+        if signature.len() != algorithm.signature_size() {
+            return RfidStatus::InvalidSignature;
+        }
+
+        let data = &mut (*tag).data;
+        data.entries.push(SupplyChainEntry {
+            pub_key: key_id,
+            signature: signature.to_vec(),
+            algorithm,
+        });
+        if tag_format::encode(data).len() > MAX_TAG_SIZE {
+            data.entries.pop();
+            return RfidStatus::TooLarge;
+        }
+        data.crc = data.calc_crc();
+
+        RfidStatus::Ok
+    })
+}
+
+/// Encode the tag in its format, ready to be written to the chip.
+///
+/// The CRC is recomputed. Buffer handling is the same as `rfid_tag_signing_data`.
+///
+/// # Safety
+/// `tag` and `written` must be valid pointers, `buffer` must be null or point to `buffer_len`
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rfid_tag_encode(
+    tag: *const RfidTag,
+    buffer: *mut u8,
+    buffer_len: usize,
+    written: *mut usize,
+) -> RfidStatus {
+    catch_panic(RfidStatus::Internal, || {
+        if tag.is_null() {
+            return RfidStatus::NullPointer;
+        }
+
+        let bytes: Vec<u8> = (*tag).data.clone().into();
+        write_buffer(&bytes, buffer, buffer_len, written)
+    })
+}
+
+/// Static, nul terminated description of a status, never freed by the caller.
+///
+/// Takes the status as an integer, values that aren't an `RfidStatus` are described as unknown.
+#[no_mangle]
+pub extern "C" fn rfid_status_message(status: u32) -> *const c_char {
+    catch_panic(ptr::null(), || {
+        let status = match RfidStatus::from_raw(status) {
+            Some(status) => status,
+            None => return b"Unknown status\0".as_ptr() as *const c_char,
+        };
+
+        let message: &'static [u8] = match status {
+            RfidStatus::Ok => b"Ok\0",
+            RfidStatus::NullPointer => b"A required pointer was null\0",
+            RfidStatus::TooLarge => b"Tag is larger than the maximum tag size\0",
+            RfidStatus::TruncatedHeader => b"Tag is too short for its header\0",
+            RfidStatus::EntryCountMismatch => b"Tag ends before all of its entries\0",
+            RfidStatus::TrailingBytes => b"Tag has bytes after its last entry\0",
+            RfidStatus::InvalidCrc => b"Invalid CRC\0",
+            RfidStatus::UnsupportedVersion => b"Unsupported tag format version\0",
+            RfidStatus::UnsupportedFlags => b"Unsupported tag format flags\0",
+            RfidStatus::UnknownAlgorithm => b"Unknown signature algorithm\0",
+            RfidStatus::InvalidFloat => b"Chip data measurement is not a finite number\0",
+            RfidStatus::UnknownDistributor => b"No valid public key for a distributor\0",
+            RfidStatus::InvalidSignature => b"Invalid signature\0",
+            RfidStatus::WrongRecipient => {
+                b"Last entry does not designate the recipient as the next distributor\0"
+            }
+            RfidStatus::InvalidKey => b"Invalid or unsupported public key\0",
+            RfidStatus::IndexOutOfRange => b"Index out of range\0",
+            RfidStatus::BufferTooSmall => b"Buffer too small\0",
+            RfidStatus::InvalidMeasurement => b"Chip data measurement section is invalid\0",
+            RfidStatus::ChipDataSigned => b"The tag's chip data is already signed\0",
+            RfidStatus::Internal => b"Internal error in the tag library\0",
+        };
+
+        message.as_ptr() as *const c_char
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use models::signer::file_key::FileKeySigner;
+    use models::signer::Signer;
+    use models::test_util::{ed25519_keypairs, key_map, signed_tag};
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_status_values() {
+        for (value, status) in RfidStatus::ALL.iter().enumerate() {
+            assert_eq!(*status as usize, value);
+        }
+        assert_eq!(RfidStatus::from_raw(RfidStatus::ALL.len() as u32), None);
+    }
+
+    #[test]
+    fn test_header_up_to_date() {
+        assert!(
+            include_str!(concat!(env!("OUT_DIR"), "/rfid_tag.h"))
+                == include_str!("../include/rfid_tag.h"),
+            concat!(
+                "include/rfid_tag.h is out of date, copy the generated header from ",
+                env!("OUT_DIR"),
+                "/rfid_tag.h"
+            )
+        );
+    }
+
+    #[test]
+    fn test_max_tag_size() {
+        assert_eq!(RFID_MAX_TAG_SIZE, MAX_TAG_SIZE);
+    }
+
+    #[test]
+    fn test_catch_panic() {
+        assert_eq!(
+            catch_panic(RfidStatus::Internal, || -> RfidStatus { panic!("bug") }),
+            RfidStatus::Internal
+        );
+        assert_eq!(
+            unsafe { CStr::from_ptr(rfid_status_message(RfidStatus::Internal as u32)) }.to_str(),
+            Ok("Internal error in the tag library")
+        );
+    }
+
+    #[test]
+    fn test_append_too_large() {
+        let chip_data = RfidChipData {
+            chip_id: 42u128.to_be_bytes(),
+            freq: 5.0,
+            voltage: 5.0,
+            temp: 5.0,
+            time: 5.0,
+        };
+        let signature = [0u8; 64];
+
+        unsafe {
+            let mut tag = ptr::null_mut();
+            assert_eq!(
+                rfid_tag_new(&chip_data, RFID_TAG_VERSION_1, &mut tag),
+                RfidStatus::Ok
+            );
+
+            let append = |tag| {
+                rfid_tag_append_entry(
+                    tag,
+                    1,
+                    RFID_ALGORITHM_ED25519,
+                    signature.as_ptr(),
+                    signature.len(),
+                )
+            };
+            while append(tag) == RfidStatus::Ok {}
+            let count = rfid_tag_entry_count(tag);
+            assert_eq!(append(tag), RfidStatus::TooLarge);
+            assert_eq!(rfid_tag_entry_count(tag), count);
+            assert!(rfid_tag_valid_crc(tag));
+
+            let mut len = 0;
+            assert_eq!(
+                rfid_tag_encode(tag, ptr::null_mut(), 0, &mut len),
+                RfidStatus::BufferTooSmall
+            );
+            assert!(len <= MAX_TAG_SIZE);
+            assert!(len + 4 + signature.len() > MAX_TAG_SIZE);
+
+            rfid_tag_free(tag);
+        }
+    }
+
+    #[test]
+    fn test_ffi() {
+        let keypairs = ed25519_keypairs(4);
+        let pems: Vec<Vec<u8>> = keypairs
+            .iter()
+            .map(|keypair| keypair.public_key_to_pem().unwrap())
+            .collect();
+
+        // Distributors 0 and 1 have signed, the tag is held by distributor 2
+        let (data, _) = signed_tag(TagVersion::Legacy, &keypairs[..3]);
+        let key_map = key_map(&keypairs);
+        let bytes: Vec<u8> = data.into();
+
+        unsafe {
+            let mut tag = ptr::null_mut();
+            assert_eq!(
+                rfid_tag_parse(bytes.as_ptr(), bytes.len(), &mut tag),
+                RfidStatus::Ok
+            );
+            assert_eq!(rfid_tag_entry_count(tag), 2);
+            assert!(rfid_tag_valid_crc(tag));
+
+            let mut chip_data = std::mem::zeroed();
+            assert_eq!(rfid_tag_chip_data(tag, &mut chip_data), RfidStatus::Ok);
+            assert_eq!(u128::from_be_bytes(chip_data.chip_id), 42);
+
+            assert_eq!(rfid_tag_measurement_count(tag), 0);
+            assert_eq!(
+                rfid_tag_add_measurement(tag, RFID_MEASUREMENT_PATH_DELAY, 1e-9),
+                RfidStatus::ChipDataSigned
+            );
+
+            let mut entry = std::mem::zeroed();
+            assert_eq!(rfid_tag_entry(tag, 1, &mut entry), RfidStatus::Ok);
+            assert_eq!(entry.key_id, 1);
+            assert_eq!(entry.algorithm, RFID_ALGORITHM_ED25519);
+            assert_eq!(entry.signature_len, 64);
+            assert_eq!(
+                rfid_tag_entry(tag, 2, &mut entry),
+                RfidStatus::IndexOutOfRange
+            );
+
+            let keys = rfid_key_table_new();
+            for (id, pem) in pems.iter().enumerate() {
+                assert_eq!(
+                    rfid_key_table_add(keys, id as u32, pem.as_ptr(), pem.len()),
+                    RfidStatus::Ok
+                );
+            }
+            assert_eq!(
+                rfid_key_table_add(keys, 9, b"nope".as_ptr(), 4),
+                RfidStatus::InvalidKey
+            );
+
+            let mut failed_index = usize::MAX;
+            assert_eq!(
+                rfid_tag_validate(tag, keys, 2, &mut failed_index),
+                RfidStatus::Ok
+            );
+            assert_eq!(
+                rfid_tag_validate(tag, keys, 3, &mut failed_index),
+                RfidStatus::WrongRecipient
+            );
+            assert_eq!(failed_index, 1);
+
+            // Distributor 2 signs the chip over to distributor 3
+            let mut len = 0;
+            assert_eq!(
+                rfid_tag_signing_data(tag, keys, 2, 3, ptr::null_mut(), 0, &mut len),
+                RfidStatus::BufferTooSmall
+            );
+            let mut signing_data = vec![0u8; len];
+            assert_eq!(
+                rfid_tag_signing_data(tag, keys, 2, 3, signing_data.as_mut_ptr(), len, &mut len),
+                RfidStatus::Ok
+            );
+
+            let signer = FileKeySigner::from_pkey(keypairs[2].clone()).unwrap();
+            let signature = signer.sign(&[&signing_data]).unwrap();
+            assert_eq!(
+                rfid_tag_append_entry(tag, 2, 7, signature.as_ptr(), signature.len()),
+                RfidStatus::UnknownAlgorithm
+            );
+            assert_eq!(
+                rfid_tag_append_entry(
+                    tag,
+                    2,
+                    RFID_ALGORITHM_ED25519,
+                    signature.as_ptr(),
+                    signature.len()
+                ),
+                RfidStatus::Ok
+            );
+            assert!(rfid_tag_valid_crc(tag));
+            assert_eq!(
+                rfid_tag_validate(tag, keys, 3, ptr::null_mut()),
+                RfidStatus::Ok
+            );
+
+            let mut encoded = vec![0u8; 1024];
+            assert_eq!(
+                rfid_tag_encode(tag, encoded.as_mut_ptr(), encoded.len(), &mut len),
+                RfidStatus::Ok
+            );
+            encoded.truncate(len);
+
+            let parsed = RfidData::try_from(encoded.clone()).unwrap();
+            assert!(parsed.validate_chain(&key_map, key_map[&3].clone()).is_ok());
+
+            encoded[10] ^= 1;
+            let mut corrupted = ptr::null_mut();
+            assert_eq!(
+                rfid_tag_parse(encoded.as_ptr(), encoded.len(), &mut corrupted),
+                RfidStatus::InvalidCrc
+            );
+            assert!(corrupted.is_null());
+
+            assert_eq!(
+                CStr::from_ptr(rfid_status_message(RfidStatus::InvalidCrc as u32)).to_str(),
+                Ok("Invalid CRC")
+            );
+            assert_eq!(
+                CStr::from_ptr(rfid_status_message(u32::MAX)).to_str(),
+                Ok("Unknown status")
+            );
+
+            rfid_key_table_free(keys);
+            rfid_tag_free(tag);
+        }
+    }
+}
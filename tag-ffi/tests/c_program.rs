@@ -0,0 +1,35 @@
+//! Compile `round_trip.c` against the checked in header, link it to the shared library and run it
+
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_c_program() {
+    let crate_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("round_trip");
+
+    // Test binaries live in deps/, next to the directory holding the library
+    let lib_dir = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .and_then(|deps| deps.parent())
+        .unwrap()
+        .to_path_buf();
+
+    let status = Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-I"])
+        .arg(crate_dir.join("include"))
+        .arg(crate_dir.join("tests/round_trip.c"))
+        .arg("-o")
+        .arg(&out)
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lrfid_tag")
+        .status()
+        .expect("Unable to run the C compiler");
+    assert!(status.success(), "round_trip.c failed to build");
+
+    let status = Command::new(&out).status().unwrap();
+    assert!(status.success(), "round_trip.c failed");
+}
@@ -0,0 +1,89 @@
+/* Builds a tag through the C API and parses it back, run by tests/c_program.rs */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "rfid_tag.h"
+
+#define CHECK(call)                                                            \
+  do {                                                                         \
+    enum RfidStatus status = (call);                                           \
+    if (status != RFID_STATUS_OK) {                                            \
+      fprintf(stderr, "%s: %s\n", #call, rfid_status_message(status));         \
+      return 1;                                                                \
+    }                                                                          \
+  } while (0)
+
+int main(void) {
+  RfidChipData chip_data;
+  memset(&chip_data, 0, sizeof(chip_data));
+  chip_data.chip_id[15] = 42;
+  chip_data.freq = 5.0f;
+  chip_data.voltage = 5.0f;
+  chip_data.temp = 5.0f;
+  chip_data.time = 5.0f;
+
+  RfidTag *tag = NULL;
+  CHECK(rfid_tag_new(&chip_data, RFID_TAG_VERSION_1, &tag));
+
+  CHECK(rfid_tag_add_measurement(tag, RFID_MEASUREMENT_RING_OSCILLATOR, 1.5e9f));
+  if (rfid_tag_add_measurement(tag, 0, 1.0f) != RFID_STATUS_INVALID_MEASUREMENT) {
+    fprintf(stderr, "a measurement of an unknown type was accepted\n");
+    return 1;
+  }
+
+  size_t len = 0;
+  if (rfid_tag_encode(tag, NULL, 0, &len) != RFID_STATUS_BUFFER_TOO_SMALL) {
+    fprintf(stderr, "sizing the encoded tag did not report its length\n");
+    return 1;
+  }
+
+  uint8_t bytes[512];
+  if (len > sizeof(bytes)) {
+    fprintf(stderr, "encoded tag is %zu bytes\n", len);
+    return 1;
+  }
+  CHECK(rfid_tag_encode(tag, bytes, sizeof(bytes), &len));
+  rfid_tag_free(tag);
+
+  RfidTag *parsed = NULL;
+  CHECK(rfid_tag_parse(bytes, len, &parsed));
+
+  RfidChipData parsed_chip_data;
+  CHECK(rfid_tag_chip_data(parsed, &parsed_chip_data));
+  if (memcmp(parsed_chip_data.chip_id, chip_data.chip_id, 16) != 0 ||
+      rfid_tag_entry_count(parsed) != 0 || !rfid_tag_valid_crc(parsed)) {
+    fprintf(stderr, "parsed tag does not match the encoded one\n");
+    return 1;
+  }
+
+  RfidMeasurement measurement;
+  if (rfid_tag_measurement_count(parsed) != 1) {
+    fprintf(stderr, "parsed tag lost its measurement\n");
+    return 1;
+  }
+  CHECK(rfid_tag_measurement(parsed, 0, &measurement));
+  if (measurement.type_id != RFID_MEASUREMENT_RING_OSCILLATOR ||
+      !measurement.registered || measurement.value != 1.5e9f ||
+      measurement.raw_len != 4) {
+    fprintf(stderr, "parsed measurement does not match\n");
+    return 1;
+  }
+
+  RfidKeyTable *keys = rfid_key_table_new();
+  const uint8_t not_a_key[] = "not a key";
+  if (rfid_key_table_add(keys, 1, not_a_key, sizeof(not_a_key)) !=
+      RFID_STATUS_INVALID_KEY) {
+    fprintf(stderr, "an invalid key was accepted\n");
+    return 1;
+  }
+  rfid_key_table_free(keys);
+  rfid_tag_free(parsed);
+
+  if (strcmp(rfid_status_message(1000), "Unknown status") != 0) {
+    fprintf(stderr, "unexpected message for an unknown status\n");
+    return 1;
+  }
+
+  return 0;
+}
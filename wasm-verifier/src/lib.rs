@@ -0,0 +1,196 @@
+//! Tag verification for the browser.
+//!
+//! Build with `wasm-pack build wasm-verifier --target web`. Signatures are checked with pure Rust
+//! crypto when targeting wasm, natively the models crate's default backend is used.
+
+use models::error::InspectionError;
+use models::key::PublicKey;
+use models::rfid_ref::RfidDataRef;
+use models::tag_format::TagVersion;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use wasm_bindgen::prelude::*;
+
+/// Distributor keys, in the same form as the central server's `KeyResponse`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct KeySet {
+    pub keys: BTreeMap<u32, PublicKey>,
+}
+
+/// One hop of a chip's journey through the supply chain
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Hop {
+    pub dist_id: u32,
+    pub distributor_name: Option<String>,
+    /// `None` if the recipient of the last hop couldn't be determined
+    pub next_dist_id: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VerificationReport {
+    pub valid: bool,
+    /// Chip ID as a decimal string, JavaScript numbers can't hold a `u128`
+    pub chip_id: Option<String>,
+    pub version: Option<TagVersion>,
+    pub recipient: Option<u32>,
+    pub provenance: Vec<Hop>,
+    pub problems: Vec<String>,
+}
+
+impl VerificationReport {
+    fn rejected(problem: String) -> Self {
+        Self {
+            problems: vec![problem],
+            ..Default::default()
+        }
+    }
+}
+
+/// Verify tag bytes against `keys`.
+///
+/// The tag is checked as addressed to `recipient`, or to whichever distributor in `keys` the last
+/// hop was signed over to.
+pub fn verify(tag: &[u8], keys: &KeySet, recipient: Option<u32>) -> VerificationReport {
+    let tag = match RfidDataRef::parse(tag) {
+        Ok(tag) => tag,
+        Err(e) => return VerificationReport::rejected(e.to_string()),
+    };
+
+    let mut problems = Vec::new();
+    let mut recipient_key = None;
+
+    if tag.entry_count() == 0 {
+        problems.push("The tag has no entries".to_string());
+    } else if let Some(entry) = tag
+        .entries()
+        .find(|entry| !keys.keys.contains_key(&entry.pub_key))
+    {
+        problems.push(InspectionError::UnknownDistributor(entry.pub_key).to_string());
+    } else if let Some(id) = recipient.filter(|id| !keys.keys.contains_key(id)) {
+        problems.push(InspectionError::UnknownDistributor(id).to_string());
+    } else {
+        // The chain is walked once, its last hop is then checked against every key so a tag
+        // signed over to someone else is told apart from a bad signature
+        match tag.recipient(&keys.keys, keys.keys.values()) {
+            Err(ndx) => problems.push(InspectionError::InvalidSignature(ndx).to_string()),
+            Ok(found) => match (found, recipient) {
+                (Some(key), Some(id)) if key.id != id => {
+                    problems.push(InspectionError::WrongRecipient(id).to_string())
+                }
+                (Some(key), _) => recipient_key = Some(key),
+                (None, Some(_)) => problems
+                    .push(InspectionError::InvalidSignature(tag.entry_count() - 1).to_string()),
+                (None, None) => problems.push(
+                    "The last hop is not signed over to any distributor in the key set".to_string(),
+                ),
+            },
+        }
+    }
+
+    let recipient = recipient_key.map(|key| key.id);
+    let entries: Vec<_> = tag.entries().collect();
+    let provenance = entries
+        .iter()
+        .enumerate()
+        .map(|(ndx, entry)| Hop {
+            dist_id: entry.pub_key,
+            distributor_name: keys
+                .keys
+                .get(&entry.pub_key)
+                .map(|key| key.distributor_name.clone()),
+            next_dist_id: entries.get(ndx + 1).map(|next| next.pub_key).or(recipient),
+        })
+        .collect();
+
+    VerificationReport {
+        valid: problems.is_empty(),
+        chip_id: Some(tag.chip_id().to_string()),
+        version: Some(tag.version()),
+        recipient,
+        provenance,
+        problems,
+    }
+}
+
+/// Verify tag bytes against a JSON key set, returning the `VerificationReport` as an object
+#[wasm_bindgen(js_name = verifyTag)]
+pub fn verify_tag(tag: &[u8], keys_json: &str, recipient: Option<u32>) -> Result<JsValue, JsValue> {
+    let keys: KeySet = serde_json::from_str(keys_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid key set: {}", e)))?;
+
+    serde_wasm_bindgen::to_value(&verify(tag, &keys, recipient))
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{verify, KeySet};
+    use models::key::PublicKey;
+    use models::requests::key_request::KeyResponse;
+    use models::rfid::RfidBuilder;
+    use models::signer::file_key::FileKeySigner;
+    use openssl::pkey::PKey;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_verify() {
+        let keypairs: Vec<_> = (0..4).map(|_| PKey::generate_ed25519().unwrap()).collect();
+        let key_map: HashMap<u32, PublicKey> = keypairs
+            .iter()
+            .enumerate()
+            .map(|(id, keypair)| {
+                let id = id as u32;
+                let pem = keypair.public_key_to_pem().unwrap();
+                (id, PublicKey::new(id, pem, format!("dist{}", id)).unwrap())
+            })
+            .collect();
+
+        let mut builder = RfidBuilder::default().chip_data(42, 5.0, 5.0, 5.0, 5.0);
+        for id in 0..2 {
+            let signer = FileKeySigner::from_pkey(keypairs[id as usize].clone()).unwrap();
+            builder = builder.add_entry(&signer, id, id + 1, &key_map).unwrap();
+        }
+        let tag: Vec<u8> = builder.build().into();
+
+        let json = serde_json::to_string(&KeyResponse {
+            keys: key_map.clone(),
+        })
+        .unwrap();
+        let keys: KeySet = serde_json::from_str(&json).unwrap();
+
+        let report = verify(&tag, &keys, None);
+        assert!(report.valid, "{:?}", report.problems);
+        assert_eq!(report.chip_id.as_deref(), Some("42"));
+        assert_eq!(report.recipient, Some(2));
+        assert_eq!(report.provenance[1].next_dist_id, Some(2));
+        assert_eq!(
+            report.provenance[0].distributor_name.as_deref(),
+            Some("dist0")
+        );
+
+        assert!(verify(&tag, &keys, Some(2)).valid);
+        assert!(!verify(&tag, &keys, Some(3)).valid);
+        assert!(!verify(&tag, &keys, Some(9)).valid);
+
+        let mut without_recipient = keys.clone();
+        without_recipient.keys.remove(&2);
+        let report = verify(&tag, &without_recipient, None);
+        assert!(!report.valid);
+        assert_eq!(report.recipient, None);
+
+        let mut tampered = tag.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        let report = verify(&tampered, &keys, None);
+        assert!(!report.valid);
+        assert_eq!(report.chip_id, None);
+
+        let empty: Vec<u8> = RfidBuilder::default()
+            .chip_data(42, 5.0, 5.0, 5.0, 5.0)
+            .build()
+            .into();
+        let report = verify(&empty, &keys, None);
+        assert!(!report.valid);
+        assert!(report.provenance.is_empty());
+    }
+}